@@ -1,4 +1,6 @@
 use rad_db_structure::identifier::Identifier;
+use rad_db_types::conversion::FromValueError;
+use std::error::Error;
 use std::fmt::{Display, Formatter};
 macro_rules! quick_error {
     ($error:ty) => {
@@ -31,4 +33,48 @@ pub struct MissingFieldError { field: Identifier }
 
  */
 
-quick_error!{ MissingFieldError; field: Identifier }
\ No newline at end of file
+quick_error!{ MissingFieldError; field: Identifier }
+
+/// Why a field of a tuple couldn't be read as a Rust type
+#[derive(Debug)]
+pub enum FieldAccessError {
+    /// There is no field with the identifier
+    Missing(Identifier),
+    /// The value of the field is a different type
+    Mismatch {
+        field: Identifier,
+        cause: FromValueError,
+    },
+    /// The value of the field is NULL, and the type can't be NULL
+    Null(Identifier),
+}
+
+impl FieldAccessError {
+    /// Creates an error for a value that couldn't be converted
+    pub fn conversion(field: Identifier, cause: FromValueError) -> Self {
+        if cause.is_null() {
+            FieldAccessError::Null(field)
+        } else {
+            FieldAccessError::Mismatch { field, cause }
+        }
+    }
+}
+
+impl Display for FieldAccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldAccessError::Missing(field) => write!(f, "No field named {}", field),
+            FieldAccessError::Mismatch { field, cause } => write!(f, "Field {}: {}", field, cause),
+            FieldAccessError::Null(field) => write!(f, "Field {} is NULL", field),
+        }
+    }
+}
+
+impl Error for FieldAccessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FieldAccessError::Mismatch { cause, .. } => Some(cause),
+            _ => None,
+        }
+    }
+}
@@ -2,8 +2,9 @@ use crate::query::query_node::QueryNode;
 use crate::wrapped_tuple::WrappedTuple;
 use rad_db_structure::identifier::Identifier;
 use rad_db_structure::tuple::Tuple;
-use rad_db_types::Value;
-use std::cmp::min;
+use rad_db_types::collation::NullOrdering;
+use rad_db_types::{Numeric, Text, Value};
+use std::cmp::{min, Ordering};
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::iter::FromIterator;
@@ -42,6 +43,10 @@ pub enum Operand {
 pub enum ConditionOperation {
     Equals(Operand),
     Nequals(Operand),
+    LessThan(Operand),
+    LessThanEquals(Operand),
+    GreaterThan(Operand),
+    GreaterThanEquals(Operand),
     And(Box<ConditionOperation>, Box<Condition>),
     Or(Box<ConditionOperation>, Box<Condition>),
 }
@@ -74,6 +79,10 @@ impl ConditionOperation {
         let ret = match self {
             ConditionOperation::Equals(_) => 1.0 / max_tuples as f64,
             ConditionOperation::Nequals(_) => 1.0 - 1.0 / max_tuples as f64,
+            ConditionOperation::LessThan(_)
+            | ConditionOperation::LessThanEquals(_)
+            | ConditionOperation::GreaterThan(_)
+            | ConditionOperation::GreaterThanEquals(_) => 1.0 / 3.0,
             ConditionOperation::And(c, r) => c.selectivity(max_tuples) * r.selectivity(max_tuples),
             ConditionOperation::Or(c, r) => {
                 min_float!(c.selectivity(max_tuples) + r.selectivity(max_tuples), 1.0)
@@ -92,8 +101,14 @@ impl ConditionOperation {
 
     fn relevant_fields(&self) -> HashSet<Identifier> {
         match &self {
-            ConditionOperation::Equals(Operand::Id(id)) => HashSet::from_iter(vec![id.clone()]),
-            ConditionOperation::Nequals(Operand::Id(id)) => HashSet::from_iter(vec![id.clone()]),
+            ConditionOperation::Equals(Operand::Id(id))
+            | ConditionOperation::Nequals(Operand::Id(id))
+            | ConditionOperation::LessThan(Operand::Id(id))
+            | ConditionOperation::LessThanEquals(Operand::Id(id))
+            | ConditionOperation::GreaterThan(Operand::Id(id))
+            | ConditionOperation::GreaterThanEquals(Operand::Id(id)) => {
+                HashSet::from_iter(vec![id.clone()])
+            }
             ConditionOperation::And(left, more) => {
                 let mut relevant = left.relevant_fields();
                 relevant.extend(more.relevant_fields());
@@ -108,35 +123,59 @@ impl ConditionOperation {
         }
     }
 
-    fn evaluate_on(&self, compare: Value, tuple: &WrappedTuple) -> Result<bool, InvalidOperation> {
+    fn evaluate_on(&self, compare: &Value, tuple: &WrappedTuple) -> Result<bool, InvalidOperation> {
+        let test = |operand: &Operand, test: fn(Ordering) -> bool| {
+            operand
+                .compare(compare, tuple)
+                .map(|ordering| ordering.map_or(false, test))
+        };
         match self {
-            ConditionOperation::Equals(eq) => match eq {
-                Operand::Id(id) => {
-                    let right = &tuple[id];
-                    Ok(&compare == right)
-                }
-                Operand::SignedNumber(signed) => {
-                    let number = i64::try_from(compare).map_err(|_| InvalidOperation)?;
-                    Ok(*signed == number)
-                }
-                Operand::UnsignedNumber(unsigned) => {
-                    let number = u64::try_from(compare).map_err(|_| InvalidOperation)?;
-                    Ok(*unsigned == number)
-                }
-                Operand::Float(f) => {
-                    let number = f64::try_from(compare).map_err(|_| InvalidOperation)?;
-                    Ok(*f == number)
-                }
-                Operand::String(_) => {}
-                Operand::Boolean(_) => {}
-            },
-            ConditionOperation::Nequals(neq) => {}
-            ConditionOperation::And(_, _) => {}
-            ConditionOperation::Or(_, _) => {}
+            ConditionOperation::Equals(eq) => test(eq, Ordering::is_eq),
+            ConditionOperation::Nequals(neq) => test(neq, Ordering::is_ne),
+            ConditionOperation::LessThan(lt) => test(lt, Ordering::is_lt),
+            ConditionOperation::LessThanEquals(le) => test(le, Ordering::is_le),
+            ConditionOperation::GreaterThan(gt) => test(gt, Ordering::is_gt),
+            ConditionOperation::GreaterThanEquals(ge) => test(ge, Ordering::is_ge),
+            ConditionOperation::And(left, right) => {
+                Ok(left.evaluate_on(compare, tuple)? && right.evaluate_on(tuple)?)
+            }
+            ConditionOperation::Or(left, right) => {
+                Ok(left.evaluate_on(compare, tuple)? || right.evaluate_on(tuple)?)
+            }
         }
     }
 }
 
+impl Operand {
+    /// Compares a value to this operand using the value collation. If either side is NULL, the
+    /// comparison has no result.
+    fn compare(
+        &self,
+        compare: &Value,
+        tuple: &WrappedTuple,
+    ) -> Result<Option<Ordering>, InvalidOperation> {
+        let operand = match self {
+            Operand::Id(id) => tuple[id].clone(),
+            Operand::SignedNumber(signed) => Value::from(*signed),
+            Operand::UnsignedNumber(unsigned) => Value::from(*unsigned),
+            Operand::Float(f) => Numeric::Double(*f).into(),
+            Operand::String(string) => Value::from(string.clone()),
+            Operand::Char(c) => Text::Char(*c).into(),
+            Operand::Boolean(b) => Value::from(*b),
+        };
+        if let Value::Optional(None) = compare {
+            return Ok(None);
+        }
+        if let Value::Optional(None) = operand {
+            return Ok(None);
+        }
+        if !compare.comparable(&operand) {
+            return Err(InvalidOperation);
+        }
+        Ok(Some(compare.collate(&operand, NullOrdering::default())))
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Condition {
     base: Identifier,
@@ -199,16 +238,10 @@ impl Condition {
         }
     }
 
-    pub fn evaluate_on(&self, tuple: WrappedTuple) -> bool {
+    /// Evaluates the condition on a tuple
+    pub fn evaluate_on(&self, tuple: &WrappedTuple) -> Result<bool, InvalidOperation> {
         let left_value = &tuple[&self.base];
-        let right_value: Operand = {
-            match &self.operation {
-                ConditionOperation::Equals(eq) => eq.clone(),
-                ConditionOperation::Nequals(neq) => neq.clone(),
-                ConditionOperation::And(l, r) => {}
-                ConditionOperation::Or(_, _) => {}
-            }
-        };
+        self.operation.evaluate_on(left_value, tuple)
     }
 }
 
@@ -221,6 +254,7 @@ impl<I: Into<Identifier>> From<I> for Operand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rad_db_types::Decimal;
 
     #[test]
     fn split_and() {
@@ -329,4 +363,78 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn evaluate_decimal() {
+        let fields = vec![Identifier::new("price"), Identifier::new("quantity")];
+        let tuple = Tuple::from_iter(&[
+            Value::from(Decimal::parse("12.00", 5, 2).unwrap()),
+            Value::from(12i64),
+        ]);
+        let wrapped = WrappedTuple::new(&fields, &tuple);
+
+        let equals_signed = Condition::new(
+            "price",
+            ConditionOperation::Equals(Operand::SignedNumber(12)),
+        );
+        assert!(equals_signed.evaluate_on(&wrapped).unwrap());
+        let equals_float =
+            Condition::new("price", ConditionOperation::Equals(Operand::Float(12.5)));
+        assert!(!equals_float.evaluate_on(&wrapped).unwrap());
+        let not_equals = Condition::new(
+            "price",
+            ConditionOperation::Nequals(Operand::UnsignedNumber(13)),
+        );
+        assert!(not_equals.evaluate_on(&wrapped).unwrap());
+        let and = Condition::and(
+            equals_signed,
+            Condition::new(
+                "quantity",
+                ConditionOperation::Equals(Operand::SignedNumber(12)),
+            ),
+        );
+        assert!(and.evaluate_on(&wrapped).unwrap());
+        let mismatched = Condition::new(
+            "quantity",
+            ConditionOperation::Equals(Operand::Boolean(true)),
+        );
+        assert!(mismatched.evaluate_on(&wrapped).is_err());
+    }
+
+    #[test]
+    fn evaluate_range() {
+        let fields = vec![
+            Identifier::new("low"),
+            Identifier::new("high"),
+            Identifier::new("none"),
+        ];
+        let tuple =
+            Tuple::from_iter(&[Value::from(3u8), Value::from(-7i64), Value::Optional(None)]);
+        let wrapped = WrappedTuple::new(&fields, &tuple);
+
+        let less = Condition::new("high", ConditionOperation::LessThan(Operand::from("low")));
+        assert!(less.evaluate_on(&wrapped).unwrap());
+        let greater = Condition::new("low", ConditionOperation::GreaterThan(Operand::Float(2.5)));
+        assert!(greater.evaluate_on(&wrapped).unwrap());
+        let at_most = Condition::new(
+            "low",
+            ConditionOperation::LessThanEquals(Operand::UnsignedNumber(3)),
+        );
+        assert!(at_most.evaluate_on(&wrapped).unwrap());
+        let at_least = Condition::new(
+            "high",
+            ConditionOperation::GreaterThanEquals(Operand::SignedNumber(0)),
+        );
+        assert!(!at_least.evaluate_on(&wrapped).unwrap());
+        let null = Condition::new(
+            "none",
+            ConditionOperation::LessThan(Operand::SignedNumber(0)),
+        );
+        assert!(!null.evaluate_on(&wrapped).unwrap());
+        let text = Condition::new(
+            "low",
+            ConditionOperation::LessThan(Operand::String("a".to_string())),
+        );
+        assert!(text.evaluate_on(&wrapped).is_err());
+    }
 }
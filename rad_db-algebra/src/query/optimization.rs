@@ -413,20 +413,22 @@ mod tests {
     use super::*;
     use crate::query::conditions::{Condition, ConditionOperation, Operand};
     use rad_db_structure::prelude::*;
-    use rad_db_types::{Type, Value};
+    use rad_db_types::{DataType, Value};
     use std::iter::FromIterator;
 
     #[test]
     fn and_equivalence() {
         let mut relation1 = Relation::new_volatile(
             Identifier::new("test1"),
-            vec![("field1", Type::from(0u64))],
+            vec![("field1", DataType::UnsignedLong)],
             64,
             PrimaryKeyDefinition::new(vec![0]),
         );
         for i in 0..1000u64 {
             //println!("Inserting tuple {}", i);
-            relation1.insert(Tuple::from_iter(&[Value::from(i)]));
+            relation1
+                .insert(Tuple::from_iter(&[Value::from(i)]))
+                .unwrap();
         }
         let query = QueryNode::select_on_condition(
             QueryNode::source(&relation1),
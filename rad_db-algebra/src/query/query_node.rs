@@ -8,7 +8,7 @@ use rad_db_structure::identifier::Identifier;
 use rad_db_structure::relations::tuple_storage::{BlockIterator, StoredTupleIterator};
 use rad_db_structure::relations::Relation;
 use rad_db_structure::tuple::Tuple;
-use rad_db_types::{Type, Value};
+use rad_db_types::{DataType, Value};
 use std::cmp::max;
 use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
@@ -101,7 +101,7 @@ pub enum QueryChildren<'a> {
 pub struct QueryNode<'a> {
     query: QueryOperation<'a>,
     children: Box<QueryChildren<'a>>,
-    resulting_relation: Vec<(Identifier, Type)>,
+    resulting_relation: Vec<(Identifier, DataType)>,
     mapping: HashMap<Identifier, Identifier>,
     id: usize,
 }
@@ -702,23 +702,27 @@ mod join_tests {
     fn cross_product() {
         let mut relation1 = Relation::new_volatile(
             Identifier::new("test1"),
-            vec![("field1", Type::from(0u64))],
+            vec![("field1", DataType::UnsignedLong)],
             64,
             PrimaryKeyDefinition::new(vec![0]),
         );
         for i in 0..100u64 {
             //println!("Inserting tuple {}", i);
-            relation1.insert(Tuple::from_iter(&[Value::from(i)]));
+            relation1
+                .insert(Tuple::from_iter(&[Value::from(i)]))
+                .unwrap();
         }
         let mut relation2 = Relation::new_volatile(
             Identifier::new("test2"),
-            vec![("field1", Type::from(0u64))],
+            vec![("field1", DataType::UnsignedLong)],
             64,
             PrimaryKeyDefinition::new(vec![0]),
         );
         for i in 0..100u64 {
             //println!("Inserting tuple {}", i);
-            relation2.insert(Tuple::from_iter(&[Value::from(i)]));
+            relation2
+                .insert(Tuple::from_iter(&[Value::from(i)]))
+                .unwrap();
         }
 
         let mut query_node =
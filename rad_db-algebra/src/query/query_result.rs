@@ -1,3 +1,4 @@
+use crate::error::FieldAccessError;
 use crate::query::query_iterator::{QueryIterator, ReferencedQueryIterator};
 use crate::query::query_node::Source;
 use crate::query::Repeatable;
@@ -5,7 +6,8 @@ use rad_db_structure::identifier::Identifier;
 use rad_db_structure::relations::tuple_storage::BlockIterator;
 use rad_db_structure::relations::RelationDefinition;
 use rad_db_structure::tuple::Tuple;
-use rad_db_types::{Type, Value};
+use rad_db_types::conversion::FromValue;
+use rad_db_types::{DataType, Value};
 use std::collections::HashMap;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
@@ -21,14 +23,14 @@ pub enum QueryResultBlocks<'a> {
 }
 
 pub struct QueryResult<'a> {
-    relation: Vec<(Identifier, Type)>,
+    relation: Vec<(Identifier, DataType)>,
     internal: QueryResultFullData<'a>,
     total_created_tuples: usize,
 }
 const ITEMS_PER_BLOCK: usize = 16;
 impl<'a> QueryResult<'a> {
     pub fn with_tuples<I: IntoIterator<Item = Tuple>>(
-        relation: Vec<(Identifier, Type)>,
+        relation: Vec<(Identifier, DataType)>,
         tuples: I,
         extra: usize,
     ) -> Self {
@@ -41,7 +43,7 @@ impl<'a> QueryResult<'a> {
         }
     }
 
-    pub fn from_source(relation: Vec<(Identifier, Type)>, source: Source<'a>) -> Self {
+    pub fn from_source(relation: Vec<(Identifier, DataType)>, source: Source<'a>) -> Self {
         let len = source.source_len();
         QueryResult {
             relation,
@@ -50,7 +52,7 @@ impl<'a> QueryResult<'a> {
         }
     }
 
-    pub fn relation(&self) -> &Vec<(Identifier, Type)> {
+    pub fn relation(&self) -> &Vec<(Identifier, DataType)> {
         &self.relation
     }
 
@@ -128,6 +130,30 @@ impl<'a> QueryResult<'a> {
         }
     }
 
+    /// Gets the value of a field of a row of this result as a Rust type
+    pub fn get<T: FromValue, I: Into<Identifier>>(
+        &self,
+        tuple: &Tuple,
+        field: I,
+    ) -> Result<T, FieldAccessError> {
+        let id = field.into();
+        match self.get_value_in_tuple(&id, tuple) {
+            None => Err(FieldAccessError::Missing(id)),
+            Some(value) => {
+                T::from_value(value.clone()).map_err(|e| FieldAccessError::conversion(id, e))
+            }
+        }
+    }
+
+    /// Gets the value of a field of a row of this result as a Rust type, where NULL is `None`
+    pub fn get_opt<T: FromValue, I: Into<Identifier>>(
+        &self,
+        tuple: &Tuple,
+        field: I,
+    ) -> Result<Option<T>, FieldAccessError> {
+        self.get::<Option<T>, I>(tuple, field)
+    }
+
     /// Gets the identifier to index mapping of this tuple
     pub fn identifier_mappings(&self) -> HashMap<Identifier, usize> {
         self.relation
@@ -179,3 +205,54 @@ impl<'a> IntoIterator for &'a QueryResult<'a> {
         ReferencedQueryIterator::new(&self.internal)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rad_db_types::Type;
+
+    #[test]
+    fn typed_rows() {
+        let relation = vec![
+            (Identifier::parse("orders::id"), DataType::UnsignedLong),
+            (
+                Identifier::parse("orders::note"),
+                DataType::String(None).optional(),
+            ),
+        ];
+        let tuples = vec![
+            Tuple::new(vec![Type::from(1u64), Type::Optional(None)]),
+            Tuple::new(vec![
+                Type::from(2u64),
+                Type::Optional(Some(Box::new(Type::from("rush")))),
+            ]),
+        ];
+        let result = QueryResult::with_tuples(relation, tuples.clone(), 0);
+        assert_eq!(
+            result
+                .get::<u64, _>(&tuples[0], Identifier::parse("orders::id"))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            result
+                .get_opt::<String, _>(&tuples[0], Identifier::parse("orders::note"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            result
+                .get::<String, _>(&tuples[1], Identifier::parse("orders::note"))
+                .unwrap(),
+            "rush"
+        );
+        assert!(matches!(
+            result.get::<String, _>(&tuples[0], Identifier::parse("orders::note")),
+            Err(FieldAccessError::Null(_))
+        ));
+        assert!(matches!(
+            result.get::<u64, _>(&tuples[0], "id"),
+            Err(FieldAccessError::Missing(_))
+        ));
+    }
+}
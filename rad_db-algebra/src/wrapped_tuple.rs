@@ -1,5 +1,7 @@
+use crate::error::FieldAccessError;
 use rad_db_structure::identifier::Identifier;
 use rad_db_structure::tuple::Tuple;
+use rad_db_types::conversion::FromValue;
 use rad_db_types::Value;
 use std::ops::Index;
 use std::sync::Arc;
@@ -14,19 +16,91 @@ impl<'a> WrappedTuple<'a> {
     pub fn new(fields: &'a Vec<Identifier>, tuple: &'a Tuple) -> Self {
         WrappedTuple { fields, tuple }
     }
+
+    /// Gets the value of a field
+    pub fn value<I: Into<Identifier>>(&self, field: I) -> Result<&'a Value, FieldAccessError> {
+        let id = field.into();
+        match self.fields.iter().position(|f| f == &id) {
+            None => Err(FieldAccessError::Missing(id)),
+            Some(index) => Ok(&self.tuple[index]),
+        }
+    }
+
+    /// Gets the value of a field as a Rust type
+    ///
+    /// # Example
+    /// `tuple.get::<i64, _>(Identifier::parse("orders::total"))`
+    pub fn get<T: FromValue, I: Into<Identifier>>(&self, field: I) -> Result<T, FieldAccessError> {
+        let id = field.into();
+        let value = self.value(id.clone())?;
+        T::from_value(value.clone()).map_err(|e| FieldAccessError::conversion(id, e))
+    }
+
+    /// Gets the value of a field as a Rust type, where NULL is `None`
+    pub fn get_opt<T: FromValue, I: Into<Identifier>>(
+        &self,
+        field: I,
+    ) -> Result<Option<T>, FieldAccessError> {
+        self.get::<Option<T>, I>(field)
+    }
 }
 
 impl<'a, I: Into<Identifier>> Index<I> for WrappedTuple<'a> {
     type Output = Value;
 
     fn index(&self, index: I) -> &Self::Output {
-        let id = index.into();
-        let pos = self.fields.iter().position(|f| f == id);
-        match pos {
-            None => {
-                panic!("No field named {} in this tuple", id)
-            }
-            Some(index) => &self.tuple[index],
+        match self.value(index) {
+            Ok(value) => value,
+            Err(e) => panic!("{} in this tuple", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rad_db_types::Type;
+
+    #[test]
+    fn typed_access() {
+        let fields = vec![
+            Identifier::parse("orders::total"),
+            Identifier::new("note"),
+            Identifier::new("count"),
+        ];
+        let tuple = Tuple::new(vec![
+            Type::from(15i64),
+            Type::Optional(None),
+            Type::Optional(Some(Box::new(Type::from(2u8)))),
+        ]);
+        let wrapped = WrappedTuple::new(&fields, &tuple);
+        assert_eq!(
+            wrapped
+                .get::<i64, _>(Identifier::parse("orders::total"))
+                .unwrap(),
+            15
+        );
+        assert_eq!(
+            wrapped
+                .get::<Value, _>(Identifier::parse("orders::total"))
+                .unwrap(),
+            Type::from(15i64)
+        );
+        assert_eq!(wrapped.get_opt::<String, _>("note").unwrap(), None);
+        assert_eq!(wrapped.get::<u8, _>("count").unwrap(), 2);
+        assert_eq!(wrapped.get_opt::<u8, _>("count").unwrap(), Some(2));
+
+        match wrapped.get::<String, _>("missing") {
+            Err(FieldAccessError::Missing(id)) => assert_eq!(id, Identifier::new("missing")),
+            other => panic!("unexpected result {:?}", other),
         }
+        assert!(matches!(
+            wrapped.get::<String, _>("note"),
+            Err(FieldAccessError::Null(_))
+        ));
+        assert!(matches!(
+            wrapped.get::<u64, _>(Identifier::parse("orders::total")),
+            Err(FieldAccessError::Mismatch { .. })
+        ));
     }
 }
@@ -1,7 +1,20 @@
 use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod record;
 
 #[proc_macro_attribute]
 pub fn type_tree(_attr: TokenStream, item: TokenStream) -> TokenStream {
     println!("{:?}", item);
     item
 }
+
+/// Maps a struct with named fields to a tuple. See `rad_db_structure::record` for the attributes
+/// that can be used on the fields.
+#[proc_macro_derive(Record, attributes(key, rename))]
+pub fn derive_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    record::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
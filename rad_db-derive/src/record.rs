@@ -0,0 +1,142 @@
+//! The implementation of `#[derive(Record)]`
+
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Error, Expr, ExprLit, Field, Fields, Lit, Result, Type};
+
+/// A field of the struct, and the attribute it's stored as
+struct RecordField {
+    ident: Ident,
+    ty: Type,
+    name: String,
+    key: bool,
+}
+
+fn parse_field(field: &Field) -> Result<RecordField> {
+    let ident = field.ident.clone().expect("fields must be named");
+    let mut name = ident.unraw().to_string();
+    let mut key = false;
+    for attr in &field.attrs {
+        if attr.path().is_ident("key") {
+            attr.meta.require_path_only()?;
+            key = true;
+        } else if attr.path().is_ident("rename") {
+            match &attr.meta.require_name_value()?.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(rename),
+                    ..
+                }) => name = rename.value(),
+                other => {
+                    return Err(Error::new_spanned(
+                        other,
+                        "expected the new name as a string, such as #[rename = \"name\"]",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(RecordField {
+        ident,
+        ty: field.ty.clone(),
+        name,
+        key,
+    })
+}
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Record can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Record can only be derived for structs",
+            ))
+        }
+    };
+    let fields = fields.iter().map(parse_field).collect::<Result<Vec<_>>>()?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let count = fields.len();
+    let idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let names: Vec<_> = fields.iter().map(|field| &field.name).collect();
+    let mut keys: Vec<usize> = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field.key)
+        .map(|(index, _)| index)
+        .collect();
+    if keys.is_empty() {
+        keys = (0..count).collect();
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#ident #ty_generics>
+            for ::rad_db_structure::tuple::Tuple #where_clause
+        {
+            fn from(record: #ident #ty_generics) -> Self {
+                ::rad_db_structure::tuple::Tuple::new(::std::vec![
+                    #(::rad_db_structure::record::ToValue::to_value(record.#idents)),*
+                ])
+            }
+        }
+
+        impl #impl_generics ::std::convert::TryFrom<::rad_db_structure::tuple::Tuple>
+            for #ident #ty_generics #where_clause
+        {
+            type Error = ::rad_db_structure::record::RecordError;
+
+            #[allow(unused_mut, unused_variables)]
+            fn try_from(
+                tuple: ::rad_db_structure::tuple::Tuple,
+            ) -> ::std::result::Result<Self, Self::Error> {
+                if tuple.len() != #count {
+                    return ::std::result::Result::Err(
+                        ::rad_db_structure::record::RecordError::WrongArity {
+                            expected: #count,
+                            found: tuple.len(),
+                        },
+                    );
+                }
+                let mut values = tuple.into_iter();
+                ::std::result::Result::Ok(#ident {
+                    #(#idents: ::rad_db_structure::record::FromValue::from_value(
+                        values.next().unwrap(),
+                    )
+                    .map_err(|cause| ::rad_db_structure::record::RecordError::Field {
+                        name: #names,
+                        cause,
+                    })?,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::rad_db_structure::record::Record for #ident #ty_generics #where_clause {
+            fn attributes() -> ::std::vec::Vec<(
+                ::std::string::String,
+                ::rad_db_structure::record::DataType,
+            )> {
+                ::std::vec![
+                    #((
+                        ::std::string::String::from(#names),
+                        <#types as ::rad_db_structure::record::ToValue>::data_type(),
+                    )),*
+                ]
+            }
+
+            fn primary_key() -> ::rad_db_structure::key::primary::PrimaryKeyDefinition {
+                ::rad_db_structure::key::primary::PrimaryKeyDefinition::new(::std::vec![#(#keys),*])
+            }
+        }
+    })
+}
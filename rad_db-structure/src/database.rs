@@ -0,0 +1,560 @@
+//! A database, which owns a catalog of named relations.
+//!
+//! Every relation of a database is stored within the namespace of the database, so the relation
+//! `employees` of the database `company` is saved as `company::employees`. Relations are always
+//! looked up by their name within the database. The name of a relation can't be within the
+//! namespace of another relation's name, such as `archive::old` next to `archive`, as its files
+//! would be saved inside the directory of the other relation. The names of the relations are kept
+//! in a catalog file next to the relations, one per line, and every relation in the catalog is
+//! reopened when the database is opened.
+//!
+//! The database and its relations are saved under the root of its [StorageConfig]. A relation can
+//! instead be created in one of the tablespaces of the configuration, in which case the name of the
+//! tablespace follows its name in the catalog, separated by a tab. The tablespace must still be in
+//! the configuration when the database is reopened.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use rad_db_types::DataType;
+
+use crate::identifier::Identifier;
+use crate::key::primary::PrimaryKeyDefinition;
+use crate::relations::tuple_storage::OpenError;
+use crate::relations::Relation;
+use crate::storage::{atomic_write, storage_path, StorageConfig};
+use crate::transaction::{Transaction, TransactionResult};
+
+/// The name of the catalog file in the directory of a database
+pub const CATALOG_FILE: &str = "catalog.txt";
+
+/// When an operation on a database failed
+#[derive(Debug)]
+pub enum DatabaseError {
+    /// There's already a relation with the name
+    AlreadyExists(Identifier),
+    /// There's no relation with the name
+    NotFound(Identifier),
+    /// The name is within the namespace of another relation's name, or the other way around, so
+    /// the files of one relation would be saved inside the directory of the other
+    Nested {
+        name: Identifier,
+        existing: Identifier,
+    },
+    /// The storage configuration doesn't have a tablespace with the name
+    UnknownTablespace(String),
+    /// A relation in the catalog couldn't be reopened
+    Open(Identifier, OpenError),
+    /// The catalog or the files of a relation couldn't be changed
+    Io(std::io::Error),
+}
+
+impl Display for DatabaseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::AlreadyExists(name) => write!(f, "Relation {} already exists", name),
+            DatabaseError::NotFound(name) => write!(f, "Relation {} doesn't exist", name),
+            DatabaseError::Nested { name, existing } => {
+                write!(f, "Relation {} is nested with relation {}", name, existing)
+            }
+            DatabaseError::UnknownTablespace(name) => {
+                write!(f, "Tablespace {} doesn't exist", name)
+            }
+            DatabaseError::Open(name, e) => write!(f, "Couldn't open relation {}: {}", name, e),
+            DatabaseError::Io(e) => write!(f, "Couldn't update database: {}", e),
+        }
+    }
+}
+
+impl Error for DatabaseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DatabaseError::Open(_, e) => Some(e),
+            DatabaseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for DatabaseError {
+    fn from(e: std::io::Error) -> Self {
+        DatabaseError::Io(e)
+    }
+}
+
+pub type DatabaseResult<T> = Result<T, DatabaseError>;
+
+/// A collection of named relations that are saved to the file system together
+pub struct Database {
+    name: Identifier,
+    config: StorageConfig,
+    relations: HashMap<Identifier, Relation>,
+    /// The tablespaces of the relations that aren't saved under the root
+    tablespaces: HashMap<Identifier, String>,
+}
+
+impl Database {
+    /// Opens the database with the name under the default storage root, reopening every relation
+    /// in its catalog. A database that doesn't exist yet is created without any relations.
+    pub fn open<I: Into<Identifier>>(name: I) -> DatabaseResult<Self> {
+        Self::open_with(name, StorageConfig::default())
+    }
+
+    /// Opens the database with the name using the storage configuration
+    pub fn open_with<I: Into<Identifier>>(name: I, config: StorageConfig) -> DatabaseResult<Self> {
+        let name = name.into();
+        let catalog = match std::fs::read_to_string(Self::catalog_path(&config, &name)) {
+            Ok(catalog) => catalog,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut relations = HashMap::new();
+        let mut tablespaces = HashMap::new();
+        for line in catalog.lines().filter(|line| !line.is_empty()) {
+            let mut fields = line.splitn(2, '\t');
+            let relation_name = Identifier::parse(fields.next().unwrap());
+            let tablespace = fields.next();
+            let root = Self::resolve_root(&config, tablespace)?;
+            let full_name = Identifier::concat(&name, &relation_name);
+            let relation = Relation::open_in(root, full_name)
+                .map_err(|e| DatabaseError::Open(relation_name.clone(), e))?;
+            if let Some(tablespace) = tablespace {
+                tablespaces.insert(relation_name.clone(), tablespace.to_string());
+            }
+            relations.insert(relation_name, relation);
+        }
+        let ret = Database {
+            name,
+            config,
+            relations,
+            tablespaces,
+        };
+        ret.save_catalog()?;
+        Ok(ret)
+    }
+
+    /// Gets the name of the database
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    /// Gets the storage configuration of the database
+    pub fn config(&self) -> &StorageConfig {
+        &self.config
+    }
+
+    /// Creates a relation in the database, which is saved as `database::name`
+    pub fn create_relation<N, S, I>(
+        &mut self,
+        name: N,
+        attributes: I,
+        bucket_size: usize,
+        primary_key: PrimaryKeyDefinition,
+    ) -> DatabaseResult<&mut Relation>
+    where
+        N: Into<Identifier>,
+        S: ToString,
+        I: IntoIterator<Item = (S, DataType)>,
+    {
+        self.create(None, name.into(), attributes, bucket_size, primary_key)
+    }
+
+    /// Creates a relation in the database that's saved in a tablespace of the storage
+    /// configuration instead of the root
+    pub fn create_relation_in<N, S, I>(
+        &mut self,
+        tablespace: &str,
+        name: N,
+        attributes: I,
+        bucket_size: usize,
+        primary_key: PrimaryKeyDefinition,
+    ) -> DatabaseResult<&mut Relation>
+    where
+        N: Into<Identifier>,
+        S: ToString,
+        I: IntoIterator<Item = (S, DataType)>,
+    {
+        self.create(
+            Some(tablespace),
+            name.into(),
+            attributes,
+            bucket_size,
+            primary_key,
+        )
+    }
+
+    fn create<S, I>(
+        &mut self,
+        tablespace: Option<&str>,
+        name: Identifier,
+        attributes: I,
+        bucket_size: usize,
+        primary_key: PrimaryKeyDefinition,
+    ) -> DatabaseResult<&mut Relation>
+    where
+        S: ToString,
+        I: IntoIterator<Item = (S, DataType)>,
+    {
+        self.check_name(&name)?;
+        let root = Self::resolve_root(&self.config, tablespace)?;
+        let full_name = Identifier::concat(&self.name, &name);
+        let relation = Relation::new_in(root, full_name, attributes, bucket_size, primary_key);
+        if let Some(tablespace) = tablespace {
+            self.tablespaces
+                .insert(name.clone(), tablespace.to_string());
+        }
+        self.relations.insert(name.clone(), relation);
+        self.save_catalog()?;
+        Ok(self.relations.get_mut(&name).unwrap())
+    }
+
+    /// Drops a relation from the database, deleting all of its saved data
+    pub fn drop_relation<N: Into<Identifier>>(&mut self, name: N) -> DatabaseResult<()> {
+        let name = name.into();
+        let relation = self
+            .relations
+            .remove(&name)
+            .ok_or_else(|| DatabaseError::NotFound(name.clone()))?;
+        self.tablespaces.remove(&name);
+        let path = storage_path(relation.storage_root(), relation.name());
+        std::mem::drop(relation);
+        self.save_catalog()?;
+        std::fs::remove_dir_all(path)?;
+        Ok(())
+    }
+
+    /// Renames a relation of the database. The relation is saved, and its files are moved to the
+    /// new name within the same tablespace. If the files can't be moved, the relation is reopened
+    /// under its old name.
+    pub fn rename_relation<N1, N2>(&mut self, from: N1, to: N2) -> DatabaseResult<()>
+    where
+        N1: Into<Identifier>,
+        N2: Into<Identifier>,
+    {
+        let from = from.into();
+        let to = to.into();
+        self.check_name(&to)?;
+        let relation = self
+            .relations
+            .remove(&from)
+            .ok_or_else(|| DatabaseError::NotFound(from.clone()))?;
+        let root = relation.storage_root().to_path_buf();
+        let old_name = relation.name().clone();
+        let old_path = storage_path(&root, &old_name);
+        // The relation is saved when it's dropped, and its files can only be moved after that
+        std::mem::drop(relation);
+
+        let full_name = Identifier::concat(&self.name, &to);
+        let new_path = storage_path(&root, &full_name);
+        let moved = std::fs::create_dir_all(new_path.parent().unwrap())
+            .and_then(|_| std::fs::rename(&old_path, &new_path));
+        let reopened = match moved {
+            Ok(()) => Relation::open_in(&root, full_name).map_err(|e| {
+                let _ = std::fs::rename(&new_path, &old_path);
+                DatabaseError::Open(to.clone(), e)
+            }),
+            Err(e) => Err(e.into()),
+        };
+        let relation = match reopened {
+            Ok(relation) => relation,
+            Err(e) => {
+                let relation = Relation::open_in(root, old_name)
+                    .map_err(|e| DatabaseError::Open(from.clone(), e))?;
+                self.relations.insert(from, relation);
+                return Err(e);
+            }
+        };
+        if let Some(tablespace) = self.tablespaces.remove(&from) {
+            self.tablespaces.insert(to.clone(), tablespace);
+        }
+        self.relations.insert(to, relation);
+        self.save_catalog()?;
+        Ok(())
+    }
+
+    /// Gets the relation with the name
+    pub fn relation<N: Into<Identifier>>(&self, name: N) -> Option<&Relation> {
+        self.relations.get(&name.into())
+    }
+
+    /// Gets mutable access to the relation with the name
+    pub fn relation_mut<N: Into<Identifier>>(&mut self, name: N) -> Option<&mut Relation> {
+        self.relations.get_mut(&name.into())
+    }
+
+    /// Gets the names of all of the relations in the database, in sorted order
+    pub fn relation_names(&self) -> Vec<&Identifier> {
+        let mut ret: Vec<_> = self.relations.keys().collect();
+        ret.sort_by_key(|name| name.to_string());
+        ret
+    }
+
+    /// Gets whether the database has a relation with the name
+    pub fn contains<N: Into<Identifier>>(&self, name: N) -> bool {
+        self.relations.contains_key(&name.into())
+    }
+
+    /// Commits a transaction that staged changes to relations of the database
+    pub fn commit(&mut self, transaction: Transaction) -> TransactionResult<()> {
+        let mut relations: Vec<&mut Relation> = self.relations.values_mut().collect();
+        transaction.commit(&mut relations)
+    }
+
+    /// Checks that a new relation can be given the name, which can't be the name of another
+    /// relation or be nested with one
+    fn check_name(&self, name: &Identifier) -> DatabaseResult<()> {
+        if self.relations.contains_key(name) {
+            return Err(DatabaseError::AlreadyExists(name.clone()));
+        }
+        let nested = self.relations.keys().find(|existing| {
+            // One name is within the other if the shorter one is the start of the longer one
+            existing.into_iter().zip(name).all(|(a, b)| a == b)
+        });
+        match nested {
+            Some(existing) => Err(DatabaseError::Nested {
+                name: name.clone(),
+                existing: existing.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the tablespace that a relation is saved in, which is `None` for relations under the
+    /// root
+    pub fn tablespace_of<N: Into<Identifier>>(&self, name: N) -> Option<&str> {
+        self.tablespaces.get(&name.into()).map(String::as_str)
+    }
+
+    /// Gets the root of a tablespace, or the root of the configuration without one
+    fn resolve_root<'a>(
+        config: &'a StorageConfig,
+        tablespace: Option<&str>,
+    ) -> DatabaseResult<&'a Path> {
+        match tablespace {
+            None => Ok(config.root()),
+            Some(tablespace) => config
+                .tablespace(tablespace)
+                .ok_or_else(|| DatabaseError::UnknownTablespace(tablespace.to_string())),
+        }
+    }
+
+    fn catalog_path(config: &StorageConfig, name: &Identifier) -> PathBuf {
+        let mut ret = storage_path(config.root(), name);
+        ret.push(CATALOG_FILE);
+        ret
+    }
+
+    /// Writes the names of the relations and their tablespaces to the catalog
+    fn save_catalog(&self) -> std::io::Result<()> {
+        let path = Self::catalog_path(&self.config, &self.name);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let mut catalog = String::new();
+        for name in self.relation_names() {
+            catalog.push_str(&name.to_string());
+            if let Some(tablespace) = self.tablespaces.get(name) {
+                catalog.push('\t');
+                catalog.push_str(tablespace);
+            }
+            catalog.push('\n');
+        }
+        atomic_write(path, catalog.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DEFAULT_ROOT;
+    use crate::tuple::Tuple;
+    use rad_db_types::Type;
+
+    fn row(id: u32, name: &str) -> Tuple {
+        Tuple::new(vec![Type::from(id), Type::from(name)])
+    }
+
+    #[test]
+    fn catalog() {
+        let root = std::env::temp_dir().join(format!("rad_db_catalog_test_{}", std::process::id()));
+        let config = StorageConfig::new(&root);
+        let name = Identifier::new("catalog_test");
+        {
+            let mut database = Database::open_with(name.clone(), config.clone()).unwrap();
+            let employees = database
+                .create_relation(
+                    "employees",
+                    vec![
+                        ("id", DataType::UnsignedInt),
+                        ("name", DataType::String(None)),
+                    ],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                )
+                .unwrap();
+            assert_eq!(
+                employees.name(),
+                &Identifier::parse("catalog_test::employees")
+            );
+            for id in 0..10 {
+                employees.insert(row(id, "employee")).unwrap();
+            }
+            database
+                .create_relation(
+                    "scratch",
+                    vec![("id", DataType::UnsignedInt)],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                )
+                .unwrap();
+            match database.create_relation(
+                "scratch",
+                vec![("id", DataType::UnsignedInt)],
+                4,
+                PrimaryKeyDefinition::new(vec![0]),
+            ) {
+                Err(DatabaseError::AlreadyExists(name)) => assert_eq!(name, "scratch".into()),
+                _ => panic!("created a relation twice"),
+            }
+            database
+                .create_relation(
+                    Identifier::parse("archive::old"),
+                    vec![("id", DataType::UnsignedInt)],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                )
+                .unwrap();
+            assert!(matches!(
+                database.create_relation(
+                    "archive",
+                    vec![("id", DataType::UnsignedInt)],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                ),
+                Err(DatabaseError::Nested { .. })
+            ));
+            assert!(matches!(
+                database.rename_relation("scratch", Identifier::parse("archive::old::scratch")),
+                Err(DatabaseError::Nested { .. })
+            ));
+            database
+                .drop_relation(Identifier::parse("archive::old"))
+                .unwrap();
+            database.drop_relation("scratch").unwrap();
+            assert!(matches!(
+                database.drop_relation("scratch"),
+                Err(DatabaseError::NotFound(_))
+            ));
+            database.rename_relation("employees", "staff").unwrap();
+        }
+
+        let mut database = Database::open_with(name, config).unwrap();
+        assert_eq!(database.relation_names(), vec![&Identifier::new("staff")]);
+        assert!(database.relation("employees").is_none());
+        let staff = database.relation("staff").unwrap();
+        assert_eq!(staff.name(), &Identifier::parse("catalog_test::staff"));
+        assert_eq!(staff.len(), 10);
+        assert_eq!(staff.get([Type::from(3u32)]), Ok(Some(row(3, "employee"))));
+        database
+            .relation_mut("staff")
+            .unwrap()
+            .insert(row(10, "new"))
+            .unwrap();
+        assert_eq!(database.relation("staff").unwrap().len(), 11);
+        let mut transaction = Transaction::new();
+        let staff = database.relation("staff").unwrap();
+        transaction.insert(staff, row(11, "hired")).unwrap();
+        transaction.delete(staff, [Type::from(0u32)]).unwrap();
+        database.commit(transaction).unwrap();
+        let staff = database.relation("staff").unwrap();
+        assert_eq!(staff.len(), 11);
+        assert_eq!(staff.get([Type::from(11u32)]), Ok(Some(row(11, "hired"))));
+
+        std::mem::drop(database);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn tablespaces() {
+        let root = std::env::temp_dir().join("rad_db_tablespaces_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let config =
+            StorageConfig::new(root.join("main")).with_tablespace("fast", root.join("ssd"));
+        let name = Identifier::new("company");
+        {
+            let mut database = Database::open_with(name.clone(), config.clone()).unwrap();
+            database
+                .create_relation(
+                    "employees",
+                    vec![
+                        ("id", DataType::UnsignedInt),
+                        ("name", DataType::String(None)),
+                    ],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                )
+                .unwrap()
+                .insert(row(1, "main"))
+                .unwrap();
+            let sessions = database
+                .create_relation_in(
+                    "fast",
+                    "sessions",
+                    vec![
+                        ("id", DataType::UnsignedInt),
+                        ("name", DataType::String(None)),
+                    ],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                )
+                .unwrap();
+            assert_eq!(sessions.storage_root(), root.join("ssd"));
+            for id in 0..10 {
+                sessions.insert(row(id, "session")).unwrap();
+            }
+            assert!(matches!(
+                database.create_relation_in(
+                    "slow",
+                    "archive",
+                    vec![("id", DataType::UnsignedInt)],
+                    4,
+                    PrimaryKeyDefinition::new(vec![0]),
+                ),
+                Err(DatabaseError::UnknownTablespace(_))
+            ));
+            let blocked = root.join("ssd/company/blocked");
+            std::fs::create_dir_all(&blocked).unwrap();
+            std::fs::write(blocked.join("file.txt"), "").unwrap();
+            assert!(matches!(
+                database.rename_relation("sessions", "blocked"),
+                Err(DatabaseError::Io(_))
+            ));
+            assert!(database.relation("blocked").is_none());
+            assert_eq!(database.relation("sessions").unwrap().len(), 10);
+            database.rename_relation("sessions", "logins").unwrap();
+        }
+        assert!(root.join("main/company/employees/manifest.txt").exists());
+        assert!(root.join("ssd/company/logins/manifest.txt").exists());
+        assert!(!root.join("main/company/logins").exists());
+        assert!(!Path::new(DEFAULT_ROOT).join("company").exists());
+
+        {
+            let database = Database::open_with(name.clone(), config).unwrap();
+            assert_eq!(database.tablespace_of("logins"), Some("fast"));
+            assert_eq!(database.tablespace_of("employees"), None);
+            assert_eq!(database.relation("logins").unwrap().len(), 10);
+            assert_eq!(
+                database
+                    .relation("employees")
+                    .unwrap()
+                    .get([Type::from(1u32)]),
+                Ok(Some(row(1, "main")))
+            );
+        }
+        assert!(matches!(
+            Database::open_with(name, StorageConfig::new(root.join("main"))),
+            Err(DatabaseError::UnknownTablespace(_))
+        ));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -82,6 +82,15 @@ impl Identifier {
         })
     }
 
+    /// Parses an identifier the way it's displayed, splitting it into namespaces on `::`
+    ///
+    /// # Example
+    ///
+    /// `"db::table"` -> `db::table`, where `db` is the parent of `table`
+    pub fn parse(string: &str) -> Self {
+        Identifier::from_iter(string.split("::"))
+    }
+
     /// Returns the length of the identifier
     pub fn len(&self) -> usize {
         1 + match &self.parent {
@@ -186,11 +195,40 @@ impl<I: Into<Identifier> + ToOwned<Owned = I>> From<&I> for Identifier {
     }
 }
 
+/// Identifiers are represented as a list of their parts, starting from the highest namespace
+#[cfg(feature = "serde")]
+impl serde::Serialize for Identifier {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Identifier {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let parts = Vec::<String>::deserialize(deserializer)?;
+        if parts.is_empty() {
+            return Err(serde::de::Error::invalid_length(0, &"at least one part"));
+        }
+        Ok(Identifier::from_iter(parts))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::identifier::Identifier;
     use std::iter::FromIterator;
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let id = Identifier::from_iter(vec!["db", "table", "field"]);
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#"["db","table","field"]"#);
+        assert_eq!(serde_json::from_str::<Identifier>(&json).unwrap(), id);
+        serde_json::from_str::<Identifier>("[]").unwrap_err();
+    }
+
     #[test]
     fn id_length() {
         assert_eq!(Identifier::new("hello").len(), 1);
@@ -222,6 +260,16 @@ mod tests {
         assert_eq!(multiple, "db::table::field");
     }
 
+    #[test]
+    fn id_parse() {
+        assert_eq!(
+            Identifier::parse("db::table::field"),
+            Identifier::from_iter(&["db", "table", "field"])
+        );
+        assert_eq!(Identifier::parse("table"), Identifier::new("table"));
+        assert_eq!(Identifier::from("db::table").len(), 1);
+    }
+
     #[test]
     fn id_concatenation() {
         let concat = Identifier::concat("db", Identifier::new("table"));
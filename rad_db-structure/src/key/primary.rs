@@ -8,6 +8,7 @@ use rad_db_types::{Numeric, SameType, Type};
 use seahash::SeaHasher;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PrimaryKeyDefinition(Vec<usize>);
 
 impl PrimaryKeyDefinition {
@@ -1,9 +1,17 @@
 use crate::identifier::Identifier;
 
+// Lets code generated by `#[derive(Record)]` refer to this crate by name from within it
+extern crate self as rad_db_structure;
+
 pub mod constraint;
+pub mod database;
 pub mod identifier;
 pub mod key;
+pub mod record;
 pub mod relations;
+pub mod snapshot;
+pub mod storage;
+pub mod transaction;
 pub mod tuple;
 
 pub trait Rename<I: Into<Identifier>> {
@@ -12,11 +20,16 @@ pub trait Rename<I: Into<Identifier>> {
 
 pub mod prelude {
 
+    pub use crate::database::Database;
     pub use crate::identifier::Identifier;
     pub use crate::key::primary::*;
+    pub use crate::record::Record;
     pub use crate::relations::{
         Relation,
         RelationDefinition
     };
+    pub use crate::snapshot::VersionedRelation;
+    pub use crate::storage::StorageConfig;
+    pub use crate::transaction::Transaction;
     pub use crate::tuple::Tuple;
 }
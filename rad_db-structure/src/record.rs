@@ -0,0 +1,158 @@
+//! Mapping Rust structs to tuples and relations.
+//!
+//! A struct with named fields can derive [Record] if every field implements [ToValue] and
+//! [FromValue]. Every field becomes an attribute, in the order the fields are declared.
+//!
+//! - `#[key]` marks a field as part of the primary key. If no fields are marked, every field is
+//!   part of the key.
+//! - `#[rename = "name"]` sets the name of the attribute, which is otherwise the name of the field.
+//! - `Option<T>` fields are stored as the nullable version of the type of `T`.
+//!
+//! ```ignore
+//! #[derive(Record)]
+//! struct Employee {
+//!     #[key]
+//!     id: u64,
+//!     #[rename = "full_name"]
+//!     name: String,
+//!     manager: Option<u64>,
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+pub use rad_db_derive::Record;
+pub use rad_db_types::conversion::{FromValue, FromValueError, ToValue};
+pub use rad_db_types::DataType;
+
+use crate::identifier::Identifier;
+use crate::key::primary::PrimaryKeyDefinition;
+use crate::relations::Relation;
+use crate::tuple::Tuple;
+
+/// When a tuple couldn't be converted into a record
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordError {
+    /// The tuple doesn't have one value for every field
+    WrongArity { expected: usize, found: usize },
+    /// The value of a field is of the wrong type
+    Field {
+        name: &'static str,
+        cause: FromValueError,
+    },
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::WrongArity { expected, found } => {
+                write!(f, "Expected {} values, but found {}", expected, found)
+            }
+            RecordError::Field { name, cause } => write!(f, "Invalid field {}: {}", name, cause),
+        }
+    }
+}
+
+impl Error for RecordError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RecordError::WrongArity { .. } => None,
+            RecordError::Field { cause, .. } => Some(cause),
+        }
+    }
+}
+
+/// A Rust type that is stored as a tuple. This should be derived rather than implemented by hand.
+pub trait Record: Into<Tuple> + TryFrom<Tuple, Error = RecordError> {
+    /// The names and types of the attributes, in order
+    fn attributes() -> Vec<(String, DataType)>;
+
+    /// The primary key of relations of this record
+    fn primary_key() -> PrimaryKeyDefinition;
+
+    /// The attributes and primary key, as they're passed to [Relation::new]
+    fn schema() -> (Vec<(String, DataType)>, PrimaryKeyDefinition) {
+        (Self::attributes(), Self::primary_key())
+    }
+
+    /// Creates a relation that stores this record
+    fn relation(name: Identifier, bucket_size: usize) -> Relation {
+        let (attributes, primary_key) = Self::schema();
+        Relation::new(name, attributes, bucket_size, primary_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rad_db_types::Type;
+
+    #[derive(Debug, Clone, PartialEq, Record)]
+    struct Employee {
+        name: String,
+        #[key]
+        id: u64,
+        #[rename = "boss"]
+        manager: Option<u64>,
+    }
+
+    #[derive(Debug, PartialEq, Record)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[test]
+    fn schema() {
+        let (attributes, primary_key) = Employee::schema();
+        assert_eq!(
+            attributes,
+            vec![
+                ("name".to_string(), DataType::String(None)),
+                ("id".to_string(), DataType::UnsignedLong),
+                ("boss".to_string(), DataType::UnsignedLong.optional()),
+            ]
+        );
+        assert_eq!(*primary_key, vec![1]);
+        assert_eq!(*Point::primary_key(), vec![0, 1]);
+    }
+
+    #[test]
+    fn tuple_round_trip() {
+        let employee = Employee {
+            name: "Ada".to_string(),
+            id: 3,
+            manager: None,
+        };
+        let tuple: Tuple = employee.clone().into();
+        assert_eq!(
+            tuple,
+            Tuple::new(vec![
+                Type::from("Ada"),
+                Type::from(3u64),
+                Type::Optional(None)
+            ])
+        );
+        assert_eq!(Employee::try_from(tuple), Ok(employee));
+    }
+
+    #[test]
+    fn invalid_tuple() {
+        assert_eq!(
+            Point::try_from(Tuple::new(vec![Type::from(1i32)])),
+            Err(RecordError::WrongArity {
+                expected: 2,
+                found: 1
+            })
+        );
+        match Point::try_from(Tuple::new(vec![Type::from(1i32), Type::from(2u8)])) {
+            Err(RecordError::Field { name, cause }) => {
+                assert_eq!(name, "y");
+                assert_eq!(cause.expected(), &DataType::SignedInt);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use rad_db_types::Type;
+use rad_db_types::DataType;
 
 #[doc(hidden)]
 mod relation_struct;
@@ -7,5 +7,5 @@ pub use relation_struct::*;
 pub mod tuple_storage;
 
 pub trait AsTypeList {
-    fn to_type_list(&self) -> Vec<Type>;
+    fn to_type_list(&self) -> Vec<DataType>;
 }
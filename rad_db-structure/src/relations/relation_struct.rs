@@ -4,31 +4,56 @@ use std::ops::{Deref, DerefMut, Index, Shr};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use rad_db_types::Type;
+use rad_db_types::{DataType, Value};
 
 use crate::identifier::Identifier;
 use crate::key::primary::PrimaryKeyDefinition;
-use crate::relations::tuple_storage::{BlockIterator, StoredTupleIterator, TupleStorage};
+use crate::relations::tuple_storage::{
+    BlockIterator, ConflictPolicy, DeletionResult, InsertOutcome, InsertionResult,
+    KeyArityError, OpenError, StoredTupleIterator, TupleStorage, TupleUpdateError,
+    UpdateResult,
+};
 use crate::relations::AsTypeList;
+use crate::storage::{storage_path, DEFAULT_ROOT};
 use crate::tuple::Tuple;
 use crate::Rename;
 
 pub struct Relation {
     name: Identifier,
-    attributes: Vec<(String, Type)>,
+    attributes: Vec<(String, DataType)>,
     primary_key: PrimaryKeyDefinition,
     backing_table: TupleStorage,
 }
 
 impl Relation {
-    /// Creates a new relation that will save it's contents into the file system
-    pub fn new<S: ToString, I: IntoIterator<Item = (S, Type)>>(
+    /// Creates a new relation that will save it's contents into the file system, under the
+    /// default storage root
+    pub fn new<S: ToString, I: IntoIterator<Item = (S, DataType)>>(
         name: Identifier,
         attributes: I,
         bucket_size: usize,
         primary_key: PrimaryKeyDefinition,
     ) -> Self {
-        let attributes: Vec<(String, Type)> = attributes
+        Self::new_in(DEFAULT_ROOT, name, attributes, bucket_size, primary_key)
+    }
+
+    /// Creates a new relation that will save it's contents under the storage root, which can be
+    /// the root of a [StorageConfig] or one of its tablespaces
+    ///
+    /// [StorageConfig]: crate::storage::StorageConfig
+    pub fn new_in<P, S, I>(
+        storage_root: P,
+        name: Identifier,
+        attributes: I,
+        bucket_size: usize,
+        primary_key: PrimaryKeyDefinition,
+    ) -> Self
+    where
+        P: Into<PathBuf>,
+        S: ToString,
+        I: IntoIterator<Item = (S, DataType)>,
+    {
+        let attributes: Vec<(String, DataType)> = attributes
             .into_iter()
             .map(|(s, ty)| (s.to_string(), ty))
             .collect();
@@ -38,8 +63,13 @@ impl Relation {
             .map(|(string, ty)| (Identifier::with_parent(&name, string), ty))
             .collect();
         let definition = RelationDefinition::new(definition);
-        let backing_table =
-            Relation::generate_tuple_storage(&name, bucket_size, &primary_key, definition);
+        let backing_table = Relation::generate_tuple_storage(
+            storage_root.into(),
+            &name,
+            bucket_size,
+            &primary_key,
+            definition,
+        );
         Relation {
             name,
             attributes,
@@ -49,13 +79,13 @@ impl Relation {
     }
 
     /// Creates a relation that only lasts for as long as the program runs
-    pub fn new_volatile<S: ToString, I: IntoIterator<Item = (S, Type)>>(
+    pub fn new_volatile<S: ToString, I: IntoIterator<Item = (S, DataType)>>(
         name: Identifier,
         attributes: I,
         bucket_size: usize,
         primary_key: PrimaryKeyDefinition,
     ) -> Self {
-        let attributes: Vec<(String, Type)> = attributes
+        let attributes: Vec<(String, DataType)> = attributes
             .into_iter()
             .map(|(s, ty)| (s.to_string(), ty))
             .collect();
@@ -76,17 +106,42 @@ impl Relation {
     }
 
     fn generate_tuple_storage(
+        storage_root: PathBuf,
         name: &Identifier,
         bucket_size: usize,
         primary_key: &PrimaryKeyDefinition,
         definition: RelationDefinition,
     ) -> TupleStorage {
-        TupleStorage::new(name.clone(), definition, primary_key.clone(), bucket_size)
+        TupleStorage::new(
+            storage_root,
+            name.clone(),
+            definition,
+            primary_key.clone(),
+            bucket_size,
+        )
     }
 
-    /// Loads the relation from memory
-    pub fn load_from_memory(id: Identifier) -> Self {
-        unimplemented!()
+    /// Reopens a relation that was saved under the default storage root. Only the manifest of the
+    /// relation is read, and its blocks are loaded when they're used, except for the blocks that
+    /// changes in its log are replayed onto.
+    pub fn open(name: Identifier) -> Result<Self, OpenError> {
+        Self::open_in(DEFAULT_ROOT, name)
+    }
+
+    /// Reopens a relation that was saved under the storage root
+    pub fn open_in<P: Into<PathBuf>>(
+        storage_root: P,
+        name: Identifier,
+    ) -> Result<Self, OpenError> {
+        let backing_table = TupleStorage::open(storage_root, name.clone())?;
+        let attributes = backing_table.attributes();
+        let primary_key = backing_table.get_primary_key_definition().clone();
+        Ok(Relation {
+            name,
+            attributes,
+            primary_key,
+            backing_table,
+        })
     }
 
     /// Gets the name of the relation
@@ -94,8 +149,13 @@ impl Relation {
         &self.name
     }
 
+    /// Gets the storage root that the relation is saved under
+    pub fn storage_root(&self) -> &Path {
+        self.backing_table.storage_root()
+    }
+
     /// Gets the name and types of the relation
-    pub fn attributes(&self) -> &Vec<(String, Type)> {
+    pub fn attributes(&self) -> &Vec<(String, DataType)> {
         &self.attributes
     }
 
@@ -143,8 +203,78 @@ impl Relation {
         TempRelation::new(self)
     }
 
-    pub fn insert(&mut self, tuple: Tuple) {
-        self.backing_table.insert(tuple);
+    /// Inserts a tuple, failing if a tuple with the same primary key is already in the relation
+    pub fn insert(&mut self, tuple: Tuple) -> InsertionResult<InsertOutcome> {
+        self.backing_table.insert(tuple)
+    }
+
+    /// Inserts a tuple, resolving a conflict with a tuple that has the same primary key using the
+    /// policy
+    pub fn insert_with(
+        &mut self,
+        tuple: Tuple,
+        policy: ConflictPolicy<'_>,
+    ) -> InsertionResult<InsertOutcome> {
+        self.backing_table.insert_with(tuple, policy)
+    }
+
+    /// Gets a copy of the tuple with the primary key, where the key has a value for each attribute
+    /// of the primary key in the order of the primary key definition
+    pub fn get<K: AsRef<[Value]>>(&self, key: K) -> Result<Option<Tuple>, KeyArityError> {
+        self.backing_table.find_by_primary(key.as_ref())
+    }
+
+    /// Updates the tuple with the primary key using a closure, returning the tuple as it was before
+    /// the update. The updated tuple must fit the relation as it would on insert, and may have a
+    /// different primary key as long as it isn't already present.
+    pub fn update<K: AsRef<[Value]>, F: FnOnce(&mut Tuple)>(
+        &mut self,
+        key: K,
+        update: F,
+    ) -> UpdateResult<Option<Tuple>> {
+        self.backing_table.update(key.as_ref(), update)
+    }
+
+    /// Assigns values to fields of every tuple that meets the condition, returning the number of
+    /// tuples that were updated
+    pub fn update_where<C, I, A>(&mut self, condition: C, assignments: A) -> UpdateResult<usize>
+    where
+        C: FnMut(&Tuple) -> bool,
+        I: Into<Identifier>,
+        A: IntoIterator<Item = (I, Value)>,
+    {
+        let mut resolved = vec![];
+        for (field, value) in assignments {
+            let field = field.into();
+            match self.get_field_index(field.clone()) {
+                Some(index) => resolved.push((index, value)),
+                None => return Err(TupleUpdateError::UnknownField(field)),
+            }
+        }
+        self.backing_table.update_where(condition, &resolved)
+    }
+
+    /// Deletes the tuple with the primary key, where the key has a value for each attribute of the
+    /// primary key in the order of the primary key definition. Returns the deleted tuple, if there
+    /// was one.
+    pub fn delete<K: AsRef<[Value]>>(&mut self, key: K) -> DeletionResult<Option<Tuple>> {
+        self.backing_table.remove(key.as_ref())
+    }
+
+    /// Deletes the tuples with each of the primary keys, returning the tuples that were deleted
+    pub fn delete_many<K: AsRef<[Value]>, I: IntoIterator<Item = K>>(
+        &mut self,
+        keys: I,
+    ) -> DeletionResult<Vec<Tuple>> {
+        self.backing_table.remove_many(keys)
+    }
+
+    pub(crate) fn storage(&self) -> &TupleStorage {
+        &self.backing_table
+    }
+
+    pub(crate) fn storage_mut(&mut self) -> &mut TupleStorage {
+        &mut self.backing_table
     }
 
     pub fn get_field_index<I : Into<Identifier>>(&self, identifier: I) -> Option<usize> {
@@ -181,19 +311,20 @@ impl<I: Into<Identifier>> Rename<I> for Relation {
 }
 
 impl AsTypeList for Relation {
-    fn to_type_list(&self) -> Vec<Type> {
+    fn to_type_list(&self) -> Vec<DataType> {
         self.attributes.iter().map(|(_, t)| t).cloned().collect()
     }
 }
 
 /// A structure representing the actual names and types of a relation
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RelationDefinition {
-    attributes: Vec<(Identifier, Type)>,
+    attributes: Vec<(Identifier, DataType)>,
 }
 
 impl RelationDefinition {
-    pub fn new(attributes: Vec<(Identifier, Type)>) -> Self {
+    pub fn new(attributes: Vec<(Identifier, DataType)>) -> Self {
         RelationDefinition { attributes }
     }
 
@@ -278,25 +409,30 @@ impl RelationDefinition {
         self.attributes.iter().map(|(id, _)| id)
     }
 
+    /// Iterates over the types of the attributes, in order
+    pub fn type_iter(&self) -> impl Iterator<Item = &DataType> {
+        self.attributes.iter().map(|(_, ty)| ty)
+    }
+
     pub fn len(&self) -> usize {
         self.attributes.len()
     }
 }
 
-impl FromIterator<(Identifier, Type)> for RelationDefinition {
-    fn from_iter<T: IntoIterator<Item = (Identifier, Type)>>(iter: T) -> Self {
+impl FromIterator<(Identifier, DataType)> for RelationDefinition {
+    fn from_iter<T: IntoIterator<Item = (Identifier, DataType)>>(iter: T) -> Self {
         Self::new(iter.into_iter().collect())
     }
 }
 
-impl FromIterator<(String, Type)> for RelationDefinition {
-    fn from_iter<T: IntoIterator<Item = (String, Type)>>(iter: T) -> Self {
+impl FromIterator<(String, DataType)> for RelationDefinition {
+    fn from_iter<T: IntoIterator<Item = (String, DataType)>>(iter: T) -> Self {
         Self::from_iter(iter.into_iter().map(|(id, v)| (Identifier::new(id), v)))
     }
 }
 
 impl Index<usize> for RelationDefinition {
-    type Output = (Identifier, Type);
+    type Output = (Identifier, DataType);
 
     fn index(&self, index: usize) -> &Self::Output {
         &self.attributes[index]
@@ -304,7 +440,7 @@ impl Index<usize> for RelationDefinition {
 }
 
 impl Index<Identifier> for RelationDefinition {
-    type Output = Type;
+    type Output = DataType;
 
     fn index(&self, index: Identifier) -> &Self::Output {
         for (id, ty) in &self.attributes {
@@ -353,8 +489,8 @@ impl PartialEq for RelationDefinition {
 }
 
 impl IntoIterator for &RelationDefinition {
-    type Item = Type;
-    type IntoIter = <Vec<Type> as IntoIterator>::IntoIter;
+    type Item = DataType;
+    type IntoIter = <Vec<DataType> as IntoIterator>::IntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
         let ret: Vec<_> = self.attributes.iter().map(|(_, ty)| ty.clone()).collect();
@@ -375,7 +511,7 @@ impl TempRelation {
         let name = format!("temp{}", id);
         let fixed = Identifier::concat(name, &relation.name);
         relation.rename(fixed);
-        std::fs::remove_dir_all(PathBuf::from(&relation.name));
+        std::fs::remove_dir_all(storage_path(relation.storage_root(), &relation.name));
         Self(relation)
     }
 }
@@ -398,9 +534,8 @@ impl Drop for TempRelation {
     fn drop(&mut self) {
         let skeleton = self.backing_table.to_skeleton();
         let internals = std::mem::replace(&mut self.backing_table, skeleton);
+        let file = storage_path(internals.storage_root(), &self.name);
         std::mem::drop(internals);
-        let mut file = PathBuf::from("DB_STORAGE");
-        file.push(PathBuf::from(&self.name));
         std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
     }
 }
@@ -417,15 +552,16 @@ impl Debug for Relation {
 
 #[cfg(test)]
 mod tests {
-    use rad_db_types::{Numeric, Unsigned};
+    use rad_db_types::{Decimal, Numeric, Text, Type, Unsigned};
 
     use super::*;
+    use crate::relations::tuple_storage::{ManifestError, TupleDeletionError, TupleInsertionError};
 
     #[test]
     fn empty_relation() {
         let relation = Relation::new(
             Identifier::new("test"),
-            vec![("field1", Type::from(0u8))],
+            vec![("field1", DataType::UnsignedByte)],
             4,
             PrimaryKeyDefinition::new(vec![0]),
         )
@@ -434,11 +570,408 @@ mod tests {
         assert!(iterator.next().is_none());
     }
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let definition = RelationDefinition::new(vec![
+            (Identifier::new("id"), DataType::UnsignedLong),
+            (
+                Identifier::from_iter(vec!["test", "name"]),
+                DataType::String(Some(16)).optional(),
+            ),
+        ]);
+        let json = serde_json::to_string(&definition).unwrap();
+        assert_eq!(
+            json,
+            r#"{"attributes":[[["id"],"UnsignedLong"],[["test","name"],{"Optional":{"String":16}}]]}"#
+        );
+        let parsed: RelationDefinition = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+
+        let key = PrimaryKeyDefinition::new(vec![0, 1]);
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, "[0,1]");
+        let parsed: PrimaryKeyDefinition = serde_json::from_str(&json).unwrap();
+        assert_eq!(*parsed, vec![0, 1]);
+
+        let tuple = Tuple::new(vec![Type::from(3u64), Type::Optional(None)]);
+        let json = serde_json::to_string(&tuple).unwrap();
+        assert_eq!(serde_json::from_str::<Tuple>(&json).unwrap(), tuple);
+    }
+
+    #[test]
+    fn insert_validation() {
+        let mut relation = Relation::new_volatile(
+            Identifier::new("validated"),
+            vec![
+                ("id", DataType::UnsignedLong),
+                ("name", DataType::String(Some(5))),
+                ("data", DataType::BinaryString(2).optional()),
+            ],
+            4,
+            PrimaryKeyDefinition::new(vec![0]),
+        );
+        let valid = Tuple::new(vec![
+            Type::from(1u64),
+            Type::from("Ada"),
+            Type::Optional(None),
+        ]);
+        assert_eq!(relation.insert(valid), Ok(InsertOutcome::Inserted));
+        assert_eq!(
+            relation.insert(Tuple::new(vec![Type::from(2u64)])),
+            Err(TupleInsertionError::WrongArity {
+                expected: 3,
+                found: 1
+            })
+        );
+        assert_eq!(
+            relation.insert(Tuple::new(vec![
+                Type::from(2u8),
+                Type::from("Grace"),
+                Type::from(true),
+            ])),
+            Err(TupleInsertionError::IncorrectTypes(vec![0, 2]))
+        );
+        assert_eq!(
+            relation.insert(Tuple::new(vec![
+                Type::from(2u64),
+                Type::from("Grace Hopper"),
+                Type::Optional(None),
+            ])),
+            Err(TupleInsertionError::TooLong {
+                index: 1,
+                length: 12,
+                max: 5
+            })
+        );
+        assert_eq!(
+            relation.insert(Tuple::new(vec![
+                Type::from(2u64),
+                Type::from("Grace"),
+                Text::BinaryString(vec![1, 2, 3], 3).into(),
+            ])),
+            Err(TupleInsertionError::TooLong {
+                index: 2,
+                length: 3,
+                max: 2
+            })
+        );
+        assert_eq!(
+            relation.insert(Tuple::new(vec![
+                Type::from(2u64),
+                Type::Optional(None),
+                Type::Optional(None),
+            ])),
+            Err(TupleInsertionError::UnexpectedNull(1))
+        );
+        assert_eq!(relation.len(), 1);
+    }
+
+    #[test]
+    fn decimals_are_rescaled() {
+        let mut relation = Relation::new_volatile(
+            Identifier::new("prices"),
+            vec![
+                ("id", DataType::UnsignedLong),
+                (
+                    "price",
+                    DataType::Decimal {
+                        precision: 5,
+                        scale: 2,
+                    },
+                ),
+            ],
+            4,
+            PrimaryKeyDefinition::new(vec![0]),
+        );
+        let price = Decimal::parse("1.5", 2, 1).unwrap();
+        relation
+            .insert(Tuple::new(vec![Type::from(1u64), price.into()]))
+            .unwrap();
+        match &relation.get([Type::from(1u64)]).unwrap().unwrap()[1] {
+            Type::Numeric(Numeric::Decimal(stored)) => {
+                assert_eq!(stored, &price);
+                assert_eq!((stored.precision(), stored.scale()), (5, 2));
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+
+    #[test]
+    fn conflict_policies() {
+        let mut relation = Relation::new_volatile(
+            Identifier::new("conflicts"),
+            vec![("id", DataType::UnsignedLong), ("count", DataType::UnsignedLong)],
+            4,
+            PrimaryKeyDefinition::new(vec![0]),
+        );
+        let row = |id: u64, count: u64| Tuple::new(vec![Type::from(id), Type::from(count)]);
+        assert_eq!(relation.insert(row(1, 1)), Ok(InsertOutcome::Inserted));
+        assert_eq!(
+            relation.insert(row(1, 2)),
+            Err(TupleInsertionError::PrimaryKeyPresent)
+        );
+        assert_eq!(
+            relation.insert_with(row(1, 3), ConflictPolicy::Ignore),
+            Ok(InsertOutcome::Ignored)
+        );
+        assert_eq!(
+            relation.insert_with(row(1, 4), ConflictPolicy::Replace),
+            Ok(InsertOutcome::Replaced(row(1, 1)))
+        );
+
+        let mut add_counts = |old: &Tuple, new: Tuple| {
+            let total = match (&old[1], &new[1]) {
+                (
+                    Type::Numeric(Numeric::Unsigned(Unsigned::Long(old))),
+                    Type::Numeric(Numeric::Unsigned(Unsigned::Long(new))),
+                ) => old + new,
+                _ => unreachable!(),
+            };
+            row(1, total)
+        };
+        assert_eq!(
+            relation.insert_with(row(1, 5), ConflictPolicy::Merge(&mut add_counts)),
+            Ok(InsertOutcome::Merged(row(1, 4)))
+        );
+        assert_eq!(
+            relation.insert_with(row(1, 0), ConflictPolicy::Merge(&mut |_, _| row(2, 0))),
+            Err(TupleInsertionError::MergeChangedKey)
+        );
+        assert_eq!(
+            relation.insert_with(row(2, 7), ConflictPolicy::Merge(&mut add_counts)),
+            Ok(InsertOutcome::Inserted)
+        );
+        let mut tuples: Vec<_> = relation.tuples().collect();
+        tuples.sort_by_key(|tuple| tuple[0].clone());
+        assert_eq!(tuples, vec![row(1, 9), row(2, 7)]);
+    }
+
+    #[test]
+    fn delete() {
+        let mut relation = Relation::new(
+            Identifier::new("delete_test"),
+            vec![("id", DataType::UnsignedInt), ("name", DataType::String(None))],
+            2,
+            PrimaryKeyDefinition::new(vec![0]),
+        )
+        .into_temp();
+        let row = |id: u32| Tuple::new(vec![Type::from(id), Type::from(id.to_string())]);
+        for id in 0..8 {
+            relation.insert(row(id)).unwrap();
+        }
+
+        assert_eq!(relation.delete([Type::from(3u32)]).unwrap(), Some(row(3)));
+        assert_eq!(relation.len(), 7);
+        assert_eq!(relation.delete([Type::from(3u32)]).unwrap(), None);
+        match relation.delete([Type::from(3u32), Type::from("3")]) {
+            Err(TupleDeletionError::WrongArity { expected, found }) => {
+                assert_eq!((expected, found), (1, 2))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let keys = (0..8u32).filter(|id| id % 2 == 0).map(|id| [Type::from(id)]);
+        let mut deleted = relation.delete_many(keys).unwrap();
+        deleted.sort_by_key(|tuple| tuple[0].clone());
+        assert_eq!(deleted, vec![row(0), row(2), row(4), row(6)]);
+        assert_eq!(relation.len(), 3);
+        let mut remaining: Vec<_> = relation.tuples().collect();
+        remaining.sort_by_key(|tuple| tuple[0].clone());
+        assert_eq!(remaining, vec![row(1), row(5), row(7)]);
+    }
+
+    #[test]
+    fn get() {
+        let mut relation = Relation::new_volatile(
+            Identifier::new("get_test"),
+            vec![("name", DataType::String(None)), ("id", DataType::UnsignedInt)],
+            2,
+            PrimaryKeyDefinition::new(vec![1]),
+        );
+        let row = |id: u32| Tuple::new(vec![Type::from(id.to_string()), Type::from(id)]);
+        for id in 0..16 {
+            relation.insert(row(id)).unwrap();
+        }
+
+        assert_eq!(relation.get([Type::from(11u32)]), Ok(Some(row(11))));
+        assert_eq!(relation.get([Type::from(16u32)]), Ok(None));
+        assert_eq!(relation.get([Type::from(11u64)]), Ok(None));
+        assert_eq!(
+            relation.get(Vec::<Type>::new()),
+            Err(KeyArityError {
+                expected: 1,
+                found: 0
+            })
+        );
+    }
+
+    #[test]
+    fn update() {
+        let mut relation = Relation::new(
+            Identifier::new("update_test"),
+            vec![("id", DataType::UnsignedInt), ("name", DataType::String(Some(8)))],
+            2,
+            PrimaryKeyDefinition::new(vec![0]),
+        )
+        .into_temp();
+        let row = |id: u32, name: &str| Tuple::new(vec![Type::from(id), Type::from(name)]);
+        for id in 0..8 {
+            relation.insert(row(id, "old")).unwrap();
+        }
+
+        let previous = relation
+            .update([Type::from(2u32)], |tuple| tuple[1] = Type::from("new"))
+            .unwrap();
+        assert_eq!(previous, Some(row(2, "old")));
+        assert_eq!(relation.get([Type::from(2u32)]), Ok(Some(row(2, "new"))));
+
+        relation
+            .update([Type::from(3u32)], |tuple| tuple[0] = Type::from(30u32))
+            .unwrap();
+        assert_eq!(relation.get([Type::from(3u32)]), Ok(None));
+        assert_eq!(relation.get([Type::from(30u32)]), Ok(Some(row(30, "old"))));
+        assert_eq!(relation.len(), 8);
+
+        match relation.update([Type::from(4u32)], |tuple| tuple[0] = Type::from(5u32)) {
+            Err(TupleUpdateError::Invalid(TupleInsertionError::PrimaryKeyPresent)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match relation.update([Type::from(4u32)], |tuple| tuple[1] = Type::from(4u8)) {
+            Err(TupleUpdateError::Invalid(TupleInsertionError::IncorrectTypes(indexes))) => {
+                assert_eq!(indexes, vec![1])
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(relation.update([Type::from(9u32)], |_| ()).unwrap(), None);
+
+        let is_odd = |tuple: &Tuple| match &tuple[0] {
+            Type::Numeric(Numeric::Unsigned(Unsigned::Int(id))) => id % 2 == 1,
+            _ => false,
+        };
+        let updated = relation
+            .update_where(is_odd, vec![("name", Type::from("odd"))])
+            .unwrap();
+        assert_eq!(updated, 3);
+        let mut names: Vec<_> = relation.tuples().map(|tuple| tuple[1].clone()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(
+            names,
+            vec![Type::from("new"), Type::from("odd"), Type::from("old")]
+        );
+        let below_two = |tuple: &Tuple| tuple[0] < Type::from(2u32);
+        match relation.update_where(
+            below_two,
+            vec![("id", Type::from(1u32)), ("name", Type::from("moved"))],
+        ) {
+            Err(TupleUpdateError::Invalid(TupleInsertionError::PrimaryKeyPresent)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(relation.get([Type::from(0u32)]), Ok(Some(row(0, "old"))));
+        assert_eq!(relation.get([Type::from(1u32)]), Ok(Some(row(1, "odd"))));
+        match relation.update_where(|_| true, vec![("missing", Type::from(0u32))]) {
+            Err(TupleUpdateError::UnknownField(field)) => assert_eq!(field, "missing".into()),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn open() {
+        let root = std::env::temp_dir().join(format!("rad_db_open_test_{}", std::process::id()));
+        let name = Identifier::new("open_test");
+        let row = |id: u32| Tuple::new(vec![Type::from(id), Type::from(id.to_string())]);
+        {
+            let mut relation = Relation::new_in(
+                &root,
+                name.clone(),
+                vec![("id", DataType::UnsignedInt), ("name", DataType::String(Some(8)))],
+                4,
+                PrimaryKeyDefinition::new(vec![0]),
+            );
+            for id in 0..32 {
+                relation.insert(row(id)).unwrap();
+            }
+        }
+        // The blocks aren't read to count their tuples, as the manifest has the counts
+        let manifest = std::fs::read_to_string(root.join("open_test/manifest.txt")).unwrap();
+        let counted: usize = manifest
+            .lines()
+            .filter_map(|line| line.strip_prefix("bucket\t"))
+            .map(|bucket| bucket.split('\t').nth(1).unwrap().parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(counted, 32);
+
+        let mut relation = Relation::open_in(&root, name.clone()).unwrap();
+        assert_eq!(relation.len(), 32);
+        assert_eq!(
+            relation.attributes(),
+            &vec![
+                ("id".to_string(), DataType::UnsignedInt),
+                ("name".to_string(), DataType::String(Some(8)))
+            ]
+        );
+        assert_eq!(**relation.primary_key(), vec![0]);
+        assert_eq!(relation.get([Type::from(17u32)]), Ok(Some(row(17))));
+        relation.insert(row(32)).unwrap();
+        assert_eq!(relation.tuples().count(), 33);
+        std::mem::drop(relation);
+        std::fs::remove_dir_all(root.join("open_test")).unwrap();
+
+        match Relation::open_in(&root, name) {
+            Err(OpenError::Manifest(ManifestError::Io(..))) => {}
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn recover_from_log() {
+        let name = Identifier::new("wal_test");
+        let directory = storage_path(DEFAULT_ROOT, &name);
+        let _ = std::fs::remove_dir_all(&directory);
+        let row = |id: u32| Tuple::new(vec![Type::from(id), Type::from(id.to_string())]);
+        let mut relation = Relation::new(
+            name.clone(),
+            vec![("id", DataType::UnsignedInt), ("name", DataType::String(None))],
+            4,
+            PrimaryKeyDefinition::new(vec![0]),
+        );
+        for id in 0..16 {
+            relation.insert(row(id)).unwrap();
+        }
+        relation.delete([Type::from(3u32)]).unwrap();
+        relation
+            .update([Type::from(5u32)], |tuple| tuple[1] = Type::from("five"))
+            .unwrap();
+        // Stop without saving anything, and lose every block
+        std::mem::forget(relation);
+        for entry in std::fs::read_dir(&directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().unwrap().to_str().unwrap().starts_with("block_") {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+
+        let relation = Relation::open(name.clone()).unwrap();
+        assert!(!directory.join("wal.log").exists());
+        assert_eq!(relation.len(), 15);
+        assert_eq!(relation.tuples().count(), 15);
+        assert_eq!(relation.get([Type::from(3u32)]), Ok(None));
+        assert_eq!(
+            relation.get([Type::from(5u32)]),
+            Ok(Some(Tuple::new(vec![Type::from(5u32), Type::from("five")])))
+        );
+        assert_eq!(relation.get([Type::from(15u32)]), Ok(Some(row(15))));
+        std::mem::drop(relation);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
     #[test]
     fn add_one() {
         let mut relation = Relation::new(
             Identifier::new("test"),
-            vec![("field1", Type::from(0u8))],
+            vec![("field1", DataType::UnsignedByte)],
             4,
             PrimaryKeyDefinition::new(vec![0]),
         )
@@ -455,7 +988,7 @@ mod tests {
     fn add_many() {
         let mut relation = Relation::new(
             Identifier::new("test"),
-            vec![("field1", Type::from(0u8))],
+            vec![("field1", DataType::UnsignedByte)],
             7,
             PrimaryKeyDefinition::new(vec![0]),
         ) //;
@@ -487,7 +1020,7 @@ mod tests {
     fn add_many_random() {
         let mut relation = Relation::new(
             Identifier::new("test"),
-            vec![("field1", Type::from(0u64))],
+            vec![("field1", DataType::UnsignedLong)],
             64,
             PrimaryKeyDefinition::new(vec![0]),
         )
@@ -522,7 +1055,7 @@ mod tests {
     fn add_large_random() {
         let mut relation = Relation::new(
             Identifier::new("test"),
-            vec![("field1", Type::from(0u64))],
+            vec![("field1", DataType::UnsignedLong)],
             128,
             PrimaryKeyDefinition::new(vec![0]),
         )
@@ -559,7 +1092,7 @@ mod tests {
         for block_size in 1..=64 {
             let mut relation = Relation::new(
                 Identifier::new("test"),
-                vec![("field1", Type::from(0u64))],
+                vec![("field1", DataType::UnsignedLong)],
                 32,
                 PrimaryKeyDefinition::new(vec![0]),
             )
@@ -5,138 +5,226 @@ use std::fs::File;
 use std::fs::OpenOptions;
 use std::hash::Hasher;
 use std::io::Write;
-use std::io::{BufRead, BufReader, BufWriter};
-use std::iter::{FilterMap, Map};
-use std::ops::{Deref, DerefMut, Index, IndexMut};
-use std::path::PathBuf;
+use std::io::{BufReader, Read};
+use std::iter::FilterMap;
+use std::ops::{Deref, Index, IndexMut};
+use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
-use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Sender, TryRecvError};
-use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::thread;
-use std::time::{Duration, Instant};
 use thread::JoinHandle;
 
 use memmap::{Mmap, MmapMut};
 
-use rad_db_types::deserialization::parse_using_types;
-use rad_db_types::serialization::serialize_values;
 use rad_db_types::Type;
 
 use crate::identifier::Identifier;
+use crate::relations::tuple_storage::block_file::{self, BlockFileError, BlockFormat};
+use crate::relations::tuple_storage::buffer_pool::{self, BufferPool, FrameId};
+use crate::relations::tuple_storage::version::Timestamp;
 use crate::relations::RelationDefinition;
+use crate::storage::{atomic_write, storage_path};
 use crate::tuple::Tuple;
 use num_bigint::BigUint;
 use std::slice::{Iter, IterMut};
 use tokio::io::AsyncWrite;
 
-/// The number of durations to included in the access rolling average
-pub const ROLLING_AVERAGE_COUNT: usize = 100;
-/// The minimum amount of time in milliseconds the rolling average must be to keep the block loaded in memory
-pub const MIN_TIME_FOR_MAINTAIN_LOAD: u128 = 500;
-
 pub struct Block {
-    parent_table: Identifier,
     relationship_definition: RelationDefinition,
-    block_num: usize,
-    block_contents: Option<BlockContents>,
-    len: usize,
-    usage: RwLock<()>,
-    reads: AtomicUsize,
+    frame: Arc<Frame>,
+    pool: Arc<BufferPool>,
+    len: AtomicUsize,
     no_backing_file: bool,
-    access_info: RwLock<AccessInformation>,
-    load_block: AtomicBool,
 }
 
 impl Block {
     pub fn len(&self) -> usize {
-        self.len
+        self.len.load(Ordering::Acquire)
     }
 }
 
+/// When the contents of a block couldn't be loaded from its file
 #[derive(Debug)]
-pub struct ReadInUseError;
+pub enum LoadError {
+    /// The file couldn't be opened or read
+    Io(PathBuf, std::io::Error),
+    /// The contents of the file couldn't be read as tuples
+    BlockFile(PathBuf, BlockFileError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(path, e) => write!(f, "Could not read block file {:?}: {}", path, e),
+            LoadError::BlockFile(path, e) => {
+                write!(f, "Could not load block file {:?}: {}", path, e)
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(_, e) => Some(e),
+            LoadError::BlockFile(_, e) => Some(e),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadInUseError {
+    /// The lock on the block was poisoned
+    Poisoned,
+    /// The contents of the block couldn't be loaded
+    Load(LoadError),
+}
 
 impl Display for ReadInUseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Could not get readable contents of this block")
+        match self {
+            ReadInUseError::Poisoned => {
+                write!(f, "Could not get readable contents of this block")
+            }
+            ReadInUseError::Load(e) => write!(f, "{}", e),
+        }
     }
 }
 
-impl Error for ReadInUseError {}
+impl Error for ReadInUseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadInUseError::Poisoned => None,
+            ReadInUseError::Load(e) => Some(e),
+        }
+    }
+}
+
+impl From<PoisonError<RwLockReadGuard<'_, Option<BlockContents>>>> for ReadInUseError {
+    fn from(_: PoisonError<RwLockReadGuard<'_, Option<BlockContents>>>) -> Self {
+        ReadInUseError::Poisoned
+    }
+}
 
-impl From<PoisonError<RwLockReadGuard<'_, ()>>> for ReadInUseError {
-    fn from(_: PoisonError<RwLockReadGuard<'_, ()>>) -> Self {
-        ReadInUseError
+impl From<LoadError> for ReadInUseError {
+    fn from(e: LoadError) -> Self {
+        ReadInUseError::Load(e)
     }
 }
 
 #[derive(Debug)]
-pub struct WriteInUseError;
+pub enum WriteInUseError {
+    /// The lock on the block was poisoned
+    Poisoned,
+    /// The contents of the block couldn't be loaded
+    Load(LoadError),
+}
 
 impl Display for WriteInUseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Could not get writable contents of this block")
+        match self {
+            WriteInUseError::Poisoned => {
+                write!(f, "Could not get writable contents of this block")
+            }
+            WriteInUseError::Load(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for WriteInUseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WriteInUseError::Poisoned => None,
+            WriteInUseError::Load(e) => Some(e),
+        }
     }
 }
 
-impl Error for WriteInUseError {}
+impl From<PoisonError<RwLockWriteGuard<'_, Option<BlockContents>>>> for WriteInUseError {
+    fn from(_: PoisonError<RwLockWriteGuard<'_, Option<BlockContents>>>) -> Self {
+        WriteInUseError::Poisoned
+    }
+}
 
-impl From<PoisonError<RwLockWriteGuard<'_, ()>>> for WriteInUseError {
-    fn from(_: PoisonError<RwLockWriteGuard<'_, ()>>) -> Self {
-        WriteInUseError
+impl From<LoadError> for WriteInUseError {
+    fn from(e: LoadError) -> Self {
+        WriteInUseError::Load(e)
     }
 }
 
 impl Block {
-    pub fn new(
+    /// Creates a block that's saved to a file under the storage root, with its contents managed
+    /// by the global buffer pool
+    pub fn new<P: AsRef<Path>>(
+        storage_root: P,
         parent_table: Identifier,
         block_num: usize,
         relationship_definition: RelationDefinition,
     ) -> Self {
-        let ret = Block {
+        Block::new_in_pool(
+            BufferPool::global(),
+            storage_root,
             parent_table,
-            relationship_definition,
             block_num,
-            block_contents: None,
-            len: 0,
-            usage: RwLock::new(()),
-            reads: Default::default(),
+            relationship_definition,
+        )
+    }
+
+    /// Creates a block that's saved to a file under the storage root, with its contents managed
+    /// by a buffer pool other than the global one
+    pub fn new_in_pool<P: AsRef<Path>>(
+        pool: Arc<BufferPool>,
+        storage_root: P,
+        parent_table: Identifier,
+        block_num: usize,
+        relationship_definition: RelationDefinition,
+    ) -> Self {
+        let mut file_name = storage_path(storage_root, &parent_table);
+        file_name.push(format!("block_{}.txt", block_num));
+        let ret = Block {
+            relationship_definition,
+            frame: Arc::new(Frame::new(file_name, None)),
+            pool,
+            len: Default::default(),
             no_backing_file: false,
-            access_info: Default::default(),
-            load_block: Default::default(),
         };
         ret.initialize_file().unwrap();
         ret
     }
 
-    /// Creates a block that never saved to a file
-    pub fn new_unbacked(
+    /// Creates a block for a file that already has a number of tuples in it, which aren't loaded
+    /// until they're used
+    pub fn open<P: AsRef<Path>>(
+        storage_root: P,
         parent_table: Identifier,
         block_num: usize,
         relationship_definition: RelationDefinition,
+        len: usize,
     ) -> Self {
-        let mut ret = Block {
+        let ret = Block::new(
+            storage_root,
             parent_table,
-            relationship_definition,
             block_num,
-            block_contents: None,
-            len: 0,
-            usage: RwLock::new(()),
-            reads: Default::default(),
-            no_backing_file: true,
-            access_info: Default::default(),
-            load_block: Default::default(),
-        };
-        ret.block_contents = Some(BlockContents {
-            relationship: ret.relationship_definition.clone(),
-            file: None,
-            internal: vec![],
-        });
+            relationship_definition,
+        );
+        ret.len.store(len, Ordering::Release);
         ret
     }
 
+    /// Creates a block that never saved to a file, so its contents are always loaded
+    pub fn new_unbacked(relationship_definition: RelationDefinition) -> Self {
+        let contents = BlockContents::new(relationship_definition.clone(), None, vec![]);
+        Block {
+            relationship_definition,
+            frame: Arc::new(Frame::new(PathBuf::new(), Some(contents))),
+            pool: BufferPool::global(),
+            len: Default::default(),
+            no_backing_file: true,
+        }
+    }
+
     fn initialize_file(&self) -> std::io::Result<()> {
         let file_name = self.file_name();
 
@@ -159,301 +247,352 @@ impl Block {
         self.try_get_contents().unwrap()
     }
 
+    /// Attempts to get immutable access to the contents of the block, loading them if they aren't
+    /// loaded. The block is pinned in its buffer pool until the contents are dropped.
     pub fn try_get_contents(&self) -> Result<InUse, ReadInUseError> {
-        let read_guard = self.usage.read()?;
-        self.reads.fetch_add(1, Ordering::Acquire);
-        self.notify_access();
-        if !self.load_status() {
-            unsafe {
-                self.load();
+        let pin = Pin::new(&self.frame);
+        loop {
+            self.load()?;
+            let contents = self.frame.contents.read()?;
+            if contents.is_some() {
+                return Ok(InUse {
+                    contents,
+                    _pin: pin,
+                });
             }
         }
-        let ret = InUse {
-            parent: self,
-            read: read_guard,
-        };
-        Ok(ret)
     }
 
-    /// Attempts to get immutable access to the contents of the block
-    pub fn get_contents_mut(&mut self) -> InUseMut {
+    /// Gets mutable access to the contents of the block
+    pub fn get_contents_mut(&self) -> InUseMut {
         self.try_get_contents_mut().unwrap()
     }
 
-    pub fn try_get_contents_mut(&mut self) -> Result<InUseMut, WriteInUseError> {
-        let write_copy = (self as *mut Self);
-        let write_guard = self.usage.write()?;
-        self.notify_access();
-        if !self.load_status() {
-            unsafe {
-                self.load();
+    /// Attempts to get mutable access to the contents of the block, loading them if they aren't
+    /// loaded. The block is pinned in its buffer pool until the contents are dropped.
+    pub fn try_get_contents_mut(&self) -> Result<InUseMut, WriteInUseError> {
+        let pin = Pin::new(&self.frame);
+        loop {
+            self.load()?;
+            let contents = self.frame.contents.write()?;
+            if contents.is_some() {
+                self.frame.dirty.store(true, Ordering::Release);
+                return Ok(InUseMut {
+                    parent: self,
+                    size: contents.as_ref().unwrap().size(),
+                    contents: Some(contents),
+                    _pin: pin,
+                });
             }
         }
-        unsafe {
-            let ret = InUseMut {
-                parent: &mut *write_copy,
-                write: write_guard,
-            };
-            Ok(ret)
-        }
     }
 
-    fn file_name(&self) -> PathBuf {
-        let mut ret = PathBuf::from("DB_STORAGE");
-        for name in &self.parent_table {
-            ret.push(name);
-        }
-        ret.push(format!("block_{}.txt", self.block_num));
-        ret
-    }
-
-    /// Determines if the contents of the block is loaded
-    fn load_status(&self) -> bool {
-        self.block_contents.is_some()
+    fn file_name(&self) -> &Path {
+        &self.frame.file_name
     }
 
-    fn notify_access(&self) {
-        let mut access_info = self.access_info.write().unwrap();
-        access_info.add_access();
+    /// Gets the id of the frame that holds the contents of the block in its buffer pool
+    pub(super) fn frame_id(&self) -> FrameId {
+        self.frame.id
     }
 
-    fn notify_finish(&self) {
-        let mut access_info = self.access_info.read().unwrap();
-        if self.reads.load(Ordering::Acquire) == 0
-            && self.load_status()
-            && access_info.should_unload()
-        {
-            unsafe {
-                self.unload();
-            }
+    /// Reads the contents of the block from its file and adds them to the buffer pool, unless
+    /// they're already loaded
+    fn load(&self) -> Result<(), LoadError> {
+        if self.no_backing_file {
+            return Ok(());
+        }
+        if self.frame.contents.read().unwrap().is_some() {
+            self.pool.hit(self.frame.id);
+            return Ok(());
+        }
+        let mut loaded = self.frame.contents.write().unwrap();
+        if loaded.is_some() {
+            self.pool.hit(self.frame.id);
+            return Ok(());
         }
+        let (file, tuples, format) = self.read_file()?;
+        self.len.store(tuples.len(), Ordering::Release);
+
+        let contents = BlockContents::new(self.relationship_definition.clone(), Some(file), tuples);
+        let size = contents.size();
+        *loaded = Some(contents);
+        // Blocks in the old text format are rewritten in the current one when they're unloaded
+        self.frame
+            .dirty
+            .store(format == BlockFormat::Text, Ordering::Release);
+        // Other frames are evicted while this one is still locked, so it can't be chosen
+        self.pool.admit(&self.frame, size);
+        Ok(())
     }
 
-    unsafe fn load(&self) {
-        //println!("Loading Block {}", self.block_num);
-        while self.load_block.load(Ordering::Relaxed) {}
-        if self.no_backing_file {
-            return;
-        }
-        let path = self.file_name();
-        let file = OpenOptions::new()
-            .write(true)
-            .read(true)
-            .open(&path)
-            .expect(&*format!("Could not open file {:?}", path));
+    /// Reads the tuples out of the block file, returning the open file, the tuples and the format
+    /// the file was in
+    fn read_file(&self) -> Result<(File, Vec<(BigUint, Tuple)>, BlockFormat), LoadError> {
+        let path = self.file_name().to_path_buf();
+        let file = match OpenOptions::new().write(true).read(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => return Err(LoadError::Io(path, e)),
+        };
 
-        let mut buf_reader = BufReader::new(&file);
-        let mut tuples = vec![];
-        let mut len = 0;
-        loop {
-            let mut str = String::new();
-            match buf_reader.read_line(&mut str) {
-                Err(_) => {
-                    panic!("Couldn't read block form file")
-                }
-                Ok(0) => break,
-                Ok(_) => {
-                    let str = str.trim_end();
-                    let mut split = str.splitn(2, ":");
-                    let hash = split.next().unwrap();
-                    let tuple_str = split.next().unwrap();
-
-                    let tuple = Tuple::new(
-                        parse_using_types(tuple_str, &self.relationship_definition)
-                            .expect("Could not parse type")
-                            .into_iter(),
-                    );
-                    len += 1;
-                    tuples.push((BigUint::from_str(hash).unwrap(), tuple));
-                }
-            }
+        let mut raw = vec![];
+        if let Err(e) = BufReader::new(&file).read_to_end(&mut raw) {
+            return Err(LoadError::Io(path, e));
+        }
+        match block_file::read_block(&raw, &self.relationship_definition) {
+            Ok(tuples) => Ok((file, tuples, BlockFormat::detect(&raw))),
+            Err(e) => Err(LoadError::BlockFile(path, e)),
         }
+    }
 
-        let contents = BlockContents {
-            relationship: self.relationship_definition.clone(),
-            file: Some(file),
-            internal: tuples,
+    /// Removes the versions that no read can see anymore if the contents are loaded, returning
+    /// the number of versions that were removed. Contents that aren't loaded only have versions
+    /// that every read sees.
+    pub fn collect_garbage(&self, horizon: Timestamp) -> usize {
+        let mut contents = self.frame.contents.write().unwrap();
+        let (removed, size) = match &mut *contents {
+            Some(contents) => (contents.collect_garbage(horizon), contents.size()),
+            None => return 0,
         };
-        unsafe {
-            let mutable = self as *const Self as *mut Self;
-            (*mutable).block_contents = Some(contents);
-            (*mutable).len = len;
+        std::mem::drop(contents);
+        if !self.no_backing_file && removed > 0 {
+            self.pool.resize(self.frame.id, size);
         }
+        removed
     }
 
-    unsafe fn unload(&self) {
-        //println!("Flushing Block {}", self.block_num);
-        if self.no_backing_file {
-            return;
+    /// Gets the number of versions in the block, without loading its contents
+    pub fn version_count(&self) -> usize {
+        match &*self.frame.contents.read().unwrap() {
+            Some(contents) => contents.version_count(),
+            None => self.len(),
         }
+    }
 
-        let unsafe_self = self as *const Self as *mut Self;
-        while self
-            .load_block
-            .compare_and_swap(false, true, Ordering::Relaxed)
-        {}
-        let replaced = std::mem::replace(&mut (*unsafe_self).block_contents, None);
-        if let Some(contents) = replaced {
-            let BlockContents {
-                file: _file,
-                internal,
-                ..
-            } = contents;
-            let file_name = self.file_name();
-            std::fs::remove_file(&file_name).unwrap();
-
-            let mut file = File::create(file_name).expect("Failed to recreate file");
-
-            let mut saved = 0;
-            let mut buf_writer = BufWriter::new(file);
-
-            for (hash, tuple) in internal {
-                writeln!(
-                    buf_writer,
-                    "{}:{}",
-                    hash,
-                    serialize_values(tuple.into_iter())
-                )
-                .unwrap();
-                saved += 1;
+    /// Writes the live versions of the tuples to the file if they're loaded, without unloading
+    /// them
+    pub fn flush(&self) -> std::io::Result<()> {
+        if self.no_backing_file {
+            return Ok(());
+        }
+        let contents = self.frame.contents.read().unwrap();
+        match &*contents {
+            Some(contents) => {
+                write_block_file(self.file_name(), contents.all_with_key())?;
+                self.frame.dirty.store(false, Ordering::Release);
+                Ok(())
             }
-            //(*unsafe_self).len = saved;
-            buf_writer.flush();
-            self.load_block.store(false, Ordering::Release);
-            /*
-            println!(
-                "Saved {} Tuples in {} seconds",
-                saved,
-                instant.elapsed().as_secs_f64()
-            )
-
-             */
+            None => Ok(()),
         }
     }
+
+    /// Writes the contents of the block to its file if they're loaded. Block files are synced to
+    /// the disk whenever they're written, so the file is synced once this returns.
+    pub fn sync(&self) -> std::io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Replaces the contents of a block file with the tuples
+fn write_block_file<'a, I: IntoIterator<Item = (&'a BigUint, &'a Tuple)>>(
+    file_name: &Path,
+    tuples: I,
+) -> std::io::Result<()> {
+    let mut buffer = vec![];
+    block_file::write_block(&mut buffer, tuples)?;
+    atomic_write(file_name, &buffer)
 }
 
 impl Drop for Block {
     fn drop(&mut self) {
-        if self.load_status() {
-            unsafe {
-                self.unload();
+        if self.no_backing_file {
+            return;
+        }
+        let contents = self.frame.contents.write().unwrap().take();
+        if let Some(contents) = contents {
+            if self.frame.dirty.load(Ordering::Acquire) {
+                if let Err(e) = write_block_file(self.file_name(), contents.all_with_key()) {
+                    log::warn!("Couldn't write block {:?}: {}", self.file_name(), e);
+                }
             }
+            self.pool.remove(self.frame.id);
         }
     }
 }
 
-#[derive(Default)]
-struct AccessInformation {
-    last_access: Option<Instant>,
-    access_delays: Vec<Duration>,
-    current_access: usize,
+/// The contents of a block, as they're shared with its buffer pool
+pub(super) struct Frame {
+    id: FrameId,
+    file_name: PathBuf,
+    contents: RwLock<Option<BlockContents>>,
+    pins: AtomicUsize,
+    /// Whether the contents were changed since they were last written to the file
+    dirty: AtomicBool,
 }
 
-impl AccessInformation {
-    pub fn add_access(&mut self) {
-        let access = Instant::now();
-        if self.last_access.is_none() {
-            self.last_access = Some(access);
-        } else {
-            let last = std::mem::replace(&mut self.last_access, Some(access)).unwrap();
-            let duration = last.elapsed();
+impl Frame {
+    fn new(file_name: PathBuf, contents: Option<BlockContents>) -> Self {
+        Frame {
+            id: buffer_pool::next_frame_id(),
+            file_name,
+            contents: RwLock::new(contents),
+            pins: Default::default(),
+            dirty: Default::default(),
+        }
+    }
+
+    pub(super) fn id(&self) -> FrameId {
+        self.id
+    }
+
+    pub(super) fn is_pinned(&self) -> bool {
+        self.pins.load(Ordering::Acquire) > 0
+    }
 
-            if self.access_delays.len() < ROLLING_AVERAGE_COUNT {
-                self.access_delays.push(duration);
-            } else {
-                self.access_delays[self.current_access] = duration;
+    pub(super) fn file_name(&self) -> &Path {
+        &self.file_name
+    }
+
+    /// Writes the contents back to the file if they were changed, then unloads them. Returns
+    /// whether the contents were unloaded, which they aren't if the frame is pinned or has
+    /// versions that not every read sees. If the contents couldn't be written, they're kept loaded
+    /// and changed.
+    pub(super) fn try_evict(&self) -> std::io::Result<bool> {
+        let mut contents = match self.contents.try_write() {
+            Ok(contents) => contents,
+            Err(_) => return Ok(false),
+        };
+        // A block is pinned before it's locked, so checking again here can't miss one
+        if self.is_pinned() {
+            return Ok(false);
+        }
+        if let Some(loaded) = &*contents {
+            if loaded.has_history() {
+                return Ok(false);
             }
-            self.current_access += 1;
-            if self.current_access >= ROLLING_AVERAGE_COUNT {
-                self.current_access = 0;
+            if self.dirty.load(Ordering::Acquire) {
+                write_block_file(&self.file_name, loaded.all_with_key())?;
+                self.dirty.store(false, Ordering::Release);
             }
         }
+        *contents = None;
+        Ok(true)
     }
+}
 
-    /// Gets the rolling average in milliseconds
-    fn rolling_average(&self) -> Option<u128> {
-        if self.access_delays.is_empty() {
-            return None;
-        }
-        let mut ret = 0;
-        for access in &self.access_delays {
-            ret += access.as_millis();
-        }
-        Some(ret / self.access_delays.len() as u128)
+/// Keeps a frame pinned while it's alive
+struct Pin<'a> {
+    frame: &'a Frame,
+}
+
+impl<'a> Pin<'a> {
+    fn new(frame: &'a Frame) -> Self {
+        frame.pins.fetch_add(1, Ordering::AcqRel);
+        Pin { frame }
     }
+}
 
-    /// Whether the block should unload after this access
-    fn should_unload(&self) -> bool {
-        match self.rolling_average() {
-            None => true,
-            Some(average) => average > MIN_TIME_FOR_MAINTAIN_LOAD,
-        }
+impl Drop for Pin<'_> {
+    fn drop(&mut self) {
+        self.frame.pins.fetch_sub(1, Ordering::AcqRel);
     }
 }
 
 pub struct InUse<'a> {
-    parent: &'a Block,
-    read: RwLockReadGuard<'a, ()>,
+    contents: RwLockReadGuard<'a, Option<BlockContents>>,
+    _pin: Pin<'a>,
 }
 
 impl Deref for InUse<'_> {
     type Target = BlockContents;
 
     fn deref(&self) -> &Self::Target {
-        if self.parent.block_contents.is_none() {
-            unsafe {
-                self.parent.load();
-            }
-        }
-        self.parent.block_contents.as_ref().unwrap()
-    }
-}
-
-impl Drop for InUse<'_> {
-    fn drop(&mut self) {
-        self.parent.reads.fetch_sub(1, Ordering::Acquire);
-        self.parent.notify_finish();
+        self.contents.as_ref().unwrap()
     }
 }
 
+/// Mutable access to the contents of a block. Tuples can only be changed through the methods of
+/// this type, so that the size of the contents is kept up to date.
 pub struct InUseMut<'a> {
-    parent: &'a mut Block,
-    write: RwLockWriteGuard<'a, ()>,
+    parent: &'a Block,
+    /// The size of the contents when they were locked
+    size: usize,
+    /// Only taken when this is dropped
+    contents: Option<RwLockWriteGuard<'a, Option<BlockContents>>>,
+    _pin: Pin<'a>,
 }
 
 impl<'a> InUseMut<'a> {
+    fn contents_mut(&mut self) -> &mut BlockContents {
+        self.contents.as_mut().unwrap().as_mut().unwrap()
+    }
+
     pub fn insert_tuple(&mut self, hash: BigUint, tuple: Tuple) -> Option<Tuple> {
-        let ret = (**self).insert_tuple(hash, tuple);
+        let ret = self.contents_mut().insert_tuple(hash, tuple);
         if ret.is_none() {
-            self.parent.len += 1;
+            self.parent.len.fetch_add(1, Ordering::AcqRel);
         }
         ret
     }
 
     pub fn remove_tuple(&mut self, hash: BigUint) -> Option<Tuple> {
-        let ret = (**self).remove_tuple(hash);
+        let ret = self.contents_mut().remove_tuple(hash);
         if ret.is_some() {
-            self.parent.len -= 1;
+            self.parent.len.fetch_sub(1, Ordering::AcqRel);
         }
         ret
     }
 
-    pub fn take_all(&mut self) -> Vec<Tuple> {
-        let ret = (**self).take_all();
-        self.parent.len = 0;
-        ret
+    /// Makes a change to the tuple stored with the hash at the timestamp, keeping the version it
+    /// replaces or deletes
+    pub(super) fn write_version(
+        &mut self,
+        hash: BigUint,
+        tuple: Option<Tuple>,
+        timestamp: Timestamp,
+    ) {
+        let change = self.contents_mut().write_version(hash, tuple, timestamp);
+        self.change_len(change);
+    }
+
+    /// Undoes the change made to the tuple stored with the hash at the timestamp
+    pub(super) fn undo_version(&mut self, hash: &BigUint, timestamp: Timestamp) {
+        let change = self.contents_mut().undo_version(hash, timestamp);
+        self.change_len(change);
     }
 
-    pub fn take_all_with_key(&mut self) -> Vec<(BigUint, Tuple)> {
-        let ret = (**self).take_all_with_key();
-        self.parent.len = 0;
+    pub(super) fn take_versions(&mut self) -> Vec<Version> {
+        let ret = self.contents_mut().take_versions();
+        self.parent.len.store(0, Ordering::Release);
         ret
     }
+
+    pub(super) fn push_version(&mut self, version: Version) {
+        if version.is_live() {
+            self.parent.len.fetch_add(1, Ordering::AcqRel);
+        }
+        self.contents_mut().push_version(version);
+    }
+
+    fn change_len(&self, change: isize) {
+        if change > 0 {
+            self.parent.len.fetch_add(change as usize, Ordering::AcqRel);
+        } else if change < 0 {
+            self.parent
+                .len
+                .fetch_sub(-change as usize, Ordering::AcqRel);
+        }
+    }
 }
 
 impl Drop for InUseMut<'_> {
+    /// Unlocks the contents, then tells the buffer pool how many bytes they take up if that changed
     fn drop(&mut self) {
-        self.parent.notify_finish()
+        let size = self.size();
+        self.contents = None;
+        if !self.parent.no_backing_file && size != self.size {
+            self.parent.pool.resize(self.parent.frame.id, size);
+        }
     }
 }
 
@@ -461,95 +600,273 @@ impl Deref for InUseMut<'_> {
     type Target = BlockContents;
 
     fn deref(&self) -> &Self::Target {
-        self.parent.block_contents.as_ref().unwrap()
+        self.contents.as_ref().unwrap().as_ref().unwrap()
     }
 }
 
-impl DerefMut for InUseMut<'_> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.parent.block_contents.as_mut().unwrap()
+/// A version of a tuple in a block. Reads at timestamps from the one the version was created at,
+/// up to the one it was deleted at, see it.
+#[derive(Debug, Clone)]
+pub struct Version {
+    hash: BigUint,
+    tuple: Tuple,
+    created: Timestamp,
+    deleted: Option<Timestamp>,
+}
+
+impl Version {
+    /// A version that every read sees, as it's read from a block file or stored without a commit
+    fn new(hash: BigUint, tuple: Tuple) -> Self {
+        Version {
+            hash,
+            tuple,
+            created: 0,
+            deleted: None,
+        }
+    }
+
+    pub(super) fn hash(&self) -> &BigUint {
+        &self.hash
+    }
+
+    fn is_live(&self) -> bool {
+        self.deleted.is_none()
+    }
+
+    fn is_visible(&self, timestamp: Timestamp) -> bool {
+        self.created <= timestamp && self.deleted.is_none_or(|deleted| deleted > timestamp)
+    }
+
+    fn size(&self) -> usize {
+        block_file::tuple_size(&self.hash, &self.tuple)
     }
 }
 
 pub struct BlockContents {
     relationship: RelationDefinition,
     file: Option<File>,
-    internal: Vec<(BigUint, Tuple)>,
+    /// Every version of the tuples, where only the newest version of each hash can be live
+    internal: Vec<Version>,
+    /// The number of bytes the versions would take up in a block file
+    size: usize,
 }
 
-fn filter_map_helper<T>(input: &Option<T>) -> Option<&T> {
-    input.as_ref()
+fn live_tuple(version: &Version) -> Option<&Tuple> {
+    if version.is_live() {
+        Some(&version.tuple)
+    } else {
+        None
+    }
 }
-fn filter_map_helper_mut<T>(input: &mut Option<T>) -> Option<&mut T> {
-    input.as_mut()
+
+fn live_tuple_mut(version: &mut Version) -> Option<&mut Tuple> {
+    if version.is_live() {
+        Some(&mut version.tuple)
+    } else {
+        None
+    }
 }
 
 impl BlockContents {
-    pub fn get_tuple(&self, hash: BigUint) -> Option<&Tuple> {
-        for (h, tuple) in &self.internal {
-            if h == &hash {
-                return Some(tuple);
-            }
+    fn new(
+        relationship: RelationDefinition,
+        file: Option<File>,
+        tuples: Vec<(BigUint, Tuple)>,
+    ) -> Self {
+        BlockContents {
+            relationship,
+            file,
+            size: block_file::encoded_size(tuples.iter().map(|(hash, tuple)| (hash, tuple))),
+            internal: tuples
+                .into_iter()
+                .map(|(hash, tuple)| Version::new(hash, tuple))
+                .collect(),
         }
-        None
+    }
+
+    /// Gets the number of bytes the versions would take up in a block file
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Gets the position of the live version of the tuple stored with the hash
+    fn live(&self, hash: &BigUint) -> Option<usize> {
+        self.internal
+            .iter()
+            .position(|version| version.is_live() && &version.hash == hash)
+    }
+
+    pub fn get_tuple(&self, hash: BigUint) -> Option<&Tuple> {
+        self.live(&hash).map(|pos| &self.internal[pos].tuple)
     }
 
     pub fn get_tuple_mut(&mut self, hash: BigUint) -> Option<&mut Tuple> {
-        for (h, tuple) in &mut self.internal {
-            if *h == hash {
-                return Some(tuple);
-            }
-        }
-        None
+        let pos = self.live(&hash)?;
+        Some(&mut self.internal[pos].tuple)
     }
 
+    /// Gets the version of the tuple stored with the hash that a read at the timestamp sees
+    pub fn get_version(&self, hash: &BigUint, timestamp: Timestamp) -> Option<&Tuple> {
+        self.internal
+            .iter()
+            .find(|version| &version.hash == hash && version.is_visible(timestamp))
+            .map(|version| &version.tuple)
+    }
+
+    /// Replaces the live version of the tuple stored with the hash, without keeping the version
+    /// it replaced
     fn insert_tuple(&mut self, hash: BigUint, tuple: Tuple) -> Option<Tuple> {
+        self.size += block_file::tuple_size(&hash, &tuple);
         if let Some(old) = self.get_tuple_mut(hash.clone()) {
-            Some(std::mem::replace(old, tuple))
+            let old = std::mem::replace(old, tuple);
+            self.size -= block_file::tuple_size(&hash, &old);
+            Some(old)
         } else {
-            self.internal.push((hash, tuple));
+            self.internal.push(Version::new(hash, tuple));
             None
         }
     }
 
+    /// Removes the live version of the tuple stored with the hash, without keeping it
     fn remove_tuple(&mut self, hash: BigUint) -> Option<Tuple> {
-        let pos = self.internal.iter().position(|(t_hash, _)| t_hash == &hash);
-        if let Some(pos) = pos {
-            Some(self.internal.remove(pos).1)
-        } else {
-            None
+        let pos = self.live(&hash)?;
+        let version = self.internal.remove(pos);
+        self.size -= version.size();
+        Some(version.tuple)
+    }
+
+    /// Whether a version of the tuple stored with the hash was created or deleted after the
+    /// timestamp
+    pub(super) fn changed_after(&self, hash: &BigUint, timestamp: Timestamp) -> bool {
+        self.internal.iter().any(|version| {
+            &version.hash == hash
+                && (version.created > timestamp
+                    || version.deleted.is_some_and(|deleted| deleted > timestamp))
+        })
+    }
+
+    /// Deletes the live version of the tuple stored with the hash at the timestamp, then adds the
+    /// tuple as a new version created at the timestamp, if there is one. Returns the change in the
+    /// number of live tuples.
+    fn write_version(
+        &mut self,
+        hash: BigUint,
+        tuple: Option<Tuple>,
+        timestamp: Timestamp,
+    ) -> isize {
+        let mut change = 0;
+        if let Some(pos) = self.live(&hash) {
+            self.internal[pos].deleted = Some(timestamp);
+            change -= 1;
         }
+        if let Some(tuple) = tuple {
+            let version = Version {
+                hash,
+                tuple,
+                created: timestamp,
+                deleted: None,
+            };
+            self.size += version.size();
+            self.internal.push(version);
+            change += 1;
+        }
+        change
+    }
+
+    /// Undoes the changes made to the tuple stored with the hash at the timestamp. Returns the
+    /// change in the number of live tuples.
+    fn undo_version(&mut self, hash: &BigUint, timestamp: Timestamp) -> isize {
+        let mut change = 0;
+        let mut size = self.size;
+        self.internal.retain(|version| {
+            let keep = &version.hash != hash || version.created != timestamp;
+            if !keep {
+                size -= version.size();
+                change -= 1;
+            }
+            keep
+        });
+        self.size = size;
+        for version in &mut self.internal {
+            if &version.hash == hash && version.deleted == Some(timestamp) {
+                version.deleted = None;
+                change += 1;
+            }
+        }
+        change
+    }
+
+    /// Removes every version that was deleted at or before the horizon, which no read can see
+    /// anymore, and lets every read see the versions created at or before it. Returns the number
+    /// of versions that were removed.
+    fn collect_garbage(&mut self, horizon: Timestamp) -> usize {
+        let before = self.internal.len();
+        let mut size = self.size;
+        self.internal.retain(|version| {
+            let keep = version.deleted.is_none_or(|deleted| deleted > horizon);
+            if !keep {
+                size -= version.size();
+            }
+            keep
+        });
+        self.size = size;
+        for version in &mut self.internal {
+            if version.created <= horizon {
+                version.created = 0;
+            }
+        }
+        before - self.internal.len()
+    }
+
+    /// Whether the block has versions that not every read sees. Only live versions are saved to
+    /// the block file, so the contents can't be unloaded until they're collected.
+    pub(super) fn has_history(&self) -> bool {
+        self.internal
+            .iter()
+            .any(|version| version.created != 0 || !version.is_live())
     }
 
-    pub fn get_tuple_from_inner(input: &(BigUint, Tuple)) -> &Tuple {
-        &input.1
+    /// Gets the number of versions in the block, including the ones that were deleted
+    pub(super) fn version_count(&self) -> usize {
+        self.internal.len()
     }
 
-    pub fn get_tuple_from_inner_mut(input: &mut (BigUint, Tuple)) -> &mut Tuple {
-        &mut input.1
+    /// Gets the live version of every tuple
+    pub fn all(&self) -> FilterMap<Iter<Version>, fn(&Version) -> Option<&Tuple>> {
+        self.internal.iter().filter_map(live_tuple)
     }
 
-    pub fn all(&self) -> Map<Iter<(BigUint, Tuple)>, fn(&(BigUint, Tuple)) -> &Tuple> {
-        self.internal.iter().map(Self::get_tuple_from_inner)
+    /// Gets the live version of every tuple with its hash
+    pub fn all_with_key(&self) -> impl Iterator<Item = (&BigUint, &Tuple)> {
+        self.internal
+            .iter()
+            .filter(|version| version.is_live())
+            .map(|version| (&version.hash, &version.tuple))
     }
 
-    pub fn all_with_key(&self) -> &Vec<(BigUint, Tuple)> {
-        &self.internal
+    /// Gets the version of every tuple that a read at the timestamp sees, with its hash
+    pub fn visible(&self, timestamp: Timestamp) -> impl Iterator<Item = (&BigUint, &Tuple)> {
+        self.internal
+            .iter()
+            .filter(move |version| version.is_visible(timestamp))
+            .map(|version| (&version.hash, &version.tuple))
     }
 
     pub fn all_mut(
         &mut self,
-    ) -> Map<IterMut<(BigUint, Tuple)>, fn(&mut (BigUint, Tuple)) -> &mut Tuple> {
-        self.internal.iter_mut().map(Self::get_tuple_from_inner_mut)
+    ) -> FilterMap<IterMut<Version>, fn(&mut Version) -> Option<&mut Tuple>> {
+        self.internal.iter_mut().filter_map(live_tuple_mut)
     }
 
-    fn take_all(&mut self) -> Vec<Tuple> {
-        let replace = std::mem::replace(&mut self.internal, Vec::new());
-        replace.into_iter().map(|(_, t)| t).collect()
+    /// Takes every version out of the block
+    fn take_versions(&mut self) -> Vec<Version> {
+        self.size = block_file::HEADER_SIZE;
+        std::mem::take(&mut self.internal)
     }
 
-    fn take_all_with_key(&mut self) -> Vec<(BigUint, Tuple)> {
-        std::mem::replace(&mut self.internal, Vec::new())
+    fn push_version(&mut self, version: Version) {
+        self.size += version.size();
+        self.internal.push(version);
     }
 }
 
@@ -569,7 +886,7 @@ impl IndexMut<BigUint> for BlockContents {
 
 impl<'a> IntoIterator for &'a BlockContents {
     type Item = &'a Tuple;
-    type IntoIter = Map<Iter<'a, (BigUint, Tuple)>, fn(&(BigUint, Tuple)) -> &Tuple>;
+    type IntoIter = FilterMap<Iter<'a, Version>, fn(&Version) -> Option<&Tuple>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.all()
@@ -578,7 +895,7 @@ impl<'a> IntoIterator for &'a BlockContents {
 
 impl<'a> IntoIterator for &'a mut BlockContents {
     type Item = &'a mut Tuple;
-    type IntoIter = Map<IterMut<'a, (BigUint, Tuple)>, fn(&mut (BigUint, Tuple)) -> &mut Tuple>;
+    type IntoIter = FilterMap<IterMut<'a, Version>, fn(&mut Version) -> Option<&mut Tuple>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.all_mut()
@@ -0,0 +1,284 @@
+//! The on-disk format of block files.
+//!
+//! A block file starts with the [MAGIC] bytes followed by a single format version byte. Every
+//! tuple after the header is stored as the length prefixed bytes of its hash, followed by its
+//! values in the binary encoding of [rad_db_types::encoding].
+//!
+//! Files written before the header existed store one `"{hash}:{values}"` line of text per tuple.
+//! These are still readable, and are rewritten in the current format the next time the block
+//! is unloaded.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+
+use rad_db_types::deserialization::{parse_using_types, ParseTupleFailure};
+use rad_db_types::encoding::{
+    decode_values, encode_values, read_length, write_length, DecodeError,
+};
+
+use crate::relations::RelationDefinition;
+use crate::tuple::Tuple;
+
+/// The bytes at the start of every binary block file
+pub const MAGIC: &[u8; 4] = b"RDBK";
+/// The current version of the block file format
+pub const BLOCK_FORMAT_VERSION: u8 = 1;
+/// The number of bytes the header of a binary block file takes up
+pub const HEADER_SIZE: usize = MAGIC.len() + 1;
+
+/// The versions of the block file format
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// Pipe delimited text, one tuple per line
+    Text,
+    /// Length prefixed, type tagged binary
+    Binary(u8),
+}
+
+/// When the contents of a block file couldn't be read
+#[derive(Debug)]
+pub enum BlockFileError {
+    /// The file has a header, but for a version that isn't known
+    UnknownVersion(u8),
+    /// A line of a text block file couldn't be parsed, where lines are counted from 1
+    Text { line: usize, reason: String },
+    /// The values on a line of a text block file couldn't be parsed, where lines are counted
+    /// from 1
+    Parse {
+        line: usize,
+        cause: ParseTupleFailure,
+    },
+    /// A tuple in a binary block file couldn't be decoded
+    Decode(DecodeError),
+}
+
+impl Display for BlockFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockFileError::UnknownVersion(version) => {
+                write!(f, "Unknown block format version {}", version)
+            }
+            BlockFileError::Text { line, reason } => {
+                write!(f, "Couldn't read line {} of text block: {}", line, reason)
+            }
+            BlockFileError::Parse { line, cause } => {
+                write!(f, "Couldn't read line {} of text block: {}", line, cause)
+            }
+            BlockFileError::Decode(e) => write!(f, "Couldn't decode block: {}", e),
+        }
+    }
+}
+
+impl Error for BlockFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BlockFileError::Parse { cause, .. } => Some(cause),
+            BlockFileError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for BlockFileError {
+    fn from(e: DecodeError) -> Self {
+        BlockFileError::Decode(e)
+    }
+}
+
+impl BlockFormat {
+    /// Determines the format of a block file from its contents
+    pub fn detect(contents: &[u8]) -> Self {
+        if contents.len() > MAGIC.len() && contents.starts_with(MAGIC) {
+            BlockFormat::Binary(contents[MAGIC.len()])
+        } else {
+            BlockFormat::Text
+        }
+    }
+}
+
+/// Reads all the tuples out of the contents of a block file, in whichever format it was written
+pub fn read_block(
+    contents: &[u8],
+    definition: &RelationDefinition,
+) -> Result<Vec<(BigUint, Tuple)>, BlockFileError> {
+    match BlockFormat::detect(contents) {
+        BlockFormat::Text => read_text_block(contents, definition),
+        BlockFormat::Binary(BLOCK_FORMAT_VERSION) => {
+            read_binary_block(&contents[MAGIC.len() + 1..])
+        }
+        BlockFormat::Binary(version) => Err(BlockFileError::UnknownVersion(version)),
+    }
+}
+
+fn read_text_block(
+    contents: &[u8],
+    definition: &RelationDefinition,
+) -> Result<Vec<(BigUint, Tuple)>, BlockFileError> {
+    let text_error = |line: usize, reason: String| BlockFileError::Text { line, reason };
+    let text = std::str::from_utf8(contents).map_err(|e| {
+        let line = contents[..e.valid_up_to()]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count();
+        text_error(line + 1, e.to_string())
+    })?;
+    let mut tuples = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        if line.is_empty() {
+            continue;
+        }
+        let mut split = line.splitn(2, ':');
+        let hash = split.next().unwrap();
+        let tuple_str = split
+            .next()
+            .ok_or_else(|| text_error(line_number, "missing hash separator".to_string()))?;
+
+        let hash = BigUint::from_str(hash).map_err(|e| text_error(line_number, e.to_string()))?;
+        let values =
+            parse_using_types(tuple_str, definition).map_err(|cause| BlockFileError::Parse {
+                line: line_number,
+                cause,
+            })?;
+        tuples.push((hash, Tuple::new(values)));
+    }
+    Ok(tuples)
+}
+
+fn read_binary_block(mut contents: &[u8]) -> Result<Vec<(BigUint, Tuple)>, BlockFileError> {
+    let mut tuples = vec![];
+    while !contents.is_empty() {
+        let hash_len = read_length(&mut contents)? as usize;
+        if hash_len > contents.len() {
+            return Err(DecodeError::UnexpectedEnd.into());
+        }
+        let (hash, rest) = contents.split_at(hash_len);
+        contents = rest;
+        let values = decode_values(&mut contents)?;
+        tuples.push((BigUint::from_bytes_le(hash), Tuple::new(values)));
+    }
+    Ok(tuples)
+}
+
+/// Writes the tuples of a block in the current block file format
+pub fn write_block<'a, W: Write, I: IntoIterator<Item = (&'a BigUint, &'a Tuple)>>(
+    writer: &mut W,
+    tuples: I,
+) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[BLOCK_FORMAT_VERSION])?;
+    let mut buffer = vec![];
+    for (hash, tuple) in tuples {
+        buffer.clear();
+        encode_tuple(&mut buffer, hash, tuple);
+        writer.write_all(&buffer)?;
+    }
+    Ok(())
+}
+
+/// Gets the number of bytes the tuples of a block take up in the current block file format
+pub fn encoded_size<'a, I: IntoIterator<Item = (&'a BigUint, &'a Tuple)>>(tuples: I) -> usize {
+    HEADER_SIZE
+        + tuples
+            .into_iter()
+            .map(|(hash, tuple)| tuple_size(hash, tuple))
+            .sum::<usize>()
+}
+
+/// Gets the number of bytes a single tuple takes up in the current block file format
+pub fn tuple_size(hash: &BigUint, tuple: &Tuple) -> usize {
+    let mut buffer = vec![];
+    encode_tuple(&mut buffer, hash, tuple);
+    buffer.len()
+}
+
+fn encode_tuple(buffer: &mut Vec<u8>, hash: &BigUint, tuple: &Tuple) {
+    let hash = hash.to_bytes_le();
+    write_length(buffer, hash.len() as u64);
+    buffer.extend_from_slice(&hash);
+    encode_values(buffer, tuple);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifier::Identifier;
+    use rad_db_types::{DataType, Type};
+    use std::iter::FromIterator;
+
+    fn definition() -> RelationDefinition {
+        RelationDefinition::from_iter(vec![
+            (Identifier::new("id"), DataType::UnsignedLong),
+            (Identifier::new("name"), DataType::String(None)),
+        ])
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let tuples = vec![
+            (
+                BigUint::from(3u64),
+                Tuple::from_iter(&[Type::from(3u64), Type::from("three")]),
+            ),
+            (
+                BigUint::from(u64::MAX) << 64,
+                Tuple::from_iter(&[Type::from(4u64), Type::from("a|b:c\n")]),
+            ),
+        ];
+        let mut buffer = vec![];
+        write_block(
+            &mut buffer,
+            tuples.iter().map(|(hash, tuple)| (hash, tuple)),
+        )
+        .unwrap();
+        assert_eq!(
+            BlockFormat::detect(&buffer),
+            BlockFormat::Binary(BLOCK_FORMAT_VERSION)
+        );
+        assert_eq!(read_block(&buffer, &definition()).unwrap(), tuples);
+    }
+
+    #[test]
+    fn read_legacy_text() {
+        let contents = "3:3|\"three\"\n4:4|\"four\"\n";
+        assert_eq!(BlockFormat::detect(contents.as_bytes()), BlockFormat::Text);
+        let tuples = read_block(contents.as_bytes(), &definition()).unwrap();
+        assert_eq!(
+            tuples,
+            vec![
+                (
+                    BigUint::from(3u64),
+                    Tuple::from_iter(&[Type::from(3u64), Type::from("three")])
+                ),
+                (
+                    BigUint::from(4u64),
+                    Tuple::from_iter(&[Type::from(4u64), Type::from("four")])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_parse_failure() {
+        let contents = "3:3|\"three\"\n4:four|\"four\"\n";
+        match read_block(contents.as_bytes(), &definition()).unwrap_err() {
+            BlockFileError::Parse { line, cause } => {
+                assert_eq!(line, 2);
+                assert_eq!(cause.column(), Some(0));
+            }
+            other => panic!("unexpected error {}", other),
+        }
+    }
+
+    #[test]
+    fn empty_block() {
+        assert!(read_block(&[], &definition()).unwrap().is_empty());
+        let mut buffer = vec![];
+        write_block(&mut buffer, std::iter::empty()).unwrap();
+        assert!(read_block(&buffer, &definition()).unwrap().is_empty());
+    }
+}
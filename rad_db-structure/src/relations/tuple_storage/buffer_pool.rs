@@ -0,0 +1,568 @@
+//! The buffer pool that decides which blocks have their contents loaded in memory.
+//!
+//! Every block that's saved to a file registers its contents with a [BufferPool] when they're
+//! loaded, along with how many bytes they take up. Blocks are pinned for as long as their contents
+//! are being used, and once the loaded blocks take up more than the budget of the pool, unpinned
+//! blocks are written back to their files and unloaded. Which block is unloaded first is chosen by
+//! an [EvictionPolicy], which can be swapped out while the pool is in use. A block that can't be
+//! written back stays loaded, and is tried again the next time the pool is over its budget.
+//!
+//! Unless they're given another pool, all blocks share the [global](BufferPool::global) pool.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use crate::relations::tuple_storage::block::Frame;
+
+/// The budget of the global buffer pool, in bytes
+pub const DEFAULT_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Identifies the contents of a block within a buffer pool
+pub type FrameId = usize;
+
+static NEXT_FRAME: AtomicUsize = AtomicUsize::new(0);
+
+static GLOBAL: OnceLock<Arc<BufferPool>> = OnceLock::new();
+
+/// Gets an id that no other frame has
+pub(super) fn next_frame_id() -> FrameId {
+    NEXT_FRAME.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Chooses which frame should be unloaded when a buffer pool is over its budget
+pub trait EvictionPolicy: Send {
+    /// A frame was loaded into the pool
+    fn admit(&mut self, frame: FrameId);
+    /// A frame that's already in the pool was used
+    fn access(&mut self, frame: FrameId);
+    /// A frame left the pool, either because it was evicted or because its block was dropped
+    fn remove(&mut self, frame: FrameId);
+    /// Chooses a frame to evict, out of the frames that the function says can be evicted
+    fn victim(&mut self, evictable: &dyn Fn(FrameId) -> bool) -> Option<FrameId>;
+}
+
+/// Evicts the frame that was used the longest time ago
+#[derive(Debug, Default)]
+pub struct Lru {
+    tick: u64,
+    last_used: HashMap<FrameId, u64>,
+    order: BTreeMap<u64, FrameId>,
+}
+
+impl Lru {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for Lru {
+    fn admit(&mut self, frame: FrameId) {
+        self.access(frame)
+    }
+
+    fn access(&mut self, frame: FrameId) {
+        self.tick += 1;
+        if let Some(last) = self.last_used.insert(frame, self.tick) {
+            self.order.remove(&last);
+        }
+        self.order.insert(self.tick, frame);
+    }
+
+    fn remove(&mut self, frame: FrameId) {
+        if let Some(last) = self.last_used.remove(&frame) {
+            self.order.remove(&last);
+        }
+    }
+
+    fn victim(&mut self, evictable: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        self.order.values().copied().find(|frame| evictable(*frame))
+    }
+}
+
+/// Approximates [Lru] by sweeping over the frames in a circle, evicting the first frame that
+/// hasn't been used since the last sweep passed it
+#[derive(Debug, Default)]
+pub struct Clock {
+    ring: Vec<FrameId>,
+    referenced: HashMap<FrameId, bool>,
+    hand: usize,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for Clock {
+    fn admit(&mut self, frame: FrameId) {
+        if self.referenced.insert(frame, true).is_none() {
+            self.ring.push(frame);
+        }
+    }
+
+    fn access(&mut self, frame: FrameId) {
+        if let Some(referenced) = self.referenced.get_mut(&frame) {
+            *referenced = true;
+        }
+    }
+
+    fn remove(&mut self, frame: FrameId) {
+        if self.referenced.remove(&frame).is_some() {
+            let position = self.ring.iter().position(|other| *other == frame).unwrap();
+            self.ring.remove(position);
+            if position < self.hand {
+                self.hand -= 1;
+            }
+            if self.hand >= self.ring.len() {
+                self.hand = 0;
+            }
+        }
+    }
+
+    fn victim(&mut self, evictable: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        // Two sweeps are enough to clear every reference bit once
+        for _ in 0..2 * self.ring.len() {
+            let frame = self.ring[self.hand];
+            self.hand = (self.hand + 1) % self.ring.len();
+            if !evictable(frame) {
+                continue;
+            }
+            let referenced = self.referenced.get_mut(&frame).unwrap();
+            if *referenced {
+                *referenced = false;
+            } else {
+                return Some(frame);
+            }
+        }
+        None
+    }
+}
+
+/// Evicts the frame that was used the fewest times since it was loaded, breaking ties by evicting
+/// the frame that was used the longest time ago
+#[derive(Debug, Default)]
+pub struct Lfu {
+    tick: u64,
+    uses: HashMap<FrameId, (u64, u64)>,
+}
+
+impl Lfu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for Lfu {
+    fn admit(&mut self, frame: FrameId) {
+        self.tick += 1;
+        self.uses.insert(frame, (1, self.tick));
+    }
+
+    fn access(&mut self, frame: FrameId) {
+        self.tick += 1;
+        if let Some((count, last)) = self.uses.get_mut(&frame) {
+            *count += 1;
+            *last = self.tick;
+        }
+    }
+
+    fn remove(&mut self, frame: FrameId) {
+        self.uses.remove(&frame);
+    }
+
+    fn victim(&mut self, evictable: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        self.uses
+            .iter()
+            .filter(|(frame, _)| evictable(**frame))
+            .min_by_key(|(_, uses)| **uses)
+            .map(|(frame, _)| *frame)
+    }
+}
+
+/// How a buffer pool has been used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStatistics {
+    /// The number of times a block was used while its contents were loaded
+    pub hits: u64,
+    /// The number of times the contents of a block had to be read from its file
+    pub misses: u64,
+    /// The number of times the contents of a block were unloaded to stay within the budget
+    pub evictions: u64,
+    /// The number of blocks that have their contents loaded
+    pub resident: usize,
+    /// The number of loaded blocks that are being used
+    pub pinned: usize,
+    /// The number of bytes the loaded contents take up
+    pub used: usize,
+}
+
+impl PoolStatistics {
+    /// The fraction of uses that didn't need to read a file, if there were any
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / total as f64)
+        }
+    }
+}
+
+struct Resident {
+    frame: Weak<Frame>,
+    size: usize,
+}
+
+struct PoolState {
+    capacity: usize,
+    used: usize,
+    policy: Box<dyn EvictionPolicy>,
+    frames: HashMap<FrameId, Resident>,
+    statistics: PoolStatistics,
+}
+
+impl PoolState {
+    /// Evicts unpinned frames until the pool is within its budget, or nothing else can be evicted
+    fn shrink(&mut self) {
+        let mut skipped = HashSet::new();
+        while self.used > self.capacity {
+            let frames = &self.frames;
+            let evictable = |id: FrameId| {
+                !skipped.contains(&id)
+                    && frames
+                        .get(&id)
+                        .and_then(|resident| resident.frame.upgrade())
+                        .is_some_and(|frame| !frame.is_pinned())
+            };
+            let victim = match self.policy.victim(&evictable) {
+                Some(victim) => victim,
+                None => return,
+            };
+            let evicted = match self.frames[&victim].frame.upgrade() {
+                Some(frame) => frame.try_evict().unwrap_or_else(|e| {
+                    log::warn!("Couldn't write block {:?}: {}", frame.file_name(), e);
+                    false
+                }),
+                None => true,
+            };
+            if evicted {
+                let resident = self.frames.remove(&victim).unwrap();
+                self.used -= resident.size;
+                self.policy.remove(victim);
+                self.statistics.evictions += 1;
+            } else {
+                // The frame was pinned after it was chosen, or couldn't be written back
+                skipped.insert(victim);
+            }
+        }
+    }
+}
+
+/// Keeps the loaded contents of blocks within a budget of bytes
+pub struct BufferPool {
+    state: Mutex<PoolState>,
+}
+
+impl BufferPool {
+    /// Creates a pool that can hold up to the number of bytes
+    pub fn new<P: EvictionPolicy + 'static>(capacity: usize, policy: P) -> Self {
+        BufferPool {
+            state: Mutex::new(PoolState {
+                capacity,
+                used: 0,
+                policy: Box::new(policy),
+                frames: HashMap::new(),
+                statistics: PoolStatistics::default(),
+            }),
+        }
+    }
+
+    /// Gets the pool that blocks use unless they're given another one, which holds up to
+    /// [DEFAULT_CAPACITY] bytes and evicts with [Lru]
+    pub fn global() -> Arc<BufferPool> {
+        GLOBAL
+            .get_or_init(|| Arc::new(BufferPool::new(DEFAULT_CAPACITY, Lru::new())))
+            .clone()
+    }
+
+    /// Gets the number of bytes the pool can hold
+    pub fn capacity(&self) -> usize {
+        self.state.lock().unwrap().capacity
+    }
+
+    /// Changes the number of bytes the pool can hold, evicting frames if it's now over its budget
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.capacity = capacity;
+        state.shrink();
+    }
+
+    /// Replaces the eviction policy. Every frame in the pool is admitted to the new policy, in no
+    /// particular order.
+    pub fn set_policy<P: EvictionPolicy + 'static>(&self, policy: P) {
+        let mut state = self.state.lock().unwrap();
+        let mut policy: Box<dyn EvictionPolicy> = Box::new(policy);
+        for id in state.frames.keys() {
+            policy.admit(*id);
+        }
+        state.policy = policy;
+    }
+
+    /// Gets how the pool has been used
+    pub fn statistics(&self) -> PoolStatistics {
+        let state = self.state.lock().unwrap();
+        let mut statistics = state.statistics;
+        statistics.resident = state.frames.len();
+        statistics.pinned = state
+            .frames
+            .values()
+            .filter_map(|resident| resident.frame.upgrade())
+            .filter(|frame| frame.is_pinned())
+            .count();
+        statistics.used = state.used;
+        statistics
+    }
+
+    /// Sets the hit, miss and eviction counts back to zero
+    pub fn reset_statistics(&self) {
+        self.state.lock().unwrap().statistics = PoolStatistics::default();
+    }
+
+    /// Records that a frame was used while it was loaded
+    pub(super) fn hit(&self, id: FrameId) {
+        let mut state = self.state.lock().unwrap();
+        state.statistics.hits += 1;
+        state.policy.access(id);
+    }
+
+    /// Adds a frame that was just loaded, evicting other frames if the pool is now over its budget
+    pub(super) fn admit(&self, frame: &Arc<Frame>, size: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.statistics.misses += 1;
+        let id = frame.id();
+        let resident = Resident {
+            frame: Arc::downgrade(frame),
+            size,
+        };
+        match state.frames.insert(id, resident) {
+            Some(old) => {
+                state.used -= old.size;
+                state.policy.access(id);
+            }
+            None => state.policy.admit(id),
+        }
+        state.used += size;
+        state.shrink();
+    }
+
+    /// Changes how many bytes a frame in the pool takes up
+    pub(super) fn resize(&self, id: FrameId, size: usize) {
+        let mut state = self.state.lock().unwrap();
+        let old = match state.frames.get_mut(&id) {
+            Some(resident) => std::mem::replace(&mut resident.size, size),
+            None => return,
+        };
+        state.used = state.used - old + size;
+        state.shrink();
+    }
+
+    /// Removes a frame that was unloaded by its block
+    pub(super) fn remove(&self, id: FrameId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(resident) = state.frames.remove(&id) {
+            state.used -= resident.size;
+            state.policy.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifier::Identifier;
+    use crate::relations::tuple_storage::block::Block;
+    use crate::relations::tuple_storage::block_file;
+    use crate::relations::RelationDefinition;
+    use crate::tuple::Tuple;
+    use num_bigint::BigUint;
+    use rad_db_types::{DataType, Type};
+    use std::iter::FromIterator;
+    use std::path::PathBuf;
+
+    fn create_blocks(test: &str, pool: &Arc<BufferPool>, count: usize) -> Vec<Block> {
+        let root = std::env::temp_dir().join("rad_db_buffer_pool_test");
+        let definition =
+            RelationDefinition::from_iter(vec![(Identifier::new("id"), DataType::UnsignedLong)]);
+        (0..count)
+            .map(|num| {
+                let mut block = Block::new_in_pool(
+                    pool.clone(),
+                    &root,
+                    Identifier::new(test),
+                    num,
+                    definition.clone(),
+                );
+                block.get_contents_mut().insert_tuple(
+                    BigUint::from(num),
+                    Tuple::from_iter(&[Type::from(num as u64)]),
+                );
+                block
+            })
+            .collect()
+    }
+
+    fn contents(block: &Block) -> Vec<Tuple> {
+        block.get_contents().all().cloned().collect()
+    }
+
+    fn loaded(pool: &BufferPool, blocks: &[Block]) -> Vec<bool> {
+        let frames = &pool.state.lock().unwrap().frames;
+        blocks
+            .iter()
+            .map(|block| frames.contains_key(&block.frame_id()))
+            .collect()
+    }
+
+    fn cleanup(test: &str) {
+        let path: PathBuf = std::env::temp_dir()
+            .join("rad_db_buffer_pool_test")
+            .join(test);
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn budget() {
+        let pool = Arc::new(BufferPool::new(usize::MAX, Lru::new()));
+        let blocks = create_blocks("budget", &pool, 4);
+        let size = pool.statistics().used / 4;
+        pool.reset_statistics();
+
+        pool.set_capacity(2 * size);
+        assert_eq!(pool.statistics().resident, 2);
+        assert_eq!(loaded(&pool, &blocks), vec![false, false, true, true]);
+
+        contents(&blocks[0]);
+        contents(&blocks[3]);
+        contents(&blocks[0]);
+        assert_eq!(loaded(&pool, &blocks), vec![true, false, false, true]);
+        assert_eq!(
+            contents(&blocks[2]),
+            vec![Tuple::from_iter(&[Type::from(2u64)])]
+        );
+        let statistics = pool.statistics();
+        assert_eq!((statistics.hits, statistics.misses), (2, 2));
+        assert_eq!(statistics.evictions, 4);
+        assert_eq!(statistics.used, 2 * size);
+        assert_eq!(loaded(&pool, &blocks), vec![true, false, true, false]);
+
+        let pinned = blocks[1].get_contents();
+        pool.set_capacity(0);
+        assert_eq!(loaded(&pool, &blocks), vec![false, true, false, false]);
+        assert_eq!(pool.statistics().pinned, 1);
+        assert_eq!(pinned.all().count(), 1);
+        std::mem::drop(pinned);
+
+        std::mem::drop(blocks);
+        assert_eq!(pool.statistics().used, 0);
+        cleanup("budget");
+    }
+
+    #[test]
+    fn policies() {
+        let clock = Arc::new(BufferPool::new(usize::MAX, Clock::new()));
+        let blocks = create_blocks("clock", &clock, 3);
+        let size = clock.statistics().used / 3;
+        contents(&blocks[0]);
+        clock.set_capacity(2 * size);
+        // Every frame is referenced, so the hand sweeps around once and evicts where it started
+        assert_eq!(loaded(&clock, &blocks), vec![false, true, true]);
+        contents(&blocks[0]);
+        assert_eq!(loaded(&clock, &blocks), vec![true, false, true]);
+        std::mem::drop(blocks);
+        cleanup("clock");
+
+        let lfu = Arc::new(BufferPool::new(usize::MAX, Lfu::new()));
+        let blocks = create_blocks("lfu", &lfu, 3);
+        let size = lfu.statistics().used / 3;
+        for _ in 0..3 {
+            contents(&blocks[0]);
+        }
+        contents(&blocks[1]);
+        lfu.set_capacity(2 * size);
+        assert_eq!(loaded(&lfu, &blocks), vec![true, true, false]);
+        contents(&blocks[2]);
+        assert_eq!(loaded(&lfu, &blocks), vec![true, false, true]);
+        std::mem::drop(blocks);
+        cleanup("lfu");
+    }
+
+    #[test]
+    fn legacy_blocks() {
+        let pool = Arc::new(BufferPool::new(usize::MAX, Lru::new()));
+        let blocks = create_blocks("legacy_blocks", &pool, 1);
+        pool.set_capacity(0);
+        let file_name = std::env::temp_dir()
+            .join("rad_db_buffer_pool_test")
+            .join("legacy_blocks")
+            .join("block_0.txt");
+        std::fs::write(&file_name, "0:0\n").unwrap();
+
+        assert_eq!(
+            contents(&blocks[0]),
+            vec![Tuple::from_iter(&[Type::from(0u64)])]
+        );
+        pool.set_capacity(0);
+        assert_eq!(loaded(&pool, &blocks), vec![false]);
+        let rewritten = std::fs::read(&file_name).unwrap();
+        assert!(rewritten.starts_with(block_file::MAGIC));
+        assert_eq!(
+            contents(&blocks[0]),
+            vec![Tuple::from_iter(&[Type::from(0u64)])]
+        );
+        std::mem::drop(blocks);
+        cleanup("legacy_blocks");
+    }
+
+    #[test]
+    fn failed_eviction() {
+        let pool = Arc::new(BufferPool::new(usize::MAX, Lru::new()));
+        let mut blocks = create_blocks("failed_eviction", &pool, 2);
+        let size = pool.statistics().used / 2;
+        assert_eq!(
+            size,
+            block_file::encoded_size(blocks[0].get_contents().all_with_key())
+        );
+        let tuple = Tuple::from_iter(&[Type::from(2u64)]);
+        let tuple_size = block_file::tuple_size(&BigUint::from(2u8), &tuple);
+        blocks[1]
+            .get_contents_mut()
+            .insert_tuple(BigUint::from(2u8), tuple);
+        assert_eq!(pool.statistics().used, 2 * size + tuple_size);
+        blocks[1]
+            .get_contents_mut()
+            .remove_tuple(BigUint::from(2u8));
+        assert_eq!(pool.statistics().used, 2 * size);
+
+        cleanup("failed_eviction");
+        pool.set_capacity(0);
+        assert_eq!(loaded(&pool, &blocks), vec![true, true]);
+        assert_eq!(pool.statistics().evictions, 0);
+
+        std::fs::create_dir_all(
+            std::env::temp_dir()
+                .join("rad_db_buffer_pool_test")
+                .join("failed_eviction"),
+        )
+        .unwrap();
+        pool.set_capacity(size);
+        assert_eq!(loaded(&pool, &blocks), vec![false, true]);
+        assert_eq!(
+            contents(&blocks[0]),
+            vec![Tuple::from_iter(&[Type::from(0u64)])]
+        );
+        std::mem::drop(blocks);
+        cleanup("failed_eviction");
+    }
+}
@@ -1,19 +1,23 @@
 use std::cell::UnsafeCell;
 use std::cmp::min;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt::{Debug, Formatter};
 use std::ops::{BitAnd, Deref, DerefMut, Not};
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 
 use num_bigint::{BigUint, ToBigUint};
 use num_traits::{One, ToPrimitive, Zero};
 
 use crate::identifier::Identifier;
-use crate::key::primary::{PrimaryKey, PrimaryKeyDefinition};
+use crate::key::primary::PrimaryKeyDefinition;
 use crate::relations::tuple_storage::block::{Block, InUse};
 use crate::relations::tuple_storage::lock::{Lock, LockRead, LockWrite};
+use crate::relations::tuple_storage::manifest::Manifest;
+use crate::relations::tuple_storage::version::{Clock, Timestamp};
 use crate::relations::tuple_storage::TupleStorage;
 use crate::relations::RelationDefinition;
+use crate::storage::DEFAULT_ROOT;
 use crate::tuple::Tuple;
 use crate::Rename;
 
@@ -36,10 +40,6 @@ impl Bucket {
     fn mask(&self) -> usize {
         mask(self.local_depth)
     }
-
-    fn is_empty(&self) -> bool {
-        self.len() == 0
-    }
 }
 
 fn mask(depth: usize) -> usize {
@@ -68,6 +68,7 @@ impl DerefMut for Bucket {
 /// The structure that maintains the buckets directory. The user only has control over the bucket size
 /// of the structure
 pub struct BlockDirectory {
+    storage_root: PathBuf,
     parent_table: Identifier,
     relationship_definition: RelationDefinition,
     bucket_lock: Lock,
@@ -79,17 +80,53 @@ pub struct BlockDirectory {
     mask: BigUint,
     primary_key_definition: PrimaryKeyDefinition,
     volatile: bool,
+    clock: Clock,
+    /// The number of reads that are open at each timestamp, whose versions are kept
+    reads: Mutex<BTreeMap<Timestamp, usize>>,
+}
+
+/// The outcome of making a change to a tuple without mutable access to the directory
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum VersionWrite {
+    Written,
+    /// The tuple was changed after the timestamp that the change was worked out at
+    Conflict,
+    /// The bucket the tuple belongs in has to be created or split first
+    Full,
+}
+
+/// How far a scan of the directories has read. Directories are scanned in the order of their
+/// numbers with the bits reversed, as expanding the directory splits each directory into two that
+/// are next to each other in that order, so a scan can go on from where it was after an expansion.
+#[derive(Debug, Clone, Default)]
+pub struct ScanCursor {
+    position: u64,
+    depth: usize,
+}
+
+impl ScanCursor {
+    /// Whether every directory has been scanned
+    pub fn is_finished(&self) -> bool {
+        self.position == 1 << self.depth
+    }
 }
 
+/// The buckets are only read while holding the bucket lock for reading, and only added or
+/// replaced while holding it for writing, and the contents of each bucket have their own lock
+unsafe impl Sync for BlockDirectory {}
+
 impl BlockDirectory {
-    /// Creates a new block directory with a specified bucket_size
-    pub fn new(
+    /// Creates a new block directory with a specified bucket_size, which saves its blocks under the
+    /// storage root
+    pub fn new<P: Into<PathBuf>>(
+        storage_root: P,
         parent_table: Identifier,
         relationship_definition: RelationDefinition,
         bucket_size: usize,
         primary_key_definition: PrimaryKeyDefinition,
     ) -> Self {
         BlockDirectory {
+            storage_root: storage_root.into(),
             parent_table,
             relationship_definition,
             bucket_lock: Default::default(),
@@ -100,6 +137,8 @@ impl BlockDirectory {
             mask: BigUint::one(),
             primary_key_definition,
             volatile: false,
+            clock: Default::default(),
+            reads: Default::default(),
         }
     }
 
@@ -110,6 +149,7 @@ impl BlockDirectory {
         primary_key_definition: PrimaryKeyDefinition,
     ) -> Self {
         BlockDirectory {
+            storage_root: PathBuf::from(DEFAULT_ROOT),
             parent_table,
             relationship_definition,
             bucket_lock: Default::default(),
@@ -120,27 +160,113 @@ impl BlockDirectory {
             mask: BigUint::one(),
             primary_key_definition,
             volatile: true,
+            clock: Default::default(),
+            reads: Default::default(),
         }
     }
 
-    pub(super) fn bucket_size(&self) -> usize {
-        self.bucket_size
-    }
-
-    fn hash_tuple(&self, tuple: &Tuple) -> BigUint {
-        let primary_key = self.get_primary_key_of_tuple(tuple);
-        primary_key.hash()
+    /// Recreates a block directory from its manifest. The blocks aren't loaded until they're used.
+    pub fn open<P: Into<PathBuf>>(
+        storage_root: P,
+        parent_table: Identifier,
+        relationship_definition: RelationDefinition,
+        manifest: Manifest,
+    ) -> Self {
+        let storage_root = storage_root.into();
+        let Manifest {
+            primary_key,
+            bucket_size,
+            global_depth,
+            buckets,
+            directories,
+            ..
+        } = manifest;
+        let buckets = buckets
+            .into_iter()
+            .enumerate()
+            .map(|(id, (local_depth, len))| {
+                let block = Block::open(
+                    &storage_root,
+                    parent_table.clone(),
+                    id,
+                    relationship_definition.clone(),
+                    len,
+                );
+                Box::new(Bucket {
+                    local_depth,
+                    block,
+                    mask: mask(local_depth).to_biguint().unwrap(),
+                })
+            })
+            .collect();
+        let mut ret = BlockDirectory {
+            storage_root,
+            parent_table,
+            relationship_definition,
+            bucket_lock: Default::default(),
+            buckets: UnsafeCell::new(buckets),
+            bucket_size,
+            global_depth,
+            directories: RwLock::new(directories.into_iter().collect()),
+            mask: BigUint::one(),
+            primary_key_definition: primary_key,
+            volatile: false,
+            clock: Default::default(),
+            reads: Default::default(),
+        };
+        ret.generate_mask();
+        ret
     }
 
-    fn get_primary_key_of_tuple<'a>(&self, tuple: &'a Tuple) -> PrimaryKey<'a> {
-        let definition = &self.primary_key_definition;
-        let ret = tuple
+    /// Gets the manifest that describes the directory as it is now
+    pub fn manifest(&self) -> Manifest {
+        let attributes = self
+            .relationship_definition
+            .identifier_iter()
+            .into_iter()
+            .map(|id| id.base().clone())
+            .zip(&self.relationship_definition)
+            .collect();
+        let buckets = {
+            let (buckets, _lock) = self.buckets();
+            buckets
+                .iter()
+                .map(|bucket| (bucket.local_depth, bucket.len()))
+                .collect()
+        };
+        let mut directories: Vec<_> = self
+            .directories
+            .read()
+            .unwrap()
             .iter()
-            .enumerate()
-            .filter(|(pos, _)| definition.contains(pos))
-            .map(|(_, val)| val)
+            .map(|(directory, bucket)| (directory.clone(), *bucket))
             .collect();
-        PrimaryKey::new(ret, definition.create_seeds())
+        directories.sort();
+        Manifest {
+            attributes,
+            primary_key: self.primary_key_definition.clone(),
+            bucket_size: self.bucket_size,
+            global_depth: self.global_depth,
+            buckets,
+            directories,
+        }
+    }
+
+    /// Saves the manifest of the directory, unless the directory is volatile
+    pub fn save_manifest(&self) -> std::io::Result<()> {
+        if self.volatile {
+            return Ok(());
+        }
+        self.manifest().save(&self.storage_root, &self.parent_table)
+    }
+
+    /// Gets the storage root that the blocks are saved under
+    pub fn storage_root(&self) -> &Path {
+        &self.storage_root
+    }
+
+    pub(super) fn bucket_size(&self) -> usize {
+        self.bucket_size
     }
 
     fn generate_mask(&mut self) {
@@ -195,13 +321,10 @@ impl BlockDirectory {
         let (mut buckets, _lock) = self.buckets_mut();
         let id = buckets.len();
         let block = if self.volatile {
-            Block::new_unbacked(
-                self.parent_table.clone(),
-                id,
-                self.relationship_definition.clone(),
-            )
+            Block::new_unbacked(self.relationship_definition.clone())
         } else {
             Block::new(
+                &self.storage_root,
                 self.parent_table.clone(),
                 id,
                 self.relationship_definition.clone(),
@@ -235,9 +358,14 @@ impl BlockDirectory {
         self.generate_mask();
     }
 
-    fn split_bucket(&mut self, bucket_index: usize, directory_number: &BigUint) {
+    /// Splits a full bucket in two, then saves the manifest with the new layout
+    fn split_bucket(
+        &mut self,
+        bucket_index: usize,
+        directory_number: &BigUint,
+    ) -> std::io::Result<()> {
         // println!("[BEFORE split] {:?}", self);
-        let (new_block_index, tuples, local_depth) = {
+        let (new_block_index, versions, local_depth) = {
             {
                 let expand = {
                     let (mut buckets, _lock) = self.buckets();
@@ -255,10 +383,10 @@ impl BlockDirectory {
             let local_depth = bucket.local_depth;
 
             let mut in_use = bucket.get_contents_mut();
-            let mut tuples = in_use.take_all();
+            let versions = in_use.take_versions();
             std::mem::drop(in_use);
             std::mem::drop(lock);
-            (self.create_new_bucket(local_depth), tuples, local_depth)
+            (self.create_new_bucket(local_depth), versions, local_depth)
         };
 
         {
@@ -294,10 +422,9 @@ impl BlockDirectory {
         //println!("[DURING split] {:?}", self);
         let (mut buckets, _lock) = self.buckets_mut();
 
-        for tuple in tuples {
-            let hash = self.hash_tuple(&tuple);
-
-            let dir = self.get_directory(&hash);
+        // Every version is moved, so reads at older timestamps still see them after the split
+        for version in versions {
+            let dir = self.get_directory(version.hash());
             let bucket_from_dir = self.directories.read().unwrap().get(&dir).cloned().unwrap();
             let as_usize = bucket_from_dir.to_usize().unwrap();
             let bucket = &mut buckets[as_usize];
@@ -313,9 +440,11 @@ impl BlockDirectory {
              */
             let mut use_mut = bucket.get_contents_mut();
 
-            use_mut.insert_tuple(hash, tuple);
+            use_mut.push_version(version);
         }
+        std::mem::drop(_lock);
         // println!("[AFTER split] {:#?}", self);
+        self.save_manifest()
     }
 
     fn get_bucket_num(&self, directory: &BigUint) -> Option<usize> {
@@ -339,6 +468,7 @@ impl BlockDirectory {
         let mut lock = self.directories.write().unwrap();
         let new_bucket = self.create_new_bucket(1);
         lock.insert(directory, new_bucket);
+        std::mem::drop(lock);
         let (buckets, _lock) = self.buckets();
         unsafe {
             let boxed = &*buckets[new_bucket] as *const Bucket;
@@ -363,6 +493,7 @@ impl BlockDirectory {
         let mut lock = self.directories.write().unwrap();
         let new_bucket = self.create_new_bucket(1);
         lock.insert(directory, new_bucket);
+        std::mem::drop(lock);
         let (buckets, _lock) = self.buckets_mut();
         unsafe {
             let boxed = &mut *buckets[new_bucket] as *mut Bucket;
@@ -371,16 +502,23 @@ impl BlockDirectory {
         }
     }
 
-    pub fn insert(&mut self, tuple: Tuple, full_hash: BigUint) -> Option<Tuple> {
+    /// Inserts a tuple, returning the tuple it replaced. The manifest is saved if a bucket had to
+    /// be created or split to make room for the tuple.
+    pub fn insert(&mut self, tuple: Tuple, full_hash: BigUint) -> std::io::Result<Option<Tuple>> {
         let (bucket, directory_number) = {
             let directory_number = self.get_directory(&full_hash);
+            if self.get_bucket_num(&directory_number).is_none() {
+                // Looking up the bucket of a directory without one creates it
+                self.get_bucket_from_directory(directory_number.clone());
+                self.save_manifest()?;
+            }
             let bucket_size = self.bucket_size;
             let bucket = self.get_bucket_from_directory(directory_number.clone());
             let len = bucket.len();
             if len == bucket_size {
                 // Overflow!
                 let bucket_num = self.get_bucket_num(&directory_number).unwrap();
-                self.split_bucket(bucket_num, &directory_number);
+                self.split_bucket(bucket_num, &directory_number)?;
                 return self.insert(tuple, full_hash);
             } else {
                 // easy insert
@@ -403,12 +541,214 @@ impl BlockDirectory {
                 )
             }
         }
-        ret
+        Ok(ret)
+    }
+
+    /// Removes the tuples stored with the hashes, then saves every block that a tuple was removed
+    /// from. Hashes that no tuple is stored with are skipped.
+    pub fn remove<I: IntoIterator<Item = BigUint>>(
+        &mut self,
+        full_hashes: I,
+    ) -> std::io::Result<Vec<Tuple>> {
+        let mut removed = vec![];
+        let mut changed = HashSet::new();
+        for full_hash in full_hashes {
+            let directory_number = self.get_directory(&full_hash);
+            let bucket_num = match self.get_bucket_num(&directory_number) {
+                Some(bucket_num) => bucket_num,
+                None => continue,
+            };
+            let bucket = self.get_bucket_from_directory_mut(directory_number);
+            let mut in_use = bucket.block.get_contents_mut();
+            if let Some(tuple) = in_use.remove_tuple(full_hash) {
+                removed.push(tuple);
+                changed.insert(bucket_num);
+            }
+        }
+
+        let (buckets, _lock) = self.buckets();
+        for bucket_num in changed {
+            buckets[bucket_num].block.flush()?;
+        }
+        Ok(removed)
+    }
+
+    /// Saves every block and syncs them to the disk
+    pub fn sync_all(&self) -> std::io::Result<()> {
+        let (buckets, _lock) = self.buckets();
+        for bucket in buckets {
+            bucket.block.sync()?;
+        }
+        Ok(())
     }
 
-    pub(super) fn get_bucket_for_primary_key(&self, full_hash: BigUint) -> &Bucket {
+    /// Makes room for a new tuple with the hash, by creating the bucket it belongs in, or by
+    /// splitting the bucket if it's full. The bucket can still be full afterwards, if every tuple
+    /// stays in the same half of the split.
+    pub(super) fn make_room(&mut self, full_hash: &BigUint) -> std::io::Result<()> {
+        let directory_number = self.get_directory(full_hash);
+        match self.get_bucket_num(&directory_number) {
+            None => {
+                self.get_bucket_from_directory(directory_number);
+                self.save_manifest()
+            }
+            Some(bucket_num) => {
+                let len = self
+                    .get_bucket_from_directory(directory_number.clone())
+                    .len();
+                if len >= self.bucket_size {
+                    self.split_bucket(bucket_num, &directory_number)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Deletes the live version of the tuple stored with the hash at the timestamp, and adds the
+    /// tuple as a new version created at the timestamp if there is one. The change is only made if
+    /// the tuple wasn't changed after the read timestamp, and if the bucket it belongs in has room.
+    pub(super) fn write_version(
+        &self,
+        full_hash: &BigUint,
+        tuple: Option<Tuple>,
+        read: Timestamp,
+        timestamp: Timestamp,
+    ) -> VersionWrite {
+        let bucket = match self.get_bucket_for_primary_key(full_hash.clone()) {
+            Some(bucket) => bucket,
+            None if tuple.is_none() => return VersionWrite::Written,
+            None => return VersionWrite::Full,
+        };
+        let mut contents = bucket.block.get_contents_mut();
+        if contents.changed_after(full_hash, read) {
+            return VersionWrite::Conflict;
+        }
+        let adds = tuple.is_some() && contents.get_tuple(full_hash.clone()).is_none();
+        if adds && bucket.len() >= self.bucket_size {
+            return VersionWrite::Full;
+        }
+        contents.write_version(full_hash.clone(), tuple, timestamp);
+        VersionWrite::Written
+    }
+
+    /// Undoes the change made to the tuple stored with the hash at the timestamp
+    pub(super) fn undo_version(&self, full_hash: &BigUint, timestamp: Timestamp) {
+        if let Some(bucket) = self.get_bucket_for_primary_key(full_hash.clone()) {
+            bucket
+                .block
+                .get_contents_mut()
+                .undo_version(full_hash, timestamp);
+        }
+    }
+
+    /// Gives a new commit to the directory its timestamp
+    pub(super) fn begin_commit(&self) -> Timestamp {
+        self.clock.begin_commit()
+    }
+
+    /// Ends a commit, after which reads can see the versions it made
+    pub(super) fn end_commit(&self, timestamp: Timestamp) {
+        self.clock.end_commit(timestamp)
+    }
+
+    /// Starts a read at the newest timestamp that reads can see. The versions the read sees are
+    /// kept until it's closed.
+    pub(super) fn open_read(&self) -> Timestamp {
+        let mut reads = self.reads.lock().unwrap();
+        // The timestamp is taken while the reads are locked, so versions it sees can't be collected
+        let timestamp = self.clock.visible();
+        *reads.entry(timestamp).or_default() += 1;
+        timestamp
+    }
+
+    /// Closes a read that was opened at the timestamp
+    pub(super) fn close_read(&self, timestamp: Timestamp) {
+        let mut reads = self.reads.lock().unwrap();
+        if let Some(count) = reads.get_mut(&timestamp) {
+            *count -= 1;
+            if *count == 0 {
+                reads.remove(&timestamp);
+            }
+        }
+    }
+
+    /// Removes every version that no open read, or read opened later, can see. Only blocks with
+    /// their contents loaded can have such versions. Returns the number of versions removed.
+    pub(super) fn collect_garbage(&self) -> usize {
+        let horizon = {
+            let reads = self.reads.lock().unwrap();
+            match reads.keys().next() {
+                Some(oldest) => *oldest,
+                None => self.clock.visible(),
+            }
+        };
+        let (buckets, _lock) = self.buckets();
+        buckets
+            .iter()
+            .map(|bucket| bucket.block.collect_garbage(horizon))
+            .sum()
+    }
+
+    /// Gets the number of versions of tuples in the directory, including deleted ones
+    pub(super) fn version_count(&self) -> usize {
+        let (buckets, _lock) = self.buckets();
+        buckets
+            .iter()
+            .map(|bucket| bucket.block.version_count())
+            .sum()
+    }
+
+    /// Gets a copy of the tuple stored with a hash, only loading the block it would be stored in
+    pub(super) fn get(&self, full_hash: &BigUint) -> Option<Tuple> {
+        self.get_at(full_hash, self.clock.visible())
+    }
+
+    /// Gets a copy of the version of the tuple stored with a hash that a read at the timestamp
+    /// sees
+    pub(super) fn get_at(&self, full_hash: &BigUint, timestamp: Timestamp) -> Option<Tuple> {
+        let bucket = self.get_bucket_for_primary_key(full_hash.clone())?;
+        let contents = bucket.block.get_contents();
+        contents.get_version(full_hash, timestamp).cloned()
+    }
+
+    /// Reads the tuples that a read at the timestamp sees, one directory at a time from where the
+    /// cursor is, until there are at least as many as the limit or every directory was read.
+    /// Buckets can be split in between scans, as a split needs mutable access to the directory.
+    pub(super) fn scan(
+        &self,
+        cursor: &mut ScanCursor,
+        timestamp: Timestamp,
+        limit: usize,
+    ) -> Vec<Tuple> {
+        let depth = self.global_depth;
+        let end = 1u64 << depth;
+        let mut position = cursor.position << (depth - cursor.depth);
+        let mut tuples = vec![];
+        while position < end && tuples.len() < limit {
+            let directory_number = BigUint::from(position.reverse_bits() >> (64 - depth));
+            if let Some(bucket) = self.get_bucket_for_primary_key(directory_number.clone()) {
+                let contents = bucket.block.get_contents();
+                // Buckets can be shared between directories, so only this directory's are read
+                tuples.extend(
+                    contents
+                        .visible(timestamp)
+                        .filter(|(hash, _)| self.get_directory(hash) == directory_number)
+                        .map(|(_, tuple)| tuple.clone()),
+                );
+            }
+            position += 1;
+        }
+        cursor.position = position;
+        cursor.depth = depth;
+        tuples
+    }
+
+    /// Gets the bucket that a tuple with the hash would be stored in, if the bucket exists
+    pub(super) fn get_bucket_for_primary_key(&self, full_hash: BigUint) -> Option<&Bucket> {
         let directory_number = self.get_directory(&full_hash);
-        self.get_bucket_from_directory(directory_number.clone())
+        self.get_bucket_num(&directory_number)?;
+        Some(self.get_bucket_from_directory(directory_number))
     }
 
     pub fn bucket_count(&self) -> usize {
@@ -444,20 +784,53 @@ impl BlockDirectory {
     }
 }
 
+/// Keeps the versions that a read at a timestamp sees until it's dropped
+pub(super) struct ReadTimestamp<'a> {
+    directory: &'a BlockDirectory,
+    timestamp: Timestamp,
+}
+
+impl<'a> ReadTimestamp<'a> {
+    fn open(directory: &'a BlockDirectory) -> Self {
+        ReadTimestamp {
+            directory,
+            timestamp: directory.open_read(),
+        }
+    }
+}
+
+impl Clone for ReadTimestamp<'_> {
+    fn clone(&self) -> Self {
+        let mut reads = self.directory.reads.lock().unwrap();
+        *reads.entry(self.timestamp).or_default() += 1;
+        ReadTimestamp {
+            directory: self.directory,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+impl Drop for ReadTimestamp<'_> {
+    fn drop(&mut self) {
+        self.directory.close_read(self.timestamp);
+    }
+}
+
 /// An iterator that goes through each block of the relation at a time. It _doesn't_ load every block
-/// into memory, and only does when the block is needed. Writes can not be made to the relation until
-/// after the iterator is dropped.
+/// into memory, and only does when the block is needed. Every block is read at the timestamp the
+/// iterator was created at, so changes committed while it's used aren't seen, and the buckets are
+/// only locked while a block is read.
 #[derive(Clone)]
 pub struct BlockIterator<'a> {
     bucket_num: usize,
     max_block_num: usize,
     directory: &'a BlockDirectory,
-    read: LockRead<'a>,
+    read: ReadTimestamp<'a>,
 }
 
 impl<'a> BlockIterator<'a> {
     fn new(directory: &'a BlockDirectory) -> Self {
-        let read = directory.bucket_lock.read();
+        let read = ReadTimestamp::open(directory);
         let max_block_num = directory.bucket_count();
 
         BlockIterator {
@@ -482,12 +855,18 @@ impl<'a> Iterator for BlockIterator<'a> {
         }
 
         while self.bucket_num < self.max_block_num {
-            let bucket = self.directory.bucket(self.bucket_num, &self.read).unwrap();
+            let lock = self.directory.bucket_lock.read();
+            let bucket = self.directory.bucket(self.bucket_num, &lock).unwrap();
             self.bucket_num += 1;
-            if !bucket.is_empty() {
+            if bucket.version_count() > 0 {
                 let contents = bucket.block.get_contents();
-                let ret: Vec<_> = contents.all().cloned().collect();
-                return Some(ret);
+                let ret: Vec<_> = contents
+                    .visible(self.read.timestamp)
+                    .map(|(_, tuple)| tuple.clone())
+                    .collect();
+                if !ret.is_empty() {
+                    return Some(ret);
+                }
             }
         }
         None
@@ -498,12 +877,12 @@ pub struct RepeatableBlockIterator<'a> {
     bucket_num: usize,
     max_block_num: usize,
     directory: &'a BlockDirectory,
-    read: LockRead<'a>,
+    read: ReadTimestamp<'a>,
 }
 
 impl<'a> RepeatableBlockIterator<'a> {
     fn new(directory: &'a BlockDirectory) -> Self {
-        let read = directory.bucket_lock.read();
+        let read = ReadTimestamp::open(directory);
         let max_block_num = directory.bucket_count();
 
         RepeatableBlockIterator {
@@ -553,19 +932,20 @@ impl<'a> IntoIterator for &RepeatableBlockIterator<'a> {
 }
 
 /// An iterator that goes through every tuple stored in relation. It _doesn't_ load every tuple
-/// into memory at once in order to save space in memory. When the iterator is produced, no
-/// writes can be made to the relation until the iterator is dropped.
+/// into memory at once in order to save space in memory. Every tuple is read at the timestamp the
+/// iterator was created at, so changes committed while it's used aren't seen, and the buckets are
+/// only locked while a block is read.
 pub struct StoredTupleIterator<'a> {
     buffer: VecDeque<Tuple>,
     bucket_num: usize,
     max_block_num: usize,
     directory: &'a BlockDirectory,
-    read: LockRead<'a>,
+    read: ReadTimestamp<'a>,
 }
 
 impl<'a> StoredTupleIterator<'a> {
     fn new(directory: &'a BlockDirectory) -> Self {
-        let read = directory.bucket_lock.read();
+        let read = ReadTimestamp::open(directory);
         let max_block_num = directory.bucket_count();
 
         StoredTupleIterator {
@@ -587,9 +967,10 @@ impl<'a> Iterator for StoredTupleIterator<'a> {
         }
 
         while self.buffer.is_empty() && self.bucket_num < self.max_block_num {
-            let block = self.directory.bucket(self.bucket_num, &self.read).unwrap();
+            let lock = self.directory.bucket_lock.read();
+            let block = self.directory.bucket(self.bucket_num, &lock).unwrap();
             let contents = block.get_contents();
-            for tuple in contents.all() {
+            for (_, tuple) in contents.visible(self.read.timestamp) {
                 self.buffer.push_back(tuple.clone())
             }
             self.bucket_num += 1;
@@ -622,8 +1003,10 @@ impl Rename<Identifier> for BlockDirectory {
 }
 
 impl Drop for BlockDirectory {
-    /// Concurrently drops all of the blocks in storage
+    /// Saves the manifest, then concurrently drops all of the blocks in storage. If the manifest
+    /// can't be saved, the one saved by the last checkpoint or split is kept.
     fn drop(&mut self) {
+        let _ = self.save_manifest();
         let buckets = std::mem::replace(&mut self.buckets, UnsafeCell::new(Vec::new()));
         let buckets = buckets.into_inner();
         let handles = buckets.into_iter().map(|bucket| {
@@ -0,0 +1,280 @@
+//! The manifest of a relation.
+//!
+//! Every relation that's saved to the file system has a manifest next to its block files, which
+//! holds everything about the relation that isn't stored in its blocks: the attributes, the
+//! primary key, the bucket size, and the layout of the hash directory. The
+//! manifest is text, with one entry per line after a header. The fields of an entry are separated
+//! by tabs, which are shown as spaces here:
+//!
+//! ```text
+//! RDBM  1
+//! bucket_size  4
+//! global_depth  1
+//! primary_key  0
+//! attribute  id  INT UNSIGNED
+//! attribute  name  VARCHAR(16) NULL
+//! bucket  1  3
+//! bucket  1  4
+//! directory  0  0
+//! directory  1  1
+//! ```
+//!
+//! `bucket` entries hold the local depth of each bucket and the number of tuples in its block, in
+//! the order of the buckets. The numbers of tuples match the blocks on disk when the manifest is
+//! saved by a checkpoint, which happens after every block is saved. `directory` entries map a
+//! directory number to the index of a bucket.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+
+use rad_db_types::DataType;
+
+use crate::identifier::Identifier;
+use crate::key::primary::PrimaryKeyDefinition;
+use crate::storage::{atomic_write, storage_path};
+
+/// The name of the manifest file in the directory of a relation
+pub const MANIFEST_FILE: &str = "manifest.txt";
+/// The first entry of every manifest
+pub const MAGIC: &str = "RDBM";
+/// The current version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// When the manifest of a relation couldn't be read
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The manifest file couldn't be opened or read
+    Io(PathBuf, std::io::Error),
+    /// The manifest has a header, but for a version that isn't known
+    UnknownVersion(u32),
+    /// An entry of the manifest is invalid or missing
+    Entry { line: usize, reason: String },
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Io(path, e) => write!(f, "Could not read manifest {:?}: {}", path, e),
+            ManifestError::UnknownVersion(version) => {
+                write!(f, "Unknown manifest version {}", version)
+            }
+            ManifestError::Entry { line, reason } => {
+                write!(f, "Couldn't read line {} of manifest: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl Error for ManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ManifestError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Everything about a relation that isn't stored in its blocks
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub attributes: Vec<(String, DataType)>,
+    pub primary_key: PrimaryKeyDefinition,
+    pub bucket_size: usize,
+    pub global_depth: usize,
+    /// The local depth of each bucket, and the number of tuples in its block
+    pub buckets: Vec<(usize, usize)>,
+    /// The directory numbers and the index of the bucket they point to
+    pub directories: Vec<(BigUint, usize)>,
+}
+
+/// Gets the path of the manifest of a relation saved under the storage root
+pub fn manifest_path(storage_root: &Path, table: &Identifier) -> PathBuf {
+    let mut ret = storage_path(storage_root, table);
+    ret.push(MANIFEST_FILE);
+    ret
+}
+
+fn parse_entry<T: FromStr>(line: usize, text: &str) -> Result<T, ManifestError>
+where
+    T::Err: Display,
+{
+    text.parse().map_err(|e: T::Err| ManifestError::Entry {
+        line,
+        reason: format!("{:?}: {}", text, e),
+    })
+}
+
+impl Manifest {
+    /// Reads the manifest of a relation saved under the storage root
+    pub fn read(storage_root: &Path, table: &Identifier) -> Result<Self, ManifestError> {
+        let path = manifest_path(storage_root, table);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(e) => Err(ManifestError::Io(path, e)),
+        }
+    }
+
+    /// Parses the contents of a manifest file
+    pub fn parse(contents: &str) -> Result<Self, ManifestError> {
+        let entry_error = |line: usize, reason: &str| ManifestError::Entry {
+            line,
+            reason: reason.to_string(),
+        };
+        let mut lines = contents.lines().enumerate();
+        let header: Option<Vec<&str>> =
+            lines.next().map(|(_, header)| header.split('\t').collect());
+        match header {
+            Some(header) if header.len() == 2 && header[0] == MAGIC => {
+                let version = parse_entry(0, header[1])?;
+                if version != MANIFEST_VERSION {
+                    return Err(ManifestError::UnknownVersion(version));
+                }
+            }
+            _ => return Err(entry_error(0, "missing header")),
+        }
+
+        let mut attributes = vec![];
+        let mut primary_key = None;
+        let mut bucket_size = None;
+        let mut global_depth = None;
+        let mut buckets = vec![];
+        let mut directories = vec![];
+        for (line, text) in lines {
+            if text.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = text.split('\t').collect();
+            match fields.as_slice() {
+                ["bucket_size", size] => bucket_size = Some(parse_entry(line, size)?),
+                ["global_depth", depth] => global_depth = Some(parse_entry(line, depth)?),
+                ["primary_key", positions @ ..] => {
+                    let positions = positions
+                        .iter()
+                        .map(|position| parse_entry(line, position))
+                        .collect::<Result<Vec<usize>, _>>()?;
+                    primary_key = Some(PrimaryKeyDefinition::new(positions));
+                }
+                ["attribute", name, data_type] => {
+                    attributes.push((name.to_string(), parse_entry(line, data_type)?))
+                }
+                ["bucket", local_depth, len] => {
+                    buckets.push((parse_entry(line, local_depth)?, parse_entry(line, len)?))
+                }
+                ["directory", directory, bucket] => {
+                    let bucket = parse_entry(line, bucket)?;
+                    if bucket >= buckets.len() {
+                        return Err(entry_error(line, "directory to a missing bucket"));
+                    }
+                    directories.push((parse_entry(line, directory)?, bucket));
+                }
+                _ => return Err(entry_error(line, "unknown entry")),
+            }
+        }
+
+        let end = contents.lines().count();
+        let primary_key: PrimaryKeyDefinition =
+            primary_key.ok_or_else(|| entry_error(end, "missing primary key"))?;
+        if primary_key
+            .iter()
+            .any(|position| *position >= attributes.len())
+        {
+            return Err(entry_error(end, "primary key of a missing attribute"));
+        }
+        Ok(Manifest {
+            attributes,
+            primary_key,
+            bucket_size: bucket_size.ok_or_else(|| entry_error(end, "missing bucket size"))?,
+            global_depth: global_depth.ok_or_else(|| entry_error(end, "missing global depth"))?,
+            buckets,
+            directories,
+        })
+    }
+
+    /// Writes the manifest in the current manifest format
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "{}\t{}", MAGIC, MANIFEST_VERSION)?;
+        writeln!(writer, "bucket_size\t{}", self.bucket_size)?;
+        writeln!(writer, "global_depth\t{}", self.global_depth)?;
+        write!(writer, "primary_key")?;
+        for position in self.primary_key.iter() {
+            write!(writer, "\t{}", position)?;
+        }
+        writeln!(writer)?;
+        for (name, data_type) in &self.attributes {
+            writeln!(writer, "attribute\t{}\t{}", name, data_type)?;
+        }
+        for (local_depth, len) in &self.buckets {
+            writeln!(writer, "bucket\t{}\t{}", local_depth, len)?;
+        }
+        for (directory, bucket) in &self.directories {
+            writeln!(writer, "directory\t{}\t{}", directory, bucket)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the manifest of a relation under the storage root
+    pub fn save(&self, storage_root: &Path, table: &Identifier) -> std::io::Result<()> {
+        let path = manifest_path(storage_root, table);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let mut buffer = vec![];
+        self.write(&mut buffer)?;
+        atomic_write(path, &buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        Manifest {
+            attributes: vec![
+                ("id".to_string(), DataType::UnsignedInt),
+                ("name".to_string(), DataType::String(Some(16)).optional()),
+            ],
+            primary_key: PrimaryKeyDefinition::new(vec![0]),
+            bucket_size: 4,
+            global_depth: 1,
+            buckets: vec![(1, 3), (1, 4)],
+            directories: vec![(BigUint::from(0u8), 0), (BigUint::from(1u8), 1)],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = vec![];
+        manifest().write(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with("RDBM\t1\n"));
+        let parsed = Manifest::parse(&text).unwrap();
+        assert_eq!(parsed.attributes, manifest().attributes);
+        assert_eq!(*parsed.primary_key, vec![0]);
+        assert_eq!(parsed.bucket_size, 4);
+        assert_eq!(parsed.global_depth, 1);
+        assert_eq!(parsed.buckets, manifest().buckets);
+        assert_eq!(parsed.directories, manifest().directories);
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        match Manifest::parse("RDBM\t2\n") {
+            Err(ManifestError::UnknownVersion(2)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match Manifest::parse("RDBM\t1\nbucket_size\t4\nattribute\tid\tNUMBER\n") {
+            Err(ManifestError::Entry { line: 2, .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match Manifest::parse("RDBM\t1\nbucket_size\t4\nglobal_depth\t1\n") {
+            Err(ManifestError::Entry { reason, .. }) => assert_eq!(reason, "missing primary key"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(Manifest::parse("RDBM\t1\ndirectory\t0\t0\n").is_err());
+        assert!(Manifest::parse("RDBM\t1\nbucket\t1\n").is_err());
+    }
+}
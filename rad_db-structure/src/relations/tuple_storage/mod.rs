@@ -1,29 +1,112 @@
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use num_bigint::BigUint;
 
+use rad_db_types::{DataType, Numeric, Text, Type, Value};
+
+pub use block::LoadError;
+pub use buffer_pool::{
+    BufferPool, Clock, EvictionPolicy, FrameId, Lfu, Lru, PoolStatistics, DEFAULT_CAPACITY,
+};
+pub(crate) use extendible_hashing::ScanCursor;
 pub use extendible_hashing::{BlockIterator, StoredTupleIterator};
+pub use manifest::ManifestError;
+pub use version::Timestamp;
+pub use wal::LogError;
 
 use crate::identifier::Identifier;
 use crate::key::primary::{PrimaryKey, PrimaryKeyDefinition};
-use crate::relations::tuple_storage::extendible_hashing::BlockDirectory;
+use crate::relations::tuple_storage::extendible_hashing::{BlockDirectory, VersionWrite};
+use crate::relations::tuple_storage::manifest::Manifest;
+use crate::relations::tuple_storage::wal::{log_path, LogRecord, WriteAheadLog, CHECKPOINT_SIZE};
 use crate::relations::RelationDefinition;
 use crate::tuple::Tuple;
 use crate::Rename;
 
 mod block;
+mod block_file;
+mod buffer_pool;
 mod extendible_hashing;
 mod lock;
+mod manifest;
+mod version;
+mod wal;
+
+/// Why changes couldn't be committed as new versions of tuples
+#[derive(Debug)]
+pub(crate) enum VersionError {
+    /// A tuple was changed by another commit after the changes were worked out
+    Conflict,
+    /// The bucket that the tuple with the hash belongs in has to be created or split first
+    Full(BigUint),
+    /// The log has grown too large, and has to be checkpointed first
+    Checkpoint,
+    /// The changes couldn't be logged
+    Io(std::io::Error),
+}
 
 /// When a tuple couldn't be inserted for some reason
 #[derive(Debug)]
 pub enum TupleInsertionError {
     PrimaryKeyPresent,
+    /// A tuple with a different primary key is stored with the same hash, so both can't be stored
+    HashCollision,
+    /// A merged tuple has a different primary key than the tuples it was merged from
+    MergeChangedKey,
+    /// The tuple doesn't have one value for every attribute of the relation
+    WrongArity {
+        expected: usize,
+        found: usize,
+    },
+    /// The values at these indexes aren't of the type of their attribute
     IncorrectTypes(Vec<usize>),
+    /// The value at this index is NULL, but its attribute isn't nullable
+    UnexpectedNull(usize),
+    /// A string or binary string is longer than its attribute allows
+    TooLong {
+        index: usize,
+        length: usize,
+        max: usize,
+    },
+    /// The insertion couldn't be written to the log or saved
+    Io(std::io::Error),
+}
+
+impl PartialEq for TupleInsertionError {
+    /// Io errors are compared by their kind
+    fn eq(&self, other: &Self) -> bool {
+        use TupleInsertionError::*;
+        match (self, other) {
+            (PrimaryKeyPresent, PrimaryKeyPresent)
+            | (HashCollision, HashCollision)
+            | (MergeChangedKey, MergeChangedKey) => true,
+            (
+                WrongArity { expected, found },
+                WrongArity {
+                    expected: e,
+                    found: f,
+                },
+            ) => (expected, found) == (e, f),
+            (IncorrectTypes(indexes), IncorrectTypes(other)) => indexes == other,
+            (UnexpectedNull(index), UnexpectedNull(other)) => index == other,
+            (
+                TooLong { index, length, max },
+                TooLong {
+                    index: i,
+                    length: l,
+                    max: m,
+                },
+            ) => (index, length, max) == (i, l, m),
+            (Io(e), Io(other)) => e.kind() == other.kind(),
+            _ => false,
+        }
+    }
 }
 
 impl Display for TupleInsertionError {
@@ -32,42 +115,334 @@ impl Display for TupleInsertionError {
             TupleInsertionError::PrimaryKeyPresent => {
                 write!(f, "Couldn't insert tuple, primary key already present")
             }
+            TupleInsertionError::HashCollision => {
+                write!(f, "Couldn't insert tuple, another key has the same hash")
+            }
+            TupleInsertionError::MergeChangedKey => {
+                write!(f, "Couldn't merge tuples, the primary key was changed")
+            }
+            TupleInsertionError::WrongArity { expected, found } => write!(
+                f,
+                "Couldn't insert tuple, expected {} values but found {}",
+                expected, found
+            ),
             TupleInsertionError::IncorrectTypes(vec) => {
                 write!(f, "Invalid types at indexes {:?}", vec)
             }
+            TupleInsertionError::UnexpectedNull(index) => {
+                write!(f, "Value at index {} is NULL, but can't be", index)
+            }
+            TupleInsertionError::TooLong { index, length, max } => write!(
+                f,
+                "Value at index {} has a length of {}, but the maximum is {}",
+                index, length, max
+            ),
+            TupleInsertionError::Io(e) => write!(f, "Couldn't save insertion: {}", e),
         }
     }
 }
 
-impl Error for TupleInsertionError {}
+impl Error for TupleInsertionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TupleInsertionError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TupleInsertionError {
+    fn from(e: std::io::Error) -> Self {
+        TupleInsertionError::Io(e)
+    }
+}
 
 pub type InsertionResult<T> = Result<T, TupleInsertionError>;
 
+/// When the values of a primary key don't match the primary key definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyArityError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl Display for KeyArityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Expected {} key values but found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for KeyArityError {}
+
+/// When tuples couldn't be deleted for some reason
+#[derive(Debug)]
+pub enum TupleDeletionError {
+    /// The key doesn't have one value for every attribute of the primary key
+    WrongArity { expected: usize, found: usize },
+    /// A block that tuples were deleted from couldn't be saved
+    Io(std::io::Error),
+}
+
+impl Display for TupleDeletionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TupleDeletionError::WrongArity { expected, found } => write!(
+                f,
+                "Couldn't delete tuple, expected {} key values but found {}",
+                expected, found
+            ),
+            TupleDeletionError::Io(e) => write!(f, "Couldn't save deletion: {}", e),
+        }
+    }
+}
+
+impl Error for TupleDeletionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TupleDeletionError::WrongArity { .. } => None,
+            TupleDeletionError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<KeyArityError> for TupleDeletionError {
+    fn from(e: KeyArityError) -> Self {
+        let KeyArityError { expected, found } = e;
+        TupleDeletionError::WrongArity { expected, found }
+    }
+}
+
+impl From<std::io::Error> for TupleDeletionError {
+    fn from(e: std::io::Error) -> Self {
+        TupleDeletionError::Io(e)
+    }
+}
+
+pub type DeletionResult<T> = Result<T, TupleDeletionError>;
+
+/// When tuples couldn't be updated for some reason
+#[derive(Debug)]
+pub enum TupleUpdateError {
+    /// The key doesn't have one value for every attribute of the primary key
+    WrongArity { expected: usize, found: usize },
+    /// An assignment is to an attribute that isn't in the relation
+    UnknownField(Identifier),
+    /// The updated tuple couldn't be stored, either because it doesn't fit the relation or because
+    /// its primary key was changed to one that's already present
+    Invalid(TupleInsertionError),
+    /// A block that tuples were updated in couldn't be saved
+    Io(std::io::Error),
+}
+
+impl Display for TupleUpdateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TupleUpdateError::WrongArity { expected, found } => write!(
+                f,
+                "Couldn't update tuple, expected {} key values but found {}",
+                expected, found
+            ),
+            TupleUpdateError::UnknownField(field) => {
+                write!(f, "Couldn't update tuples, no field named {}", field)
+            }
+            TupleUpdateError::Invalid(e) => write!(f, "Couldn't update tuple: {}", e),
+            TupleUpdateError::Io(e) => write!(f, "Couldn't save update: {}", e),
+        }
+    }
+}
+
+impl Error for TupleUpdateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TupleUpdateError::WrongArity { .. } | TupleUpdateError::UnknownField(_) => None,
+            TupleUpdateError::Invalid(e) => Some(e),
+            TupleUpdateError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<KeyArityError> for TupleUpdateError {
+    fn from(e: KeyArityError) -> Self {
+        let KeyArityError { expected, found } = e;
+        TupleUpdateError::WrongArity { expected, found }
+    }
+}
+
+impl From<TupleInsertionError> for TupleUpdateError {
+    fn from(e: TupleInsertionError) -> Self {
+        match e {
+            TupleInsertionError::Io(e) => TupleUpdateError::Io(e),
+            e => TupleUpdateError::Invalid(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for TupleUpdateError {
+    fn from(e: std::io::Error) -> Self {
+        TupleUpdateError::Io(e)
+    }
+}
+
+pub type UpdateResult<T> = Result<T, TupleUpdateError>;
+
+/// When a relation that was saved to the file system couldn't be reopened
+#[derive(Debug)]
+pub enum OpenError {
+    /// The manifest couldn't be read
+    Manifest(ManifestError),
+    /// The log couldn't be read
+    Log(LogError),
+    /// A block file couldn't be read
+    Block(LoadError),
+    /// The changes in the log couldn't be saved to the blocks
+    Io(std::io::Error),
+}
+
+impl Display for OpenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenError::Manifest(e) => write!(f, "{}", e),
+            OpenError::Log(e) => write!(f, "{}", e),
+            OpenError::Block(e) => write!(f, "{}", e),
+            OpenError::Io(e) => write!(f, "Couldn't save replayed log: {}", e),
+        }
+    }
+}
+
+impl Error for OpenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OpenError::Manifest(e) => Some(e),
+            OpenError::Log(e) => Some(e),
+            OpenError::Block(e) => Some(e),
+            OpenError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<ManifestError> for OpenError {
+    fn from(e: ManifestError) -> Self {
+        OpenError::Manifest(e)
+    }
+}
+
+impl From<LogError> for OpenError {
+    fn from(e: LogError) -> Self {
+        OpenError::Log(e)
+    }
+}
+
+impl From<LoadError> for OpenError {
+    fn from(e: LoadError) -> Self {
+        OpenError::Block(e)
+    }
+}
+
+impl From<std::io::Error> for OpenError {
+    fn from(e: std::io::Error) -> Self {
+        OpenError::Io(e)
+    }
+}
+
+/// What to do when a tuple is inserted with the same primary key as a stored tuple
+#[derive(Default)]
+pub enum ConflictPolicy<'a> {
+    /// Fail with [TupleInsertionError::PrimaryKeyPresent]
+    #[default]
+    Reject,
+    /// Keep the stored tuple, and discard the new one
+    Ignore,
+    /// Replace the stored tuple with the new one
+    Replace,
+    /// Replace the stored tuple with the result of merging it with the new one. The merged tuple
+    /// must have the same primary key.
+    Merge(&'a mut dyn FnMut(&Tuple, Tuple) -> Tuple),
+}
+
+/// What happened when a tuple was inserted
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertOutcome {
+    /// There was no tuple with the same primary key
+    Inserted,
+    /// There was a tuple with the same primary key, and it was kept
+    Ignored,
+    /// The stored tuple was replaced, and this is the tuple that was replaced
+    Replaced(Tuple),
+    /// The stored tuple was merged with the new one, and this is the tuple before the merge
+    Merged(Tuple),
+}
+
+/// The length of a value and the maximum length its type allows, if the type limits its length
+fn length_limit(data_type: &DataType, value: &Value) -> Option<(usize, usize)> {
+    match (data_type, value) {
+        (DataType::Optional(inner), Type::Optional(Some(value))) => length_limit(inner, value),
+        (DataType::Optional(inner), value) => length_limit(inner, value),
+        (DataType::String(Some(max)), Type::Text(Text::String(string, _))) => {
+            Some((string.len(), *max as usize))
+        }
+        (DataType::BinaryString(width), Type::Text(Text::BinaryString(bytes, _))) => {
+            Some((bytes.len(), *width as usize))
+        }
+        _ => None,
+    }
+}
+
+/// Rescales a decimal to the precision and scale of its attribute, so that every decimal of an
+/// attribute is stored the same way. The value must be accepted by the type.
+fn rescale(data_type: &DataType, value: Value) -> Value {
+    match (data_type, value) {
+        (DataType::Optional(inner), Type::Optional(Some(value))) => {
+            Type::Optional(Some(Box::new(rescale(inner, *value))))
+        }
+        (DataType::Optional(inner), value) => rescale(inner, value),
+        (DataType::Decimal { precision, scale }, Type::Numeric(Numeric::Decimal(decimal))) => {
+            let rescaled = decimal
+                .rescale(*precision, *scale)
+                .expect("Accepted decimals fit their type");
+            Numeric::Decimal(rescaled).into()
+        }
+        (_, value) => value,
+    }
+}
+
 #[derive(Debug)]
 pub struct TupleStorage {
     identifier: Identifier,
     relation: RelationDefinition,
     primary_key_definition: PrimaryKeyDefinition,
     true_storage: BlockDirectory,
+    /// Every change is appended to the log before it's made, unless the storage is volatile. It's
+    /// locked so that versions can be committed without mutable access to the storage.
+    log: Option<Mutex<WriteAheadLog>>,
 }
 
 impl TupleStorage {
-    pub fn new(
+    /// Creates a storage that saves its blocks under the storage root
+    pub fn new<P: Into<PathBuf>>(
+        storage_root: P,
         identifier: Identifier,
         relation: RelationDefinition,
         primary_key_definition: PrimaryKeyDefinition,
         max_size: usize,
     ) -> Self {
+        let storage_root = storage_root.into();
+        let log = WriteAheadLog::new(log_path(&storage_root, &identifier));
         Self {
             identifier: identifier.clone(),
             relation: relation.clone(),
             primary_key_definition: primary_key_definition.clone(),
             true_storage: BlockDirectory::new(
+                storage_root,
                 identifier,
                 relation,
                 max_size,
                 primary_key_definition,
             ),
+            log: Some(Mutex::new(log)),
         }
     }
 
@@ -87,11 +462,251 @@ impl TupleStorage {
                 max_size,
                 primary_key_definition,
             ),
+            log: None,
         }
     }
 
+    /// Reopens the storage of a relation that was saved under the storage root, using its manifest.
+    /// The changes in the log are replayed onto the blocks, then the storage checkpoints.
+    pub fn open<P: Into<PathBuf>>(
+        storage_root: P,
+        identifier: Identifier,
+    ) -> Result<Self, OpenError> {
+        let storage_root = storage_root.into();
+        let manifest = Manifest::read(&storage_root, &identifier)?;
+        let log_path = log_path(&storage_root, &identifier);
+        let records = WriteAheadLog::read(&log_path)?;
+        let relation = RelationDefinition::new(
+            manifest
+                .attributes
+                .iter()
+                .map(|(name, ty)| (Identifier::with_parent(&identifier, name), ty.clone()))
+                .collect(),
+        );
+        let mut ret = Self {
+            identifier: identifier.clone(),
+            relation: relation.clone(),
+            primary_key_definition: manifest.primary_key.clone(),
+            true_storage: BlockDirectory::open(storage_root, identifier, relation, manifest),
+            log: Some(Mutex::new(WriteAheadLog::new(log_path))),
+        };
+        for record in records {
+            ret.apply(record)?;
+        }
+        ret.checkpoint()?;
+        Ok(ret)
+    }
+
+    /// Saves every block and the manifest, then truncates the log, as every change in the log is
+    /// now in the blocks
+    pub fn checkpoint(&mut self) -> std::io::Result<()> {
+        let log = match &mut self.log {
+            Some(log) => log.get_mut().unwrap(),
+            None => return Ok(()),
+        };
+        self.true_storage.sync_all()?;
+        self.true_storage.save_manifest()?;
+        log.truncate()
+    }
+
+    /// Appends the records to the log, checkpointing first if the log has grown too large
+    fn write_log(&mut self, records: &[LogRecord]) -> std::io::Result<()> {
+        let size = self.log.as_mut().map(|log| log.get_mut().unwrap().size());
+        if size >= Some(CHECKPOINT_SIZE) {
+            self.checkpoint()?;
+        }
+        match &mut self.log {
+            Some(log) => log.get_mut().unwrap().append(records),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the change of a record to the blocks, without logging it
+    fn apply(&mut self, record: LogRecord) -> std::io::Result<()> {
+        match record {
+            LogRecord::Put(hash, tuple) => {
+                self.true_storage.insert(tuple, hash)?;
+            }
+            LogRecord::Delete(hash) => {
+                self.true_storage.remove(vec![hash])?;
+            }
+            LogRecord::Batch(records) => {
+                for record in records {
+                    self.apply(record)?;
+                }
+            }
+            LogRecord::Prepare {
+                commit_marker,
+                records,
+            } => {
+                if Path::new(&commit_marker).exists() {
+                    self.apply(LogRecord::Batch(records))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the changes of a transaction, where each hash is paired with the tuple it ends up
+    /// storing or `None` if the tuple is deleted. The changes are logged as a single record, so
+    /// that either all of them are replayed or none of them are.
+    pub(crate) fn commit_changes(
+        &mut self,
+        changes: Vec<(BigUint, Option<Tuple>)>,
+    ) -> std::io::Result<()> {
+        let batch = LogRecord::Batch(change_records(changes));
+        self.write_log(std::slice::from_ref(&batch))?;
+        self.apply(batch)
+    }
+
+    /// Makes the prepared changes of a transaction over more than one relation, once its commit
+    /// marker exists. As the transaction is committed by then, every change is made even if the
+    /// changes can't be logged or saved, and the first error is returned afterwards.
+    pub(crate) fn commit_prepared_changes(
+        &mut self,
+        changes: Vec<(BigUint, Option<Tuple>)>,
+    ) -> std::io::Result<()> {
+        let records = change_records(changes);
+        let mut result = self.write_log(&[LogRecord::Batch(records.clone())]);
+        for record in records {
+            let applied = self.apply(record);
+            if result.is_ok() {
+                result = applied;
+            }
+        }
+        result
+    }
+
+    /// Logs the changes of a transaction over more than one relation without making them. When
+    /// the log is replayed, the changes are only made if the commit marker file exists by then.
+    pub(crate) fn prepare_changes(
+        &mut self,
+        changes: Vec<(BigUint, Option<Tuple>)>,
+        commit_marker: &str,
+    ) -> std::io::Result<()> {
+        self.write_log(&[LogRecord::Prepare {
+            commit_marker: commit_marker.to_string(),
+            records: change_records(changes),
+        }])
+    }
+
+    /// Makes the changes of a transaction as new versions of the tuples, without mutable access to
+    /// the storage, returning the timestamp they were committed at. Nothing is changed if a tuple
+    /// was changed after the read timestamp that the changes were worked out at, or if a bucket
+    /// has to be created or split, or the log checkpointed, first.
+    pub(crate) fn commit_versions(
+        &self,
+        changes: &[(BigUint, Option<Tuple>)],
+        read: Timestamp,
+    ) -> Result<Timestamp, VersionError> {
+        if let Some(log) = &self.log {
+            if log.lock().unwrap().size() >= CHECKPOINT_SIZE {
+                return Err(VersionError::Checkpoint);
+            }
+        }
+        let timestamp = self.true_storage.begin_commit();
+        let result = self.write_versions(changes, read, timestamp);
+        self.true_storage.end_commit(timestamp);
+        result.map(|_| timestamp)
+    }
+
+    /// Writes a version for each change, then logs the changes. The versions are undone if not
+    /// all of them can be written, or if the changes can't be logged.
+    fn write_versions(
+        &self,
+        changes: &[(BigUint, Option<Tuple>)],
+        read: Timestamp,
+        timestamp: Timestamp,
+    ) -> Result<(), VersionError> {
+        for (written, (hash, tuple)) in changes.iter().enumerate() {
+            let error = match self
+                .true_storage
+                .write_version(hash, tuple.clone(), read, timestamp)
+            {
+                VersionWrite::Written => continue,
+                VersionWrite::Conflict => VersionError::Conflict,
+                VersionWrite::Full => VersionError::Full(hash.clone()),
+            };
+            self.undo_versions(&changes[..written], timestamp);
+            return Err(error);
+        }
+        if let Some(log) = &self.log {
+            let batch = LogRecord::Batch(change_records(changes.to_vec()));
+            if let Err(e) = log.lock().unwrap().append(std::slice::from_ref(&batch)) {
+                self.undo_versions(changes, timestamp);
+                return Err(VersionError::Io(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn undo_versions(&self, changes: &[(BigUint, Option<Tuple>)], timestamp: Timestamp) {
+        for (hash, _) in changes {
+            self.true_storage.undo_version(hash, timestamp);
+        }
+    }
+
+    /// Makes room for a new tuple with the hash, which a commit of versions couldn't do
+    pub(crate) fn make_room(&mut self, hash: &BigUint) -> std::io::Result<()> {
+        self.true_storage.make_room(hash)
+    }
+
+    /// Starts a read at the newest timestamp that reads can see. The versions it sees are kept
+    /// until it's closed with [TupleStorage::close_read].
+    pub(crate) fn open_read(&self) -> Timestamp {
+        self.true_storage.open_read()
+    }
+
+    /// Closes a read that was opened at the timestamp
+    pub(crate) fn close_read(&self, timestamp: Timestamp) {
+        self.true_storage.close_read(timestamp)
+    }
+
+    /// Gets a copy of the version of the tuple stored with the hash that a read at the timestamp
+    /// sees
+    pub(crate) fn get_at(&self, hash: &BigUint, timestamp: Timestamp) -> Option<Tuple> {
+        self.true_storage.get_at(hash, timestamp)
+    }
+
+    /// Reads at least the limit of tuples that a read at the timestamp sees from where the cursor
+    /// is, unless the scan finishes first
+    pub(crate) fn scan(
+        &self,
+        cursor: &mut ScanCursor,
+        timestamp: Timestamp,
+        limit: usize,
+    ) -> Vec<Tuple> {
+        self.true_storage.scan(cursor, timestamp, limit)
+    }
+
+    /// Removes every version that no read can see anymore, returning the number removed
+    pub(crate) fn collect_garbage(&self) -> usize {
+        self.true_storage.collect_garbage()
+    }
+
+    /// Gets the number of versions of tuples that are kept, including deleted ones
+    pub(crate) fn version_count(&self) -> usize {
+        self.true_storage.version_count()
+    }
+
+    /// Whether changes to the storage are written to a log
+    pub(crate) fn is_logged(&self) -> bool {
+        self.log.is_some()
+    }
+
+    /// Gets a copy of the tuple stored with the hash
+    pub(crate) fn get_by_hash(&self, hash: &BigUint) -> Option<Tuple> {
+        self.true_storage.get(hash)
+    }
+
+    /// Gets the storage root that the blocks are saved under
+    pub fn storage_root(&self) -> &Path {
+        self.true_storage.storage_root()
+    }
+
+    /// Creates an empty storage with the same definition, which is never saved to the file system
     pub fn to_skeleton(&self) -> Self {
-        Self::new(
+        Self::new_volatile(
             self.identifier.clone(),
             self.relation.clone(),
             self.primary_key_definition.clone(),
@@ -99,19 +714,202 @@ impl TupleStorage {
         )
     }
 
-    /// Insert an entire tuple into the storage medium
-    pub fn insert(&mut self, tuple: Tuple) -> InsertionResult<Option<Tuple>> {
+    /// Checks that a tuple has a value for every attribute, and that every value can be stored as
+    /// the type of its attribute. Returns the tuple as it would be stored, where every decimal has
+    /// the precision and scale of its attribute.
+    pub fn validate(&self, tuple: Tuple) -> InsertionResult<Tuple> {
+        if tuple.len() != self.relation.len() {
+            return Err(TupleInsertionError::WrongArity {
+                expected: self.relation.len(),
+                found: tuple.len(),
+            });
+        }
+        let mut values = Vec::with_capacity(tuple.len());
+        let mut incorrect = vec![];
+        for (index, (value, data_type)) in
+            tuple.into_iter().zip(self.relation.type_iter()).enumerate()
+        {
+            if data_type.accepts(&value) {
+                values.push(rescale(data_type, value));
+                continue;
+            }
+            if value == Type::Optional(None) {
+                return Err(TupleInsertionError::UnexpectedNull(index));
+            }
+            match length_limit(data_type, &value) {
+                Some((length, max)) if length > max => {
+                    return Err(TupleInsertionError::TooLong { index, length, max })
+                }
+                _ => incorrect.push(index),
+            }
+        }
+        if incorrect.is_empty() {
+            Ok(Tuple::new(values))
+        } else {
+            Err(TupleInsertionError::IncorrectTypes(incorrect))
+        }
+    }
+
+    /// Insert an entire tuple into the storage medium, after checking that it fits the relation.
+    /// Fails if a tuple with the same primary key is already stored.
+    pub fn insert(&mut self, tuple: Tuple) -> InsertionResult<InsertOutcome> {
+        self.insert_with(tuple, ConflictPolicy::Reject)
+    }
+
+    /// Insert an entire tuple into the storage medium, resolving a conflict with a stored tuple
+    /// that has the same primary key using the policy
+    pub fn insert_with(
+        &mut self,
+        tuple: Tuple,
+        policy: ConflictPolicy<'_>,
+    ) -> InsertionResult<InsertOutcome> {
+        let tuple = self.validate(tuple)?;
         let hash = self.hash_tuple(&tuple);
-        let result = Ok(self.true_storage.insert(tuple, hash));
-        //println!("{:#?}", self.true_storage);
-        result
+        let key = self.key_values(&tuple);
+        let existing = match self.true_storage.get(&hash) {
+            None => {
+                self.put(hash, tuple)?;
+                return Ok(InsertOutcome::Inserted);
+            }
+            Some(existing) if !self.has_key(&existing, &key) => {
+                return Err(TupleInsertionError::HashCollision)
+            }
+            Some(existing) => existing,
+        };
+        match policy {
+            ConflictPolicy::Reject => Err(TupleInsertionError::PrimaryKeyPresent),
+            ConflictPolicy::Ignore => Ok(InsertOutcome::Ignored),
+            ConflictPolicy::Replace => {
+                self.put(hash, tuple)?;
+                Ok(InsertOutcome::Replaced(existing))
+            }
+            ConflictPolicy::Merge(merge) => {
+                let merged = self.validate(merge(&existing, tuple))?;
+                if !self.has_key(&merged, &key) {
+                    return Err(TupleInsertionError::MergeChangedKey);
+                }
+                self.put(hash, merged)?;
+                Ok(InsertOutcome::Merged(existing))
+            }
+        }
+    }
+
+    /// Logs a tuple, then stores it
+    fn put(&mut self, hash: BigUint, tuple: Tuple) -> std::io::Result<()> {
+        let record = LogRecord::Put(hash, tuple);
+        self.write_log(std::slice::from_ref(&record))?;
+        self.apply(record)
+    }
+
+    /// Removes the tuple with the primary key, where the key has the values of the primary key
+    /// attributes in the order of the primary key definition
+    pub fn remove(&mut self, key: &[Value]) -> DeletionResult<Option<Tuple>> {
+        let hash = self.hash_key(key)?;
+        if !self.stores_key(&hash, key) {
+            return Ok(None);
+        }
+        self.write_log(&[LogRecord::Delete(hash.clone())])?;
+        Ok(self.true_storage.remove(vec![hash])?.pop())
+    }
+
+    /// Removes the tuples with each of the primary keys, returning the tuples that were removed
+    pub fn remove_many<K: AsRef<[Value]>, I: IntoIterator<Item = K>>(
+        &mut self,
+        keys: I,
+    ) -> DeletionResult<Vec<Tuple>> {
+        let mut hashes = vec![];
+        for key in keys {
+            let key = key.as_ref();
+            let hash = self.hash_key(key)?;
+            if self.stores_key(&hash, key) {
+                hashes.push(hash);
+            }
+        }
+        let records: Vec<_> = hashes.iter().cloned().map(LogRecord::Delete).collect();
+        self.write_log(&records)?;
+        Ok(self.true_storage.remove(hashes)?)
+    }
+
+    /// Updates the tuple with the primary key, returning the tuple as it was before the update. If
+    /// the update changes the primary key, the tuple is moved to the bucket of its new key.
+    pub fn update<F: FnOnce(&mut Tuple)>(
+        &mut self,
+        key: &[Value],
+        update: F,
+    ) -> UpdateResult<Option<Tuple>> {
+        let existing = match self.find_by_primary(key)? {
+            Some(existing) => existing,
+            None => return Ok(None),
+        };
+        let mut updated = existing.clone();
+        update(&mut updated);
+        let updated = self.validate(updated)?;
+        self.replace_all(vec![(existing.clone(), updated)])?;
+        Ok(Some(existing))
     }
-    pub fn remove(&mut self, primary_key: PrimaryKey<'_>) -> Result<Tuple, ()> {
-        unimplemented!()
+
+    /// Sets the values of the attributes at the indexes of every tuple that meets the condition,
+    /// returning the number of tuples that were updated. Either every matching tuple is updated or
+    /// none are, and the result doesn't depend on the order the tuples are stored in.
+    pub fn update_where<C: FnMut(&Tuple) -> bool>(
+        &mut self,
+        mut condition: C,
+        assignments: &[(usize, Value)],
+    ) -> UpdateResult<usize> {
+        let matching: Vec<Tuple> = self.all_tuples().filter(|tuple| condition(tuple)).collect();
+        let mut updates = Vec::with_capacity(matching.len());
+        for existing in matching {
+            let mut updated = existing.clone();
+            for (index, value) in assignments {
+                updated[*index] = value.clone();
+            }
+            updates.push((existing, self.validate(updated)?));
+        }
+        let count = updates.len();
+        self.replace_all(updates)?;
+        Ok(count)
+    }
+
+    /// Stores updated tuples in place of the tuples they were updated from, as a single logged
+    /// batch. New primary keys are checked against the relation as it is after the update, so keys
+    /// can be moved between the updated tuples, and nothing is stored if any key conflicts.
+    fn replace_all(&mut self, updates: Vec<(Tuple, Tuple)>) -> UpdateResult<()> {
+        let old_hashes: HashSet<BigUint> = updates
+            .iter()
+            .map(|(existing, _)| self.hash_tuple(existing))
+            .collect();
+        let mut new_hashes = HashSet::new();
+        let mut deletes = vec![];
+        let mut puts = vec![];
+        for (existing, updated) in updates {
+            let old_hash = self.hash_tuple(&existing);
+            let new_hash = self.hash_tuple(&updated);
+            if !new_hashes.insert(new_hash.clone()) {
+                return Err(TupleInsertionError::PrimaryKeyPresent.into());
+            }
+            if !old_hashes.contains(&new_hash) {
+                if let Some(stored) = self.true_storage.get(&new_hash) {
+                    return Err(self.conflict(&stored, &updated).into());
+                }
+            }
+            if old_hash != new_hash {
+                deletes.push(LogRecord::Delete(old_hash));
+            }
+            puts.push(LogRecord::Put(new_hash, updated));
+        }
+        deletes.append(&mut puts);
+        let batch = LogRecord::Batch(deletes);
+        self.write_log(std::slice::from_ref(&batch))?;
+        Ok(self.apply(batch)?)
     }
 
-    pub fn find_by_primary(&self, primary_key: PrimaryKey<'_>) -> Result<&Tuple, ()> {
-        unimplemented!()
+    /// Finds the tuple with the primary key, where the key has the values of the primary key
+    /// attributes in the order of the primary key definition. Only the block that the tuple would
+    /// be stored in is read.
+    pub fn find_by_primary(&self, key: &[Value]) -> Result<Option<Tuple>, KeyArityError> {
+        let hash = self.hash_key(key)?;
+        let found = self.true_storage.get(&hash);
+        Ok(found.filter(|tuple| self.has_key(tuple, key)))
     }
     /// Gets a [StoredTupleIterator] for the tuple storage
     ///
@@ -132,7 +930,69 @@ impl TupleStorage {
         primary_key.hash()
     }
 
-    fn get_primary_key_definition(&self) -> &PrimaryKeyDefinition {
+    /// Hashes the values of a primary key the same way the tuple they came from would be hashed
+    pub(crate) fn hash_key(&self, key: &[Value]) -> Result<BigUint, KeyArityError> {
+        let definition = self.get_primary_key_definition();
+        if key.len() != definition.len() {
+            return Err(KeyArityError {
+                expected: definition.len(),
+                found: key.len(),
+            });
+        }
+        let mut positioned: Vec<_> = definition.iter().zip(key).collect();
+        positioned.sort_by_key(|(pos, _)| **pos);
+        let values = positioned.into_iter().map(|(_, value)| value).collect();
+        Ok(PrimaryKey::new(values, definition.create_seeds()).hash())
+    }
+
+    /// Whether the tuple has exactly these primary key values, and not just the same hash
+    pub(crate) fn has_key(&self, tuple: &Tuple, key: &[Value]) -> bool {
+        self.get_primary_key_definition()
+            .iter()
+            .zip(key)
+            .all(|(pos, value)| &tuple[*pos] == value)
+    }
+
+    /// Gets why a tuple can't be stored with the same hash as a stored tuple
+    pub(crate) fn conflict(&self, stored: &Tuple, tuple: &Tuple) -> TupleInsertionError {
+        if self.has_key(stored, &self.key_values(tuple)) {
+            TupleInsertionError::PrimaryKeyPresent
+        } else {
+            TupleInsertionError::HashCollision
+        }
+    }
+
+    /// Whether the tuple stored with the hash has exactly these primary key values
+    fn stores_key(&self, hash: &BigUint, key: &[Value]) -> bool {
+        self.true_storage
+            .get(hash)
+            .is_some_and(|stored| self.has_key(&stored, key))
+    }
+
+    /// Gets the values of the primary key attributes of a tuple, in the order of the primary key
+    /// definition
+    fn key_values(&self, tuple: &Tuple) -> Vec<Value> {
+        self.get_primary_key_definition()
+            .iter()
+            .map(|pos| tuple[*pos].clone())
+            .collect()
+    }
+
+    /// Gets the names and types of the attributes, without the name of the relation
+    pub(crate) fn attributes(&self) -> Vec<(String, DataType)> {
+        self.relation
+            .identifier_iter()
+            .into_iter()
+            .map(|id| id.base().clone())
+            .zip(&self.relation)
+            .collect()
+    }
+
+    pub(crate) fn bucket_size(&self) -> usize {
+        self.true_storage.bucket_size()
+    }
+
+    pub(crate) fn get_primary_key_definition(&self) -> &PrimaryKeyDefinition {
         &self.primary_key_definition
     }
 
@@ -155,9 +1015,36 @@ impl TupleStorage {
     }
 }
 
+/// Gets the records that make changes, where each hash is paired with the tuple it ends up storing
+/// or `None` if the tuple is deleted
+fn change_records(changes: Vec<(BigUint, Option<Tuple>)>) -> Vec<LogRecord> {
+    changes
+        .into_iter()
+        .map(|(hash, tuple)| match tuple {
+            Some(tuple) => LogRecord::Put(hash, tuple),
+            None => LogRecord::Delete(hash),
+        })
+        .collect()
+}
+
 impl Rename<Identifier> for TupleStorage {
     fn rename(&mut self, name: Identifier) {
+        if let Some(log) = &mut self.log {
+            log.get_mut()
+                .unwrap()
+                .set_path(log_path(self.true_storage.storage_root(), &name));
+        }
         self.identifier = name.clone();
         self.true_storage.rename(name);
     }
 }
+
+impl Drop for TupleStorage {
+    /// Checkpoints, so that the log doesn't need to be replayed when the storage is reopened. If
+    /// the checkpoint fails, the log is kept and replayed instead.
+    fn drop(&mut self) {
+        if let Err(e) = self.checkpoint() {
+            log::warn!("Couldn't checkpoint {}: {}", self.identifier, e);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! The timestamps that versions of tuples are created and deleted at.
+//!
+//! Every commit that makes versions of the tuples of a relation is given a timestamp from the
+//! [Clock] of the relation. Versions made by a commit are already stored in the blocks while it's
+//! running, so reads only see commits up to the [visible](Clock::visible) timestamp, which is the
+//! newest timestamp that no running commit is older than. Once a commit ends, whether its
+//! versions were kept or undone, the visible timestamp can move past it.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The timestamp of a commit. Tuples that weren't made by a commit, such as the ones read from a
+/// block file, have a timestamp of 0.
+pub type Timestamp = u64;
+
+#[derive(Debug, Default)]
+struct Commits {
+    /// The timestamp given to the last commit
+    last: Timestamp,
+    /// The timestamps of the commits that haven't ended yet
+    running: BTreeSet<Timestamp>,
+}
+
+/// Gives out the timestamps of the commits to a relation
+#[derive(Debug, Default)]
+pub(crate) struct Clock {
+    commits: Mutex<Commits>,
+    visible: AtomicU64,
+}
+
+impl Clock {
+    /// Gets the timestamp of every commit that reads can see, which every commit up to has ended
+    pub(crate) fn visible(&self) -> Timestamp {
+        self.visible.load(Ordering::Acquire)
+    }
+
+    /// Gives a new commit its timestamp. Reads don't see the commit until it's ended.
+    pub(crate) fn begin_commit(&self) -> Timestamp {
+        let mut commits = self.commits.lock().unwrap();
+        commits.last += 1;
+        let timestamp = commits.last;
+        commits.running.insert(timestamp);
+        timestamp
+    }
+
+    /// Ends a commit, after which reads can see the versions it made
+    pub(crate) fn end_commit(&self, timestamp: Timestamp) {
+        let mut commits = self.commits.lock().unwrap();
+        commits.running.remove(&timestamp);
+        let visible = match commits.running.iter().next() {
+            Some(oldest) => oldest - 1,
+            None => commits.last,
+        };
+        self.visible.store(visible, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_commits() {
+        let clock = Clock::default();
+        let first = clock.begin_commit();
+        let second = clock.begin_commit();
+        assert_eq!(clock.visible(), 0);
+        clock.end_commit(second);
+        assert_eq!(clock.visible(), first - 1);
+        clock.end_commit(first);
+        assert_eq!(clock.visible(), second);
+    }
+}
@@ -0,0 +1,472 @@
+//! The write-ahead log of a relation.
+//!
+//! Changes to the blocks of a relation are only held in memory until the blocks are unloaded, so
+//! every change is first appended to a log next to the manifest, and the log is synced to the disk
+//! before the change is made. When the relation is reopened, the log is replayed onto the blocks,
+//! and a checkpoint saves every block before the log is truncated.
+//!
+//! The log starts with the [MAGIC] bytes followed by a single format version byte. Every record
+//! after the header starts with the length of its body and the [seahash] checksum of that length,
+//! each as 8 little endian bytes, followed by the body and the checksum of the body. The body is a tag byte, the length prefixed bytes of the
+//! hash of the tuple, and for a [LogRecord::Put] the values of the tuple in the binary encoding of
+//! [rad_db_types::encoding]. The body of a [LogRecord::Batch] is the tag byte, the number of
+//! records in it, and the length prefixed body of each of them, so that the whole batch is covered
+//! by one checksum. A [LogRecord::Prepare] has the length prefixed path of its commit marker before
+//! the records of the batch.
+//!
+//! A record that ends past the end of the log, but whose length matches its checksum, was being
+//! written when the process stopped, so the change was never made and the record is ignored. Any
+//! other record that doesn't match its checksums means the log is corrupt.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use num_bigint::BigUint;
+
+use rad_db_types::encoding::{
+    decode_values, encode_values, read_length, write_length, DecodeError,
+};
+
+use crate::identifier::Identifier;
+use crate::storage::{storage_path, sync_directory};
+use crate::tuple::Tuple;
+
+/// The name of the log file in the directory of a relation
+pub const LOG_FILE: &str = "wal.log";
+/// The bytes at the start of every log
+pub const MAGIC: &[u8; 4] = b"RDBW";
+/// The current version of the log format
+pub const LOG_FORMAT_VERSION: u8 = 1;
+/// The size in bytes that a log can grow to before the relation checkpoints
+pub const CHECKPOINT_SIZE: u64 = 4 * 1024 * 1024;
+
+const TAG_PUT: u8 = 0;
+const TAG_DELETE: u8 = 1;
+const TAG_BATCH: u8 = 2;
+const TAG_PREPARE: u8 = 3;
+const CHECKSUM_LEN: usize = 8;
+const RECORD_HEADER_LEN: usize = 8 + CHECKSUM_LEN;
+
+/// A single change to the blocks of a relation. Replaying a record more than once has the same
+/// effect as replaying it once.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogRecord {
+    /// The tuple is stored with the hash, replacing any tuple already stored with it
+    Put(BigUint, Tuple),
+    /// The tuple stored with the hash is removed, if there is one
+    Delete(BigUint),
+    /// Records that are either all replayed or, if the process stopped while they were being
+    /// appended, none of them are
+    Batch(Vec<LogRecord>),
+    /// The records a transaction over more than one relation makes to this one, which are only
+    /// replayed if the transaction committed, which it did once the file at the path exists
+    Prepare {
+        commit_marker: String,
+        records: Vec<LogRecord>,
+    },
+}
+
+/// When the log of a relation couldn't be read
+#[derive(Debug)]
+pub enum LogError {
+    /// The log file couldn't be read
+    Io(PathBuf, std::io::Error),
+    /// The log has a header, but for a version that isn't known
+    UnknownVersion(u8),
+    /// A record before the end of the log doesn't match its checksum
+    Corrupt { offset: usize },
+    /// A record matches its checksum, but its body couldn't be decoded
+    Decode { offset: usize, cause: DecodeError },
+}
+
+impl Display for LogError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogError::Io(path, e) => write!(f, "Could not read log {:?}: {}", path, e),
+            LogError::UnknownVersion(version) => write!(f, "Unknown log version {}", version),
+            LogError::Corrupt { offset } => write!(f, "Corrupt log record at byte {}", offset),
+            LogError::Decode { offset, cause } => {
+                write!(
+                    f,
+                    "Couldn't decode log record at byte {}: {}",
+                    offset, cause
+                )
+            }
+        }
+    }
+}
+
+impl Error for LogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LogError::Io(_, e) => Some(e),
+            LogError::Decode { cause, .. } => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+/// Gets the path of the log of a relation saved under the storage root
+pub fn log_path(storage_root: &Path, table: &Identifier) -> PathBuf {
+    let mut ret = storage_path(storage_root, table);
+    ret.push(LOG_FILE);
+    ret
+}
+
+fn write_hash(body: &mut Vec<u8>, hash: &BigUint) {
+    let hash = hash.to_bytes_le();
+    write_length(body, hash.len() as u64);
+    body.extend_from_slice(&hash);
+}
+
+fn read_slice<'a>(body: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = read_length(body)? as usize;
+    if len > body.len() {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (ret, rest) = body.split_at(len);
+    *body = rest;
+    Ok(ret)
+}
+
+impl LogRecord {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let mut body = vec![];
+        self.encode_body(&mut body);
+        let len = (body.len() as u64).to_le_bytes();
+        buffer.extend_from_slice(&len);
+        buffer.extend_from_slice(&seahash::hash(&len).to_le_bytes());
+        buffer.extend_from_slice(&body);
+        buffer.extend_from_slice(&seahash::hash(&body).to_le_bytes());
+    }
+
+    fn encode_body(&self, body: &mut Vec<u8>) {
+        match self {
+            LogRecord::Put(hash, tuple) => {
+                body.push(TAG_PUT);
+                write_hash(body, hash);
+                encode_values(body, tuple);
+            }
+            LogRecord::Delete(hash) => {
+                body.push(TAG_DELETE);
+                write_hash(body, hash);
+            }
+            LogRecord::Batch(records) => {
+                body.push(TAG_BATCH);
+                LogRecord::encode_nested(body, records);
+            }
+            LogRecord::Prepare {
+                commit_marker,
+                records,
+            } => {
+                body.push(TAG_PREPARE);
+                write_length(body, commit_marker.len() as u64);
+                body.extend_from_slice(commit_marker.as_bytes());
+                LogRecord::encode_nested(body, records);
+            }
+        }
+    }
+
+    fn encode_nested(body: &mut Vec<u8>, records: &[LogRecord]) {
+        write_length(body, records.len() as u64);
+        let mut nested = vec![];
+        for record in records {
+            nested.clear();
+            record.encode_body(&mut nested);
+            write_length(body, nested.len() as u64);
+            body.extend_from_slice(&nested);
+        }
+    }
+
+    fn decode_nested(rest: &mut &[u8]) -> Result<Vec<LogRecord>, DecodeError> {
+        let count = read_length(rest)?;
+        let mut records = vec![];
+        for _ in 0..count {
+            records.push(LogRecord::decode(read_slice(rest)?)?);
+        }
+        Ok(records)
+    }
+
+    fn decode(body: &[u8]) -> Result<Self, DecodeError> {
+        let (tag, mut rest) = body.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        let ret = match *tag {
+            TAG_PUT => {
+                let hash = BigUint::from_bytes_le(read_slice(&mut rest)?);
+                LogRecord::Put(hash, Tuple::new(decode_values(&mut rest)?))
+            }
+            TAG_DELETE => LogRecord::Delete(BigUint::from_bytes_le(read_slice(&mut rest)?)),
+            TAG_BATCH => LogRecord::Batch(LogRecord::decode_nested(&mut rest)?),
+            TAG_PREPARE => {
+                let commit_marker = String::from_utf8(read_slice(&mut rest)?.to_vec())
+                    .map_err(|_| DecodeError::InvalidValue("commit marker isn't UTF-8"))?;
+                LogRecord::Prepare {
+                    commit_marker,
+                    records: LogRecord::decode_nested(&mut rest)?,
+                }
+            }
+            _ => return Err(DecodeError::InvalidValue("unknown log record")),
+        };
+        if rest.is_empty() {
+            Ok(ret)
+        } else {
+            Err(DecodeError::InvalidValue("log record is too long"))
+        }
+    }
+}
+
+/// Splits the first 8 bytes off of the slice as a little endian number
+fn split_u64(bytes: &[u8]) -> (u64, &[u8]) {
+    let (number, rest) = bytes.split_at(8);
+    let mut array = [0; 8];
+    array.copy_from_slice(number);
+    (u64::from_le_bytes(array), rest)
+}
+
+/// Reads every complete record out of the contents of a log
+pub fn read_log(contents: &[u8]) -> Result<Vec<LogRecord>, LogError> {
+    if contents.is_empty() {
+        return Ok(vec![]);
+    }
+    if contents.len() <= MAGIC.len() || !contents.starts_with(MAGIC) {
+        return Err(LogError::Corrupt { offset: 0 });
+    }
+    let version = contents[MAGIC.len()];
+    if version != LOG_FORMAT_VERSION {
+        return Err(LogError::UnknownVersion(version));
+    }
+
+    let mut records = vec![];
+    let mut offset = MAGIC.len() + 1;
+    while offset < contents.len() {
+        let rest = &contents[offset..];
+        if rest.len() < RECORD_HEADER_LEN {
+            break;
+        }
+        let (len, rest) = rest.split_at(8);
+        let (len_checksum, rest) = split_u64(rest);
+        if seahash::hash(len) != len_checksum {
+            return Err(LogError::Corrupt { offset });
+        }
+        let (body_len, _) = split_u64(len);
+        if body_len.saturating_add(CHECKSUM_LEN as u64) > rest.len() as u64 {
+            break;
+        }
+        let (body, rest) = rest.split_at(body_len as usize);
+        let (checksum, rest) = split_u64(rest);
+        if seahash::hash(body) != checksum {
+            return Err(LogError::Corrupt { offset });
+        }
+        let record = LogRecord::decode(body).map_err(|cause| LogError::Decode { offset, cause })?;
+        records.push(record);
+        offset = contents.len() - rest.len();
+    }
+    Ok(records)
+}
+
+/// The log of a relation, which is only created once a record is appended
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+    /// Whether a failed append may have left bytes past the size that couldn't be removed yet
+    torn: bool,
+}
+
+impl WriteAheadLog {
+    pub fn new(path: PathBuf) -> Self {
+        WriteAheadLog {
+            path,
+            file: None,
+            size: 0,
+            torn: false,
+        }
+    }
+
+    /// Reads every record in the log at the path, where a log that doesn't exist has no records
+    pub fn read(path: &Path) -> Result<Vec<LogRecord>, LogError> {
+        match std::fs::read(path) {
+            Ok(contents) => read_log(&contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(LogError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    /// The size of the log in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Moves the log to a new path. The log at the old path is left as it is.
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
+        self.file = None;
+        self.size = 0;
+        self.torn = false;
+    }
+
+    /// Appends the records to the log, and only returns once they're synced to the disk. If the
+    /// append fails, the log is cut back to the records before it.
+    pub fn append(&mut self, records: &[LogRecord]) -> std::io::Result<()> {
+        if self.file.is_none() {
+            let directory = self.path.parent().unwrap();
+            std::fs::create_dir_all(directory)?;
+            let existed = self.path.exists();
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            if !existed {
+                sync_directory(directory)?;
+            }
+            if self.torn {
+                file.set_len(self.size)?;
+                self.torn = false;
+            } else {
+                self.size = file.metadata()?.len();
+            }
+            self.file = Some(file);
+        }
+        let mut buffer = vec![];
+        if self.size == 0 {
+            buffer.extend_from_slice(MAGIC);
+            buffer.push(LOG_FORMAT_VERSION);
+        }
+        for record in records {
+            record.encode(&mut buffer);
+        }
+        let file = self.file.as_mut().unwrap();
+        if let Err(e) = file.write_all(&buffer).and_then(|_| file.sync_data()) {
+            if file.set_len(self.size).is_err() {
+                self.file = None;
+                self.torn = true;
+            }
+            return Err(e);
+        }
+        self.size += buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Removes every record from the log, once the changes they hold are saved in the blocks
+    pub fn truncate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        self.size = 0;
+        self.torn = false;
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rad_db_types::Type;
+    use std::iter::FromIterator;
+
+    fn records() -> Vec<LogRecord> {
+        vec![
+            LogRecord::Put(
+                BigUint::from(3u64),
+                Tuple::from_iter(&[Type::from(3u64), Type::from("three")]),
+            ),
+            LogRecord::Delete(BigUint::from(u64::MAX) << 64),
+            LogRecord::Batch(vec![
+                LogRecord::Delete(BigUint::from(4u8)),
+                LogRecord::Batch(vec![]),
+                LogRecord::Put(BigUint::from(5u8), Tuple::from_iter(&[Type::from(5u64)])),
+            ]),
+            LogRecord::Prepare {
+                commit_marker: "transactions/1.commit".to_string(),
+                records: vec![LogRecord::Delete(BigUint::from(6u8))],
+            },
+            LogRecord::Put(
+                BigUint::from(0u8),
+                Tuple::from_iter(&[Type::Optional(None)]),
+            ),
+        ]
+    }
+
+    fn encoded() -> Vec<u8> {
+        let mut buffer = MAGIC.to_vec();
+        buffer.push(LOG_FORMAT_VERSION);
+        for record in records() {
+            record.encode(&mut buffer);
+        }
+        buffer
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(read_log(&encoded()).unwrap(), records());
+        assert!(read_log(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn torn_tail() {
+        let full = encoded();
+        let mut start = MAGIC.len() + 1;
+        for (index, record) in records().iter().enumerate() {
+            let mut encoded = vec![];
+            record.encode(&mut encoded);
+            let end = start + encoded.len();
+            for torn in start + 1..end {
+                assert_eq!(
+                    read_log(&full[..torn]).unwrap(),
+                    records()[..index].to_vec()
+                );
+            }
+            start = end;
+        }
+    }
+
+    #[test]
+    fn failed_append() {
+        let path = std::env::temp_dir()
+            .join(format!("rad_db_failed_append_{}", std::process::id()))
+            .join("relation.wal");
+        let mut log = WriteAheadLog::new(path.clone());
+        log.append(&records()[..2]).unwrap();
+
+        // the bytes of an append that failed part of the way through, which couldn't be removed
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xff; 7]).unwrap();
+        log.file = None;
+        log.torn = true;
+
+        log.append(&records()[2..]).unwrap();
+        assert_eq!(WriteAheadLog::read(&path).unwrap(), records());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn corrupt() {
+        let start = MAGIC.len() + 1;
+        let mut last = vec![];
+        records().last().unwrap().encode(&mut last);
+        let last_start = encoded().len() - last.len();
+        for (index, offset) in [
+            (start + RECORD_HEADER_LEN + 1, start),
+            (start, start),
+            (last_start + 2, last_start),
+            (encoded().len() - 1, last_start),
+        ] {
+            let mut corrupt = encoded();
+            corrupt[index] ^= 0xff;
+            match read_log(&corrupt) {
+                Err(LogError::Corrupt { offset: found }) => assert_eq!(found, offset),
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+        let mut version = encoded();
+        version[MAGIC.len()] = 2;
+        assert!(matches!(
+            read_log(&version),
+            Err(LogError::UnknownVersion(2))
+        ));
+    }
+}
@@ -0,0 +1,418 @@
+//! Consistent snapshots of a relation while it's being written to, through versions of its
+//! tuples that are kept in its blocks.
+//!
+//! A [VersionedRelation] can be shared between threads, and both reads and writes only need a
+//! shared reference. Each commit is given a timestamp, and instead of changing tuples in place, it
+//! deletes the current version of each tuple it changes at that timestamp and adds a new version
+//! created at it. Reads only see a commit once every change it made is in the blocks, so they see
+//! either all of the changes of a commit or none of them.
+//!
+//! A [Snapshot] reads every tuple as it was at the timestamp the snapshot was taken at. Scans of a
+//! snapshot only read the relation a few directories at a time, so writers can commit and buckets
+//! can be split in between, but the snapshot never sees their changes. Scans of the relation
+//! itself, such as with a [BlockIterator] or a query on [VersionedRelation::read], read at the
+//! timestamp they were started at.
+//!
+//! Writers work out their changes from the relation as it is, then commit them while sharing it,
+//! only locking the blocks they change. If another commit changed one of the tuples in the
+//! meantime, the changes are undone and worked out again. A writer only needs the relation to
+//! itself to split a bucket or checkpoint the log, which waits for other commits and for guards
+//! from [VersionedRelation::read] to be dropped, but not for snapshots.
+//!
+//! Versions that no snapshot or scan can see anymore are removed by a thread in the background.
+//! Only the newest version of each tuple is saved to the block files, so a block isn't unloaded
+//! while it has older versions, and every tuple has a single version when a relation is reopened.
+//!
+//! [BlockIterator]: crate::relations::tuple_storage::BlockIterator
+
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+use std::thread;
+use std::time::Duration;
+
+use rad_db_types::{DataType, Value};
+
+use crate::identifier::Identifier;
+pub use crate::relations::tuple_storage::Timestamp;
+use crate::relations::tuple_storage::{KeyArityError, ScanCursor, VersionError};
+use crate::relations::Relation;
+use crate::transaction::{Transaction, TransactionResult};
+use crate::tuple::Tuple;
+
+/// How often the background thread removes versions that can't be seen anymore
+pub const GARBAGE_COLLECTION_INTERVAL: Duration = Duration::from_millis(100);
+/// The number of tuples that a scan of a snapshot reads each time it reads the relation
+pub const BATCH_SIZE: usize = 64;
+
+struct Shared {
+    name: Identifier,
+    attributes: Vec<(String, DataType)>,
+    /// Only locked for writing to split a bucket or checkpoint the log
+    relation: RwLock<Relation>,
+}
+
+/// A relation that can be read from consistent snapshots while it's being written to
+#[derive(Clone)]
+pub struct VersionedRelation {
+    shared: Arc<Shared>,
+}
+
+impl VersionedRelation {
+    /// Wraps a relation, and starts the background thread that removes old versions
+    pub fn new(relation: Relation) -> Self {
+        let shared = Arc::new(Shared {
+            name: relation.name().clone(),
+            attributes: relation.attributes().clone(),
+            relation: RwLock::new(relation),
+        });
+
+        let weak: Weak<Shared> = Arc::downgrade(&shared);
+        thread::spawn(move || loop {
+            thread::sleep(GARBAGE_COLLECTION_INTERVAL);
+            match weak.upgrade() {
+                Some(shared) => {
+                    shared.relation.read().unwrap().storage().collect_garbage();
+                }
+                None => return,
+            }
+        });
+        VersionedRelation { shared }
+    }
+
+    /// Gets the name of the relation
+    pub fn name(&self) -> &Identifier {
+        &self.shared.name
+    }
+
+    /// Gets the name and types of the relation
+    pub fn attributes(&self) -> &Vec<(String, DataType)> {
+        &self.shared.attributes
+    }
+
+    /// Gets read access to the relation, such as to query it. Writers can still commit while the
+    /// guard is held, unless they have to split a bucket or checkpoint the log.
+    pub fn read(&self) -> RwLockReadGuard<'_, Relation> {
+        self.shared.relation.read().unwrap()
+    }
+
+    /// Takes a snapshot of every commit that has ended
+    pub fn snapshot(&self) -> Snapshot {
+        let timestamp = self.read().storage().open_read();
+        Snapshot {
+            shared: self.shared.clone(),
+            timestamp,
+        }
+    }
+
+    /// Commits a transaction that only staged changes to this relation, returning its timestamp.
+    /// Snapshots taken before the commit never see its changes, and snapshots taken after see all
+    /// of them. The changes are worked out again if another commit changes the same tuples first.
+    pub fn commit(&self, transaction: Transaction) -> TransactionResult<Timestamp> {
+        loop {
+            let relation = self.read();
+            let storage = relation.storage();
+            // The versions that the changes are checked against are kept until they're committed
+            let read = storage.open_read();
+            let committed = transaction
+                .resolve_changes(&relation)
+                .map(|changes| storage.commit_versions(&changes, read));
+            storage.close_read(read);
+            let error = match committed? {
+                Ok(timestamp) => return Ok(timestamp),
+                Err(error) => error,
+            };
+            std::mem::drop(relation);
+            match error {
+                VersionError::Conflict => thread::yield_now(),
+                VersionError::Full(hash) => self.write().storage_mut().make_room(&hash)?,
+                VersionError::Checkpoint => self.write().storage_mut().checkpoint()?,
+                VersionError::Io(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Relation> {
+        self.shared.relation.write().unwrap()
+    }
+
+    /// Inserts a tuple, failing if a tuple with the same primary key is already in the relation
+    pub fn insert(&self, tuple: Tuple) -> TransactionResult<Timestamp> {
+        let transaction =
+            self.stage(|transaction, relation| transaction.insert(relation, tuple))?;
+        self.commit(transaction)
+    }
+
+    /// Assigns values to fields of the tuple with the primary key
+    pub fn update<K, I, A>(&self, key: K, assignments: A) -> TransactionResult<Timestamp>
+    where
+        K: AsRef<[Value]>,
+        I: Into<Identifier>,
+        A: IntoIterator<Item = (I, Value)>,
+    {
+        let transaction =
+            self.stage(|transaction, relation| transaction.update(relation, key, assignments))?;
+        self.commit(transaction)
+    }
+
+    /// Deletes the tuple with the primary key
+    pub fn delete<K: AsRef<[Value]>>(&self, key: K) -> TransactionResult<Timestamp> {
+        let transaction = self.stage(|transaction, relation| transaction.delete(relation, key))?;
+        self.commit(transaction)
+    }
+
+    /// Stages changes to the relation in a transaction, which should then be committed with
+    /// [VersionedRelation::commit]. The relation is read while the closure runs.
+    pub fn stage<F>(&self, stage: F) -> TransactionResult<Transaction>
+    where
+        F: FnOnce(&mut Transaction, &Relation) -> TransactionResult<()>,
+    {
+        let mut transaction = Transaction::new();
+        stage(&mut transaction, &self.read())?;
+        Ok(transaction)
+    }
+
+    /// Removes every version that no snapshot or scan can see, returning the number of versions
+    /// that were removed. This also happens in the background.
+    pub fn collect_garbage(&self) -> usize {
+        self.read().storage().collect_garbage()
+    }
+
+    /// The number of versions of tuples that are kept, including the deleted ones
+    pub fn version_count(&self) -> usize {
+        self.read().storage().version_count()
+    }
+}
+
+/// A consistent view of a [VersionedRelation], as it was when the snapshot was taken. Versions the
+/// snapshot can see are kept until it's dropped.
+pub struct Snapshot {
+    shared: Arc<Shared>,
+    timestamp: Timestamp,
+}
+
+impl Snapshot {
+    /// Gets the timestamp of the last commit the snapshot sees
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Gets the tuple with the primary key, where the key has a value for each attribute of the
+    /// primary key in the order of the primary key definition
+    pub fn get<K: AsRef<[Value]>>(&self, key: K) -> Result<Option<Tuple>, KeyArityError> {
+        let key = key.as_ref();
+        let relation = self.shared.relation.read().unwrap();
+        let storage = relation.storage();
+        let hash = storage.hash_key(key)?;
+        Ok(storage
+            .get_at(&hash, self.timestamp)
+            .filter(|tuple| storage.has_key(tuple, key)))
+    }
+
+    /// Gets an iterator over every tuple the snapshot sees
+    pub fn tuples(&self) -> SnapshotIterator<'_> {
+        SnapshotIterator {
+            snapshot: self,
+            buffer: Vec::new(),
+            cursor: ScanCursor::default(),
+        }
+    }
+
+    /// Gets the number of tuples the snapshot sees
+    pub fn len(&self) -> usize {
+        self.tuples().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tuples().next().is_none()
+    }
+
+    /// Copies every tuple the snapshot sees into a volatile relation with the same name and
+    /// attributes
+    pub fn to_relation(&self) -> Relation {
+        let mut relation = {
+            let relation = self.shared.relation.read().unwrap();
+            let storage = relation.storage();
+            Relation::new_volatile(
+                self.shared.name.clone(),
+                self.shared.attributes.clone(),
+                storage.bucket_size(),
+                storage.get_primary_key_definition().clone(),
+            )
+        };
+        for tuple in self.tuples() {
+            relation
+                .insert(tuple)
+                .expect("Snapshot has conflicting tuples");
+        }
+        relation
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let relation = self
+            .shared
+            .relation
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        relation.storage().close_read(self.timestamp);
+    }
+}
+
+/// An iterator over the tuples a snapshot sees. The relation is only read while a batch of tuples
+/// is scanned.
+pub struct SnapshotIterator<'a> {
+    snapshot: &'a Snapshot,
+    /// The tuples of the current batch, in reverse order
+    buffer: Vec<Tuple>,
+    cursor: ScanCursor,
+}
+
+impl Iterator for SnapshotIterator<'_> {
+    type Item = Tuple;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffer.is_empty() && !self.cursor.is_finished() {
+            let relation = self.snapshot.shared.relation.read().unwrap();
+            self.buffer =
+                relation
+                    .storage()
+                    .scan(&mut self.cursor, self.snapshot.timestamp, BATCH_SIZE);
+            self.buffer.reverse();
+        }
+        self.buffer.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::primary::PrimaryKeyDefinition;
+    use rad_db_types::Type;
+
+    fn row(id: u32, name: &str) -> Tuple {
+        Tuple::new(vec![Type::from(id), Type::from(name)])
+    }
+
+    fn versioned(rows: u32) -> VersionedRelation {
+        let mut relation = Relation::new_volatile(
+            Identifier::new("employees"),
+            vec![
+                ("id", DataType::UnsignedInt),
+                ("name", DataType::String(None)),
+            ],
+            4,
+            PrimaryKeyDefinition::new(vec![0]),
+        );
+        for id in 0..rows {
+            relation.insert(row(id, "old")).unwrap();
+        }
+        VersionedRelation::new(relation)
+    }
+
+    #[test]
+    fn snapshot_isolation() {
+        let relation = versioned(200);
+        let before = relation.snapshot();
+        let mut scan = before.tuples();
+        let first = scan.next().unwrap();
+
+        // Enough inserts to split buckets while the scan is part of the way through
+        for id in 200..400 {
+            relation.insert(row(id, "new")).unwrap();
+        }
+        relation
+            .update([Type::from(1u32)], vec![("name", Type::from("updated"))])
+            .unwrap();
+        relation.delete([Type::from(2u32)]).unwrap();
+        let transaction = relation
+            .stage(|transaction, relation| {
+                transaction.delete(relation, [Type::from(3u32)])?;
+                transaction.insert(relation, row(3, "replaced"))
+            })
+            .unwrap();
+        relation.commit(transaction).unwrap();
+
+        let mut seen: Vec<Tuple> = std::iter::once(first).chain(scan).collect();
+        seen.sort_by_key(|tuple| tuple[0].to_string().parse::<u32>().unwrap());
+        assert_eq!(seen, (0..200).map(|id| row(id, "old")).collect::<Vec<_>>());
+        assert_eq!(before.get([Type::from(2u32)]), Ok(Some(row(2, "old"))));
+        assert_eq!(before.get([Type::from(200u32)]), Ok(None));
+
+        let after = relation.snapshot();
+        assert!(after.timestamp() > before.timestamp());
+        assert_eq!(after.len(), 399);
+        assert_eq!(after.get([Type::from(1u32)]), Ok(Some(row(1, "updated"))));
+        assert_eq!(after.get([Type::from(2u32)]), Ok(None));
+        assert_eq!(after.get([Type::from(3u32)]), Ok(Some(row(3, "replaced"))));
+        assert_eq!(after.get([Type::from(200u32)]), Ok(Some(row(200, "new"))));
+
+        let copy = after.to_relation();
+        assert_eq!(copy.len(), 399);
+        assert_eq!(copy.get([Type::from(1u32)]), Ok(Some(row(1, "updated"))));
+    }
+
+    #[test]
+    fn concurrent_writers() {
+        let relation = versioned(0);
+        let snapshot = relation.snapshot();
+        let writers: Vec<_> = (0..4u32)
+            .map(|writer| {
+                let relation = relation.clone();
+                thread::spawn(move || {
+                    for id in 0..25 {
+                        relation.insert(row(writer * 100 + id, "new")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert!(snapshot.is_empty());
+        assert_eq!(relation.snapshot().len(), 100);
+    }
+
+    #[test]
+    fn relation_scans() {
+        let relation = versioned(100);
+        let read = relation.read();
+        let mut scan = read.tuples();
+        let first = scan.next().unwrap();
+
+        relation
+            .update([Type::from(1u32)], vec![("name", Type::from("updated"))])
+            .unwrap();
+        relation.delete([Type::from(2u32)]).unwrap();
+
+        let mut seen: Vec<Tuple> = std::iter::once(first).chain(scan).collect();
+        seen.sort_by_key(|tuple| tuple[0].to_string().parse::<u32>().unwrap());
+        assert_eq!(seen, (0..100).map(|id| row(id, "old")).collect::<Vec<_>>());
+        assert_eq!(read.tuples().count(), 99);
+    }
+
+    #[test]
+    fn garbage_collection() {
+        let relation = versioned(4);
+        let old = relation.snapshot();
+        for _ in 0..3 {
+            relation
+                .update([Type::from(0u32)], vec![("name", Type::from("new"))])
+                .unwrap();
+        }
+        relation.delete([Type::from(1u32)]).unwrap();
+        assert_eq!(relation.version_count(), 7);
+
+        relation.collect_garbage();
+        assert_eq!(relation.version_count(), 7);
+        assert_eq!(old.get([Type::from(0u32)]), Ok(Some(row(0, "old"))));
+        assert_eq!(old.get([Type::from(1u32)]), Ok(Some(row(1, "old"))));
+
+        std::mem::drop(old);
+        relation.collect_garbage();
+        assert_eq!(relation.version_count(), 3);
+        let snapshot = relation.snapshot();
+        assert_eq!(snapshot.get([Type::from(0u32)]), Ok(Some(row(0, "new"))));
+        assert_eq!(snapshot.get([Type::from(1u32)]), Ok(None));
+        assert_eq!(snapshot.len(), 3);
+    }
+}
@@ -0,0 +1,135 @@
+//! Where relations are saved in the file system.
+//!
+//! Every relation that isn't volatile is saved under a storage root, in a directory made of the
+//! parts of its name, so the relation `company::employees` under the root `/data` is saved in
+//! `/data/company/employees`. Relations that are created without a root use [DEFAULT_ROOT], which
+//! is relative to the working directory.
+//!
+//! A [StorageConfig] sets the root of a database, and can also name other roots as tablespaces, so
+//! that individual relations can be saved somewhere else, such as on a different mount point.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::identifier::Identifier;
+
+/// The storage root of relations that are created without one
+pub const DEFAULT_ROOT: &str = "DB_STORAGE";
+
+/// Gets the directory that everything under an identifier is saved in, within a storage root
+pub fn storage_path<P: AsRef<Path>>(root: P, identifier: &Identifier) -> PathBuf {
+    let mut ret = root.as_ref().to_path_buf();
+    for name in identifier {
+        ret.push(name);
+    }
+    ret
+}
+
+/// Replaces the contents of a file, so that after a crash the file has either its old contents or
+/// all of the new ones. The bytes are written to a temporary file that's synced to the disk, then
+/// the temporary file is renamed over the file and the directory is synced to keep the rename.
+pub fn atomic_write<P: AsRef<Path>>(path: P, bytes: &[u8]) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let temporary = path.with_extension("tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::mem::drop(file);
+    std::fs::rename(&temporary, path)?;
+    match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => sync_directory(directory),
+        _ => sync_directory(Path::new(".")),
+    }
+}
+
+/// Syncs the entries of a directory to the disk. Directories can only be opened as files on unix,
+/// so nothing is done on other platforms.
+pub(crate) fn sync_directory(directory: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    File::open(directory)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = directory;
+    Ok(())
+}
+
+/// The storage root and the named tablespaces that relations can be saved in
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageConfig {
+    root: PathBuf,
+    tablespaces: HashMap<String, PathBuf>,
+}
+
+impl StorageConfig {
+    /// Creates a configuration that saves everything under the root, without any tablespaces
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        StorageConfig {
+            root: root.into(),
+            tablespaces: HashMap::new(),
+        }
+    }
+
+    /// Adds a tablespace, replacing the root of a tablespace that already has the name
+    pub fn with_tablespace<S: ToString, P: Into<PathBuf>>(mut self, name: S, root: P) -> Self {
+        self.tablespaces.insert(name.to_string(), root.into());
+        self
+    }
+
+    /// Gets the root that relations are saved in when they aren't in a tablespace
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Gets the root of a tablespace, if there's one with the name
+    pub fn tablespace(&self, name: &str) -> Option<&Path> {
+        self.tablespaces.get(name).map(PathBuf::as_path)
+    }
+
+    /// Gets the names of all of the tablespaces, in sorted order
+    pub fn tablespace_names(&self) -> Vec<&str> {
+        let mut ret: Vec<_> = self.tablespaces.keys().map(String::as_str).collect();
+        ret.sort_unstable();
+        ret
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig::new(DEFAULT_ROOT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tablespaces() {
+        let config = StorageConfig::new("/data")
+            .with_tablespace("fast", "/mnt/ssd")
+            .with_tablespace("archive", "/mnt/hdd");
+        assert_eq!(config.root(), Path::new("/data"));
+        assert_eq!(config.tablespace("fast"), Some(Path::new("/mnt/ssd")));
+        assert_eq!(config.tablespace("slow"), None);
+        assert_eq!(config.tablespace_names(), vec!["archive", "fast"]);
+        assert_eq!(StorageConfig::default().root(), Path::new(DEFAULT_ROOT));
+        assert_eq!(
+            storage_path(config.root(), &Identifier::parse("company::employees")),
+            PathBuf::from("/data/company/employees")
+        );
+    }
+
+    #[test]
+    fn atomic_writes() {
+        let directory =
+            std::env::temp_dir().join(format!("rad_db_atomic_writes_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("file");
+        atomic_write(&path, b"first").unwrap();
+        atomic_write(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert!(!path.with_extension("tmp").exists());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
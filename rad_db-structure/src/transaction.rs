@@ -0,0 +1,669 @@
+//! Transactions, which group changes to one or more relations.
+//!
+//! A [Transaction] stages inserts, updates and deletes without changing any relation. When the
+//! transaction commits, every staged change is checked against the relations first, and the
+//! changes are only made if all of them can be. Dropping a transaction, or calling
+//! [Transaction::rollback], discards the staged changes.
+//!
+//! # Isolation
+//!
+//! Staged changes are only visible through [Transaction::get]. Every other reader of a relation,
+//! including scans with a [BlockIterator] or a [StoredTupleIterator], only sees the changes once
+//! the transaction commits. Committing needs mutable access to every relation that was changed, so
+//! a commit can't happen while one of those relations is being scanned, and a scan sees either all
+//! of the changes a transaction made to its relation or none of them.
+//!
+//! A transaction doesn't lock the relations it stages changes for. Changes made to a relation
+//! after a transaction staged its own are seen when the transaction commits, so the commit fails if
+//! they conflict, such as when a tuple with the same primary key as a staged insert was inserted.
+//!
+//! # Durability
+//!
+//! When a transaction changes a single relation, its changes are appended to the log of the
+//! relation as a single record, so if the process stops during the commit, either all of the
+//! changes are replayed or none of them are.
+//!
+//! When a transaction changes more than one relation, the changes to each relation are first
+//! logged as prepared, which aren't replayed on their own. Then an empty commit marker file is
+//! written to the [COMMIT_MARKER_DIRECTORY] of the storage root of the first changed relation,
+//! which commits the transaction. Prepared changes are only replayed if their commit marker
+//! exists, so if the process stops during the commit, every relation either replays the changes
+//! of the transaction or none do. The prepared changes refer to the marker by its absolute path, so
+//! that it's found however the relations are reopened. Once every relation has logged the changes
+//! as committed, the marker is removed. A marker left behind by a stopped process is kept, as
+//! relations that haven't been reopened since still need it.
+//!
+//! If a relation can't log or save the changes once the marker is written, the changes are still
+//! made to every relation, as the transaction is already committed. The marker is kept so the
+//! prepared changes are replayed, and the commit returns [TransactionError::CommittedWithError].
+//!
+//! [BlockIterator]: crate::relations::tuple_storage::BlockIterator
+//! [StoredTupleIterator]: crate::relations::tuple_storage::StoredTupleIterator
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use num_bigint::BigUint;
+
+use rad_db_types::Value;
+
+use crate::identifier::Identifier;
+use crate::relations::tuple_storage::{KeyArityError, TupleInsertionError, TupleStorage};
+use crate::relations::Relation;
+use crate::storage::atomic_write;
+use crate::tuple::Tuple;
+
+/// The directory under a storage root that commit markers are written to
+pub const COMMIT_MARKER_DIRECTORY: &str = ".transactions";
+
+/// When a change couldn't be staged, or a transaction couldn't be committed
+#[derive(Debug)]
+pub enum TransactionError {
+    /// A key doesn't have one value for every attribute of the primary key
+    WrongArity { expected: usize, found: usize },
+    /// An assignment is to an attribute that isn't in the relation
+    UnknownField(Identifier),
+    /// A tuple doesn't fit the relation, or its primary key is already present
+    Invalid {
+        relation: Identifier,
+        cause: TupleInsertionError,
+    },
+    /// Changes were staged for a relation that wasn't given to the commit
+    MissingRelation(Identifier),
+    /// The changes couldn't be saved
+    Io(std::io::Error),
+    /// The transaction committed and every change was made, but not all of them could be saved.
+    /// The changes are made again when the relations are reopened.
+    CommittedWithError(std::io::Error),
+}
+
+impl Display for TransactionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionError::WrongArity { expected, found } => {
+                write!(f, "Expected {} key values but found {}", expected, found)
+            }
+            TransactionError::UnknownField(field) => write!(f, "No field named {}", field),
+            TransactionError::Invalid { relation, cause } => {
+                write!(f, "Invalid change to {}: {}", relation, cause)
+            }
+            TransactionError::MissingRelation(relation) => {
+                write!(f, "Relation {} wasn't given to the commit", relation)
+            }
+            TransactionError::Io(e) => write!(f, "Couldn't save transaction: {}", e),
+            TransactionError::CommittedWithError(e) => {
+                write!(f, "Transaction committed, but couldn't be saved: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for TransactionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TransactionError::Invalid { cause, .. } => Some(cause),
+            TransactionError::Io(e) | TransactionError::CommittedWithError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyArityError> for TransactionError {
+    fn from(e: KeyArityError) -> Self {
+        let KeyArityError { expected, found } = e;
+        TransactionError::WrongArity { expected, found }
+    }
+}
+
+impl From<std::io::Error> for TransactionError {
+    fn from(e: std::io::Error) -> Self {
+        TransactionError::Io(e)
+    }
+}
+
+pub type TransactionResult<T> = Result<T, TransactionError>;
+
+/// The tuple that each changed hash of a relation ends up storing, where `None` means the tuple
+/// was deleted
+pub(crate) type ResolvedChanges = Vec<(BigUint, Option<Tuple>)>;
+
+/// A change that's staged for a relation
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    Insert(Tuple),
+    Update {
+        key: Vec<Value>,
+        assignments: Vec<(usize, Value)>,
+    },
+    Delete(Vec<Value>),
+}
+
+/// Changes to one or more relations that are made together
+#[derive(Debug, Default)]
+pub struct Transaction {
+    /// The changes staged for each relation, by the name of the relation, in the order they were
+    /// staged
+    changes: HashMap<Identifier, Vec<Change>>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no changes are staged
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Stages an insert of a tuple, which must fit the relation. The commit fails if a tuple with
+    /// the same primary key is in the relation by then.
+    pub fn insert(&mut self, relation: &Relation, tuple: Tuple) -> TransactionResult<()> {
+        let invalid = |cause| TransactionError::Invalid {
+            relation: relation.name().clone(),
+            cause,
+        };
+        let tuple = relation.storage().validate(tuple).map_err(invalid)?;
+        self.stage(relation, Change::Insert(tuple));
+        Ok(())
+    }
+
+    /// Stages assigning values to fields of the tuple with the primary key. Nothing is updated if
+    /// there's no tuple with the key when the transaction commits.
+    pub fn update<K, I, A>(
+        &mut self,
+        relation: &Relation,
+        key: K,
+        assignments: A,
+    ) -> TransactionResult<()>
+    where
+        K: AsRef<[Value]>,
+        I: Into<Identifier>,
+        A: IntoIterator<Item = (I, Value)>,
+    {
+        let key = key.as_ref();
+        relation.storage().hash_key(key)?;
+        let mut resolved = vec![];
+        for (field, value) in assignments {
+            let field = field.into();
+            match relation.get_field_index(field.clone()) {
+                Some(index) => resolved.push((index, value)),
+                None => return Err(TransactionError::UnknownField(field)),
+            }
+        }
+        self.stage(
+            relation,
+            Change::Update {
+                key: key.to_vec(),
+                assignments: resolved,
+            },
+        );
+        Ok(())
+    }
+
+    /// Stages a delete of the tuple with the primary key
+    pub fn delete<K: AsRef<[Value]>>(
+        &mut self,
+        relation: &Relation,
+        key: K,
+    ) -> TransactionResult<()> {
+        let key = key.as_ref();
+        relation.storage().hash_key(key)?;
+        self.stage(relation, Change::Delete(key.to_vec()));
+        Ok(())
+    }
+
+    /// Gets the tuple with the primary key as it would be if the transaction committed now
+    pub fn get<K: AsRef<[Value]>>(
+        &self,
+        relation: &Relation,
+        key: K,
+    ) -> TransactionResult<Option<Tuple>> {
+        let key = key.as_ref();
+        let storage = relation.storage();
+        let hash = storage.hash_key(key)?;
+        let staged = match self.changes.get(relation.name()) {
+            Some(changes) => resolve(relation, changes)?,
+            None => HashMap::new(),
+        };
+        Ok(lookup(storage, &staged, &hash).filter(|tuple| storage.has_key(tuple, key)))
+    }
+
+    /// Makes every staged change to the relations, which must include every relation that changes
+    /// were staged for. If any change can't be made, such as an insert of a primary key that's
+    /// already present, none of the changes are made.
+    pub fn commit(self, relations: &mut [&mut Relation]) -> TransactionResult<()> {
+        self.commit_changes(relations).map(|_| ())
+    }
+
+    /// Commits the transaction, returning the index of each relation that was changed with its
+    /// resolved changes
+    pub(crate) fn commit_changes(
+        self,
+        relations: &mut [&mut Relation],
+    ) -> TransactionResult<Vec<(usize, ResolvedChanges)>> {
+        let mut resolved = Vec::with_capacity(self.changes.len());
+        for (name, changes) in &self.changes {
+            let index = relations
+                .iter()
+                .position(|relation| relation.name() == name)
+                .ok_or_else(|| TransactionError::MissingRelation(name.clone()))?;
+            let staged: ResolvedChanges = resolve(relations[index], changes)?.into_iter().collect();
+            resolved.push((index, staged));
+        }
+        let commit_marker = match prepare(relations, &resolved)? {
+            Some(commit_marker) => commit_marker,
+            None => {
+                for (index, staged) in &resolved {
+                    relations[*index]
+                        .storage_mut()
+                        .commit_changes(staged.clone())?;
+                }
+                return Ok(resolved);
+            }
+        };
+        // The transaction is committed once the marker exists, so every relation is changed
+        let mut failure = None;
+        for (index, staged) in &resolved {
+            let storage = relations[*index].storage_mut();
+            let committed = if storage.is_logged() {
+                storage.commit_prepared_changes(staged.clone())
+            } else {
+                storage.commit_changes(staged.clone())
+            };
+            if let Err(e) = committed {
+                failure.get_or_insert(e);
+            }
+        }
+        match failure {
+            // The marker is kept, so that the prepared changes are replayed
+            Some(e) => Err(TransactionError::CommittedWithError(e)),
+            None => {
+                if let Err(e) = std::fs::remove_file(&commit_marker) {
+                    log::warn!("Couldn't remove commit marker {:?}: {}", commit_marker, e);
+                }
+                Ok(resolved)
+            }
+        }
+    }
+
+    /// Works out the changes to a relation as it is now, without making them. Fails if changes
+    /// were staged for any other relation.
+    pub(crate) fn resolve_changes(
+        &self,
+        relation: &Relation,
+    ) -> TransactionResult<ResolvedChanges> {
+        if let Some(name) = self.changes.keys().find(|name| *name != relation.name()) {
+            return Err(TransactionError::MissingRelation(name.clone()));
+        }
+        match self.changes.get(relation.name()) {
+            Some(changes) => Ok(resolve(relation, changes)?.into_iter().collect()),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Discards every staged change
+    pub fn rollback(self) {}
+
+    fn stage(&mut self, relation: &Relation, change: Change) {
+        self.changes
+            .entry(relation.name().clone())
+            .or_default()
+            .push(change);
+    }
+}
+
+/// Logs the changes to every relation as prepared, then writes the commit marker that commits them,
+/// if more than one logged relation is changed. Returns the path of the commit marker.
+fn prepare(
+    relations: &mut [&mut Relation],
+    resolved: &[(usize, ResolvedChanges)],
+) -> TransactionResult<Option<PathBuf>> {
+    let logged: Vec<_> = resolved
+        .iter()
+        .filter(|(index, _)| relations[*index].storage().is_logged())
+        .collect();
+    if logged.len() < 2 {
+        return Ok(None);
+    }
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let name = format!(
+        "{}_{}_{}.commit",
+        std::process::id(),
+        since_epoch,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let directory = relations[logged[0].0]
+        .storage_root()
+        .join(COMMIT_MARKER_DIRECTORY);
+    // The path is logged, so it's made absolute to be found from any working directory
+    std::fs::create_dir_all(&directory)?;
+    let commit_marker = directory.canonicalize()?.join(name);
+    let path = commit_marker.to_str().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The path of the commit marker isn't UTF-8",
+        )
+    })?;
+    for (index, staged) in logged {
+        relations[*index]
+            .storage_mut()
+            .prepare_changes(staged.clone(), path)?;
+    }
+    atomic_write(&commit_marker, &[])?;
+    Ok(Some(commit_marker))
+}
+
+/// Gets the tuple stored with the hash, as it is after the staged changes
+fn lookup(
+    storage: &TupleStorage,
+    staged: &HashMap<BigUint, Option<Tuple>>,
+    hash: &BigUint,
+) -> Option<Tuple> {
+    match staged.get(hash) {
+        Some(tuple) => tuple.clone(),
+        None => storage.get_by_hash(hash),
+    }
+}
+
+/// Works out the tuple that each hash changed by the changes ends up storing, where `None` means
+/// the tuple is deleted
+fn resolve(
+    relation: &Relation,
+    changes: &[Change],
+) -> TransactionResult<HashMap<BigUint, Option<Tuple>>> {
+    let storage = relation.storage();
+    let invalid = |cause| TransactionError::Invalid {
+        relation: relation.name().clone(),
+        cause,
+    };
+    let mut staged = HashMap::new();
+    for change in changes {
+        match change {
+            Change::Insert(tuple) => {
+                let hash = storage.hash_tuple(tuple);
+                if let Some(stored) = lookup(storage, &staged, &hash) {
+                    return Err(invalid(storage.conflict(&stored, tuple)));
+                }
+                staged.insert(hash, Some(tuple.clone()));
+            }
+            Change::Update { key, assignments } => {
+                let hash = storage.hash_key(key)?;
+                let existing = match lookup(storage, &staged, &hash) {
+                    Some(existing) if storage.has_key(&existing, key) => existing,
+                    _ => continue,
+                };
+                let mut updated = existing;
+                for (index, value) in assignments {
+                    updated[*index] = value.clone();
+                }
+                let updated = storage.validate(updated).map_err(invalid)?;
+                let new_hash = storage.hash_tuple(&updated);
+                if new_hash != hash {
+                    if let Some(stored) = lookup(storage, &staged, &new_hash) {
+                        return Err(invalid(storage.conflict(&stored, &updated)));
+                    }
+                    staged.insert(hash, None);
+                }
+                staged.insert(new_hash, Some(updated));
+            }
+            Change::Delete(key) => {
+                let hash = storage.hash_key(key)?;
+                if let Some(existing) = lookup(storage, &staged, &hash) {
+                    if storage.has_key(&existing, key) {
+                        staged.insert(hash, None);
+                    }
+                }
+            }
+        }
+    }
+    Ok(staged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::primary::PrimaryKeyDefinition;
+    use crate::storage::storage_path;
+    use rad_db_types::{DataType, Type};
+
+    fn relation(name: &str) -> Relation {
+        Relation::new_volatile(
+            Identifier::new(name),
+            vec![
+                ("id", DataType::UnsignedInt),
+                ("name", DataType::String(None)),
+            ],
+            4,
+            PrimaryKeyDefinition::new(vec![0]),
+        )
+    }
+
+    fn row(id: u32, name: &str) -> Tuple {
+        Tuple::new(vec![Type::from(id), Type::from(name)])
+    }
+
+    #[test]
+    fn commit() {
+        let mut employees = relation("employees");
+        let mut managers = relation("managers");
+        for id in 0..4 {
+            employees.insert(row(id, "old")).unwrap();
+        }
+
+        let mut transaction = Transaction::new();
+        transaction.insert(&employees, row(10, "new")).unwrap();
+        transaction
+            .update(
+                &employees,
+                [Type::from(1u32)],
+                vec![("name", Type::from("updated"))],
+            )
+            .unwrap();
+        transaction
+            .update(
+                &employees,
+                [Type::from(2u32)],
+                vec![("id", Type::from(20u32))],
+            )
+            .unwrap();
+        transaction.delete(&employees, [Type::from(3u32)]).unwrap();
+        transaction.insert(&managers, row(1, "manager")).unwrap();
+
+        assert_eq!(
+            transaction.get(&employees, [Type::from(1u32)]).unwrap(),
+            Some(row(1, "updated"))
+        );
+        assert_eq!(
+            transaction.get(&employees, [Type::from(3u32)]).unwrap(),
+            None
+        );
+        assert_eq!(employees.get([Type::from(1u32)]), Ok(Some(row(1, "old"))));
+        assert_eq!(employees.tuples().count(), 4);
+        assert!(managers.is_empty());
+
+        transaction
+            .commit(&mut [&mut employees, &mut managers])
+            .unwrap();
+        let mut ids: Vec<_> = employees.tuples().map(|tuple| tuple[0].clone()).collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![0u32, 1, 10, 20]
+                .into_iter()
+                .map(Type::from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            employees.get([Type::from(1u32)]),
+            Ok(Some(row(1, "updated")))
+        );
+        assert_eq!(employees.get([Type::from(20u32)]), Ok(Some(row(20, "old"))));
+        assert_eq!(
+            managers.get([Type::from(1u32)]),
+            Ok(Some(row(1, "manager")))
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut employees = relation("employees");
+        let mut managers = relation("managers");
+        employees.insert(row(0, "old")).unwrap();
+
+        let mut transaction = Transaction::new();
+        transaction.insert(&managers, row(1, "manager")).unwrap();
+        transaction.insert(&employees, row(1, "new")).unwrap();
+        employees.insert(row(1, "direct")).unwrap();
+        match transaction.commit(&mut [&mut employees, &mut managers]) {
+            Err(TransactionError::Invalid { relation, cause }) => {
+                assert_eq!(relation, Identifier::new("employees"));
+                assert_eq!(cause, TupleInsertionError::PrimaryKeyPresent);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(managers.is_empty());
+        assert_eq!(
+            employees.get([Type::from(1u32)]),
+            Ok(Some(row(1, "direct")))
+        );
+
+        let mut transaction = Transaction::new();
+        transaction.insert(&managers, row(1, "manager")).unwrap();
+        assert!(matches!(
+            transaction.commit(&mut [&mut employees]),
+            Err(TransactionError::MissingRelation(_))
+        ));
+        assert!(managers.is_empty());
+
+        let mut transaction = Transaction::new();
+        assert!(matches!(
+            transaction.insert(&employees, Tuple::new(vec![Type::from(2u32)])),
+            Err(TransactionError::Invalid { .. })
+        ));
+        assert!(matches!(
+            transaction.delete(&employees, [Type::from(2u32), Type::from("b")]),
+            Err(TransactionError::WrongArity {
+                expected: 1,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            transaction.update(
+                &employees,
+                [Type::from(0u32)],
+                vec![("salary", Type::from(2u32))]
+            ),
+            Err(TransactionError::UnknownField(_))
+        ));
+        transaction
+            .update(
+                &employees,
+                [Type::from(0u32)],
+                vec![("id", Type::from(1u32))],
+            )
+            .unwrap();
+        assert!(transaction.commit(&mut [&mut employees]).is_err());
+        assert_eq!(employees.get([Type::from(0u32)]), Ok(Some(row(0, "old"))));
+    }
+
+    #[test]
+    fn rollback() {
+        let mut employees = relation("employees");
+        let mut transaction = Transaction::new();
+        transaction.insert(&employees, row(0, "new")).unwrap();
+        transaction.rollback();
+        assert!(employees.is_empty());
+
+        let mut transaction = Transaction::new();
+        transaction.insert(&employees, row(0, "new")).unwrap();
+        transaction.delete(&employees, [Type::from(0u32)]).unwrap();
+        assert_eq!(
+            transaction.get(&employees, [Type::from(0u32)]).unwrap(),
+            None
+        );
+        transaction.commit(&mut [&mut employees]).unwrap();
+        assert!(employees.is_empty());
+    }
+
+    #[test]
+    fn prepared_changes() {
+        let names = vec![
+            Identifier::new("prepared_employees"),
+            Identifier::new("prepared_managers"),
+        ];
+        let root =
+            std::env::temp_dir().join(format!("rad_db_prepared_test_{}", std::process::id()));
+        let markers = root.join(COMMIT_MARKER_DIRECTORY);
+        let committed = markers.join("prepared_test.commit");
+        let stopped = markers.join("prepared_test_stopped.commit");
+        std::fs::create_dir_all(&markers).unwrap();
+        atomic_write(&committed, &[]).unwrap();
+        for name in &names {
+            let mut relation = Relation::new_in(
+                &root,
+                name.clone(),
+                vec![
+                    ("id", DataType::UnsignedInt),
+                    ("name", DataType::String(None)),
+                ],
+                4,
+                PrimaryKeyDefinition::new(vec![0]),
+            );
+            relation.insert(row(0, "old")).unwrap();
+            let storage = relation.storage_mut();
+            for (tuple, marker) in [
+                (row(1, "committed"), &committed),
+                (row(2, "stopped"), &stopped),
+            ] {
+                let hash = storage.hash_tuple(&tuple);
+                storage
+                    .prepare_changes(vec![(hash, Some(tuple))], marker.to_str().unwrap())
+                    .unwrap();
+            }
+            // Stop without saving anything
+            std::mem::forget(relation);
+        }
+
+        let mut relations: Vec<_> = names
+            .iter()
+            .map(|name| Relation::open_in(&root, name.clone()).unwrap())
+            .collect();
+        for relation in &relations {
+            assert_eq!(
+                relation.get([Type::from(1u32)]),
+                Ok(Some(row(1, "committed")))
+            );
+            assert_eq!(relation.get([Type::from(2u32)]), Ok(None));
+        }
+        std::fs::remove_file(committed).unwrap();
+
+        let mut transaction = Transaction::new();
+        for relation in &relations {
+            transaction.insert(relation, row(3, "new")).unwrap();
+        }
+        let mut borrowed: Vec<_> = relations.iter_mut().collect();
+        transaction.commit(&mut borrowed).unwrap();
+        // The marker is logged with its absolute path
+        let absolute = markers.canonicalize().unwrap();
+        for name in &names {
+            let log = std::fs::read(storage_path(&root, name).join("wal.log")).unwrap();
+            let absolute = absolute.to_str().unwrap().as_bytes();
+            assert!(log.windows(absolute.len()).any(|bytes| bytes == absolute));
+        }
+        std::mem::drop(relations);
+        for name in &names {
+            let relation = Relation::open_in(&root, name.clone()).unwrap();
+            assert_eq!(relation.get([Type::from(3u32)]), Ok(Some(row(3, "new"))));
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
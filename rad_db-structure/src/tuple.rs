@@ -14,6 +14,7 @@ use std::cmp::Reverse;
 /// Represents a single row within a database.
 /// A tuple knows no information about itself besides its contents
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tuple(Vec<Type>);
 
 impl Tuple {
@@ -0,0 +1,574 @@
+//! Checked arithmetic on values.
+//!
+//! Numbers of different types are promoted before the operation is performed:
+//! - Two integers produce an integer as wide as the wider of the two. If either of them is signed
+//!   the result is signed, and it is widened once more if the unsigned one is at least as wide.
+//! - An integer and a decimal produce a decimal.
+//! - Two floats produce a float, and any other combination with a float or double produces a
+//!   double.
+//!
+//! Strings and characters can be added together to concatenate them. An integer amount of days can
+//! be added to or subtracted from a date, and an integer amount of years from a year. Subtracting
+//! two dates gives the days between them.
+//!
+//! Intervals can be added to and subtracted from each other, and from dates, date times and
+//! timestamps. Adding an interval with a time part to a date gives a date time. Subtracting two
+//! date times or timestamps gives the interval between them.
+//!
+//! If either side is NULL the result is NULL, and if either side is an optional value the result is
+//! optional as well.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Div, Mul, Sub};
+
+use chrono::{Duration, Local, TimeZone};
+
+use crate::data_type::DataType;
+use crate::decimal::DecimalError;
+use crate::{Decimal, Interval, Numeric, Signed, Text, Time, Type, Unsigned, Value};
+
+/// An arithmetic operator
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Subtract => write!(f, "-"),
+            Operator::Multiply => write!(f, "*"),
+            Operator::Divide => write!(f, "/"),
+        }
+    }
+}
+
+/// When an arithmetic operation couldn't be performed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArithmeticError {
+    /// The result doesn't fit in its type
+    Overflow,
+    /// The right side of a division was zero
+    DivisionByZero,
+    /// The operator can't be used on these types
+    Unsupported {
+        operator: Operator,
+        left: DataType,
+        right: DataType,
+    },
+}
+
+impl Display for ArithmeticError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArithmeticError::Overflow => write!(f, "Arithmetic overflow"),
+            ArithmeticError::DivisionByZero => write!(f, "Division by zero"),
+            ArithmeticError::Unsupported {
+                operator,
+                left,
+                right,
+            } => write!(f, "Can't evaluate {} {} {}", left, operator, right),
+        }
+    }
+}
+
+impl Error for ArithmeticError {}
+
+impl From<DecimalError> for ArithmeticError {
+    fn from(e: DecimalError) -> Self {
+        match e {
+            DecimalError::DivisionByZero => ArithmeticError::DivisionByZero,
+            _ => ArithmeticError::Overflow,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ArithmeticError>;
+
+/// An integer with its signedness and width, where the width is 0 for bytes up to 3 for longs
+#[derive(Debug, Copy, Clone)]
+struct Integer {
+    value: i128,
+    signed: bool,
+    width: u8,
+}
+
+impl Integer {
+    fn of(numeric: &Numeric) -> Option<Self> {
+        let (value, signed, width) = match *numeric {
+            Numeric::Signed(Signed::Byte(b)) => (b as i128, true, 0),
+            Numeric::Signed(Signed::Short(s)) => (s as i128, true, 1),
+            Numeric::Signed(Signed::Int(i)) => (i as i128, true, 2),
+            Numeric::Signed(Signed::Long(l)) => (l as i128, true, 3),
+            Numeric::Unsigned(Unsigned::Byte(b)) => (b as i128, false, 0),
+            Numeric::Unsigned(Unsigned::Short(s)) => (s as i128, false, 1),
+            Numeric::Unsigned(Unsigned::Int(i)) => (i as i128, false, 2),
+            Numeric::Unsigned(Unsigned::Long(l)) => (l as i128, false, 3),
+            _ => return None,
+        };
+        Some(Integer {
+            value,
+            signed,
+            width,
+        })
+    }
+
+    /// The signedness and width of the result of an operation on two integers
+    fn promote(left: Integer, right: Integer) -> (bool, u8) {
+        let width = left.width.max(right.width);
+        match (left.signed, right.signed) {
+            (false, false) => (false, width),
+            (true, true) => (true, width),
+            (true, false) if right.width >= left.width => (true, (right.width + 1).min(3)),
+            (false, true) if left.width >= right.width => (true, (left.width + 1).min(3)),
+            _ => (true, width),
+        }
+    }
+
+    fn into_numeric(value: i128, signed: bool, width: u8) -> Result<Numeric> {
+        let overflow = |_| ArithmeticError::Overflow;
+        let ret = match (signed, width) {
+            (true, 0) => Numeric::Signed(Signed::Byte(i8::try_from(value).map_err(overflow)?)),
+            (true, 1) => Numeric::Signed(Signed::Short(i16::try_from(value).map_err(overflow)?)),
+            (true, 2) => Numeric::Signed(Signed::Int(i32::try_from(value).map_err(overflow)?)),
+            (true, _) => Numeric::Signed(Signed::Long(i64::try_from(value).map_err(overflow)?)),
+            (false, 0) => Numeric::Unsigned(Unsigned::Byte(u8::try_from(value).map_err(overflow)?)),
+            (false, 1) => {
+                Numeric::Unsigned(Unsigned::Short(u16::try_from(value).map_err(overflow)?))
+            }
+            (false, 2) => Numeric::Unsigned(Unsigned::Int(u32::try_from(value).map_err(overflow)?)),
+            (false, _) => {
+                Numeric::Unsigned(Unsigned::Long(u64::try_from(value).map_err(overflow)?))
+            }
+        };
+        Ok(ret)
+    }
+
+    fn to_decimal(self) -> Decimal {
+        if self.signed {
+            Decimal::from(self.value as i64)
+        } else {
+            Decimal::from(self.value as u64)
+        }
+    }
+}
+
+fn integer_operation(operator: Operator, left: Integer, right: Integer) -> Result<Numeric> {
+    let value = match operator {
+        Operator::Add => left.value.checked_add(right.value),
+        Operator::Subtract => left.value.checked_sub(right.value),
+        Operator::Multiply => left.value.checked_mul(right.value),
+        Operator::Divide => {
+            if right.value == 0 {
+                return Err(ArithmeticError::DivisionByZero);
+            }
+            left.value.checked_div(right.value)
+        }
+    }
+    .ok_or(ArithmeticError::Overflow)?;
+    let (signed, width) = Integer::promote(left, right);
+    Integer::into_numeric(value, signed, width)
+}
+
+fn decimal_operation(operator: Operator, left: &Decimal, right: &Decimal) -> Result<Numeric> {
+    let ret = match operator {
+        Operator::Add => left.checked_add(right)?,
+        Operator::Subtract => left.checked_sub(right)?,
+        Operator::Multiply => left.checked_mul(right)?,
+        Operator::Divide => left.checked_div(right)?,
+    };
+    Ok(Numeric::Decimal(ret))
+}
+
+fn double_operation(operator: Operator, left: f64, right: f64) -> Result<f64> {
+    let ret = match operator {
+        Operator::Add => left + right,
+        Operator::Subtract => left - right,
+        Operator::Multiply => left * right,
+        Operator::Divide => {
+            if right == 0.0 {
+                return Err(ArithmeticError::DivisionByZero);
+            }
+            left / right
+        }
+    };
+    if ret.is_infinite() && left.is_finite() && right.is_finite() {
+        Err(ArithmeticError::Overflow)
+    } else {
+        Ok(ret)
+    }
+}
+
+fn as_double(numeric: &Numeric) -> f64 {
+    match numeric {
+        Numeric::Float(f) => *f as f64,
+        Numeric::Double(d) => *d,
+        Numeric::Decimal(d) => d.to_f64(),
+        integer => Integer::of(integer).unwrap().value as f64,
+    }
+}
+
+fn numeric_operation(operator: Operator, left: &Numeric, right: &Numeric) -> Result<Numeric> {
+    match (left, right) {
+        (Numeric::Float(left), Numeric::Float(right)) => {
+            let ret = double_operation(operator, *left as f64, *right as f64)?;
+            if (ret as f32).is_infinite() && ret.is_finite() {
+                return Err(ArithmeticError::Overflow);
+            }
+            Ok(Numeric::Float(ret as f32))
+        }
+        (Numeric::Float(_), _)
+        | (Numeric::Double(_), _)
+        | (_, Numeric::Float(_))
+        | (_, Numeric::Double(_)) => Ok(Numeric::Double(double_operation(
+            operator,
+            as_double(left),
+            as_double(right),
+        )?)),
+        (Numeric::Decimal(left), Numeric::Decimal(right)) => {
+            decimal_operation(operator, left, right)
+        }
+        (Numeric::Decimal(left), right) => {
+            let right = Integer::of(right).unwrap().to_decimal();
+            decimal_operation(operator, left, &right)
+        }
+        (left, Numeric::Decimal(right)) => {
+            let left = Integer::of(left).unwrap().to_decimal();
+            decimal_operation(operator, &left, right)
+        }
+        (left, right) => integer_operation(
+            operator,
+            Integer::of(left).unwrap(),
+            Integer::of(right).unwrap(),
+        ),
+    }
+}
+
+fn as_days(value: &Value) -> Option<i64> {
+    match value {
+        Type::Numeric(numeric) => i64::try_from(Integer::of(numeric)?.value).ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Type::Text(Text::Char(c)) => Some(c.to_string()),
+        Type::Text(Text::String(s, _)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Removes all the layers of optional from a value, or gives `None` if it's NULL
+fn non_null(value: &Value) -> Option<&Value> {
+    match value {
+        Type::Optional(None) => None,
+        Type::Optional(Some(inner)) => non_null(inner),
+        value => Some(value),
+    }
+}
+
+fn date_operation(operator: Operator, days: i64) -> Option<Duration> {
+    match operator {
+        Operator::Add => Duration::try_days(days),
+        Operator::Subtract => Duration::try_days(days.checked_neg()?),
+        _ => None,
+    }
+}
+
+/// Adds or subtracts an interval from a point in time
+fn interval_operation(operator: Operator, time: &Time, interval: &Interval) -> Option<Time> {
+    let interval = match operator {
+        Operator::Add => *interval,
+        Operator::Subtract => interval.checked_neg()?,
+        _ => return None,
+    };
+    let ret = match time {
+        Time::Date(date) => {
+            let midnight = Local
+                .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+                .earliest()?;
+            let date_time = interval.add_to(&midnight)?;
+            if interval.get_nanos() == 0 {
+                Time::Date(date_time.date_naive())
+            } else {
+                Time::DateTime(date_time)
+            }
+        }
+        Time::DateTime(date_time) => Time::DateTime(interval.add_to(date_time)?),
+        Time::Timestamp(timestamp) => Time::Timestamp(interval.add_to(timestamp)?),
+        _ => return None,
+    };
+    Some(ret)
+}
+
+fn non_null_operation(operator: Operator, left: &Value, right: &Value) -> Result<Value> {
+    let unsupported = || ArithmeticError::Unsupported {
+        operator,
+        left: DataType::of(left).unwrap(),
+        right: DataType::of(right).unwrap(),
+    };
+    match (left, right) {
+        (Type::Numeric(left), Type::Numeric(right)) => {
+            numeric_operation(operator, left, right).map(Type::Numeric)
+        }
+        (Type::Time(Time::Interval(left)), Type::Time(Time::Interval(right))) => {
+            let interval = match operator {
+                Operator::Add => left.checked_add(right),
+                Operator::Subtract => left.checked_sub(right),
+                _ => return Err(unsupported()),
+            }
+            .ok_or(ArithmeticError::Overflow)?;
+            Ok(Time::Interval(interval).into())
+        }
+        (Type::Time(time), Type::Time(Time::Interval(interval)))
+            if operator == Operator::Add || operator == Operator::Subtract =>
+        {
+            if let Time::Year(_) = time {
+                return Err(unsupported());
+            }
+            let time =
+                interval_operation(operator, time, interval).ok_or(ArithmeticError::Overflow)?;
+            Ok(time.into())
+        }
+        (Type::Time(Time::Interval(_)), Type::Time(_)) if operator == Operator::Add => {
+            non_null_operation(operator, right, left)
+        }
+        (Type::Time(left_time), Type::Time(right_time)) if operator == Operator::Subtract => {
+            let interval = match (left_time, right_time) {
+                (Time::Date(left), Time::Date(right)) => {
+                    return Ok(Signed::Long(left.signed_duration_since(*right).num_days()).into())
+                }
+                (Time::DateTime(left), Time::DateTime(right)) => Interval::between(right, left),
+                (Time::DateTime(left), Time::Timestamp(right)) => Interval::between(right, left),
+                (Time::Timestamp(left), Time::DateTime(right)) => Interval::between(right, left),
+                (Time::Timestamp(left), Time::Timestamp(right)) => Interval::between(right, left),
+                _ => return Err(unsupported()),
+            }
+            .ok_or(ArithmeticError::Overflow)?;
+            Ok(Time::Interval(interval).into())
+        }
+        (Type::Time(Time::Date(date)), days) if as_days(days).is_some() => {
+            let duration =
+                date_operation(operator, as_days(days).unwrap()).ok_or_else(unsupported)?;
+            let date = date
+                .checked_add_signed(duration)
+                .ok_or(ArithmeticError::Overflow)?;
+            Ok(Time::Date(date).into())
+        }
+        (days, Type::Time(Time::Date(_))) if operator == Operator::Add => {
+            non_null_operation(operator, right, days)
+        }
+        (Type::Time(Time::Year(year)), years) if as_days(years).is_some() => {
+            let years =
+                i32::try_from(as_days(years).unwrap()).map_err(|_| ArithmeticError::Overflow)?;
+            let year = match operator {
+                Operator::Add => year.checked_add(years),
+                Operator::Subtract => year.checked_sub(years),
+                _ => return Err(unsupported()),
+            }
+            .ok_or(ArithmeticError::Overflow)?;
+            Ok(Time::Year(year).into())
+        }
+        (Type::Text(_), Type::Text(_)) if operator == Operator::Add => {
+            match (text(left), text(right)) {
+                (Some(left), Some(right)) => Ok(Text::String(left + &right, None).into()),
+                _ => Err(unsupported()),
+            }
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+/// Performs an arithmetic operation on two values
+pub fn evaluate(operator: Operator, left: &Value, right: &Value) -> Result<Value> {
+    let (non_null_left, non_null_right) = match (non_null(left), non_null(right)) {
+        (Some(left), Some(right)) => (left, right),
+        _ => return Ok(Type::Optional(None)),
+    };
+    let ret = non_null_operation(operator, non_null_left, non_null_right)?;
+    match (left, right) {
+        (Type::Optional(_), _) | (_, Type::Optional(_)) => Ok(Type::Optional(Some(Box::new(ret)))),
+        _ => Ok(ret),
+    }
+}
+
+impl Type {
+    /// Adds two values, as described in the [arithmetic module]
+    ///
+    /// [arithmetic module]: crate::arithmetic
+    pub fn checked_add(&self, other: &Value) -> Result<Value> {
+        evaluate(Operator::Add, self, other)
+    }
+
+    /// Subtracts a value from this one, as described in the [arithmetic module]
+    ///
+    /// [arithmetic module]: crate::arithmetic
+    pub fn checked_sub(&self, other: &Value) -> Result<Value> {
+        evaluate(Operator::Subtract, self, other)
+    }
+
+    /// Multiplies two values, as described in the [arithmetic module]
+    ///
+    /// [arithmetic module]: crate::arithmetic
+    pub fn checked_mul(&self, other: &Value) -> Result<Value> {
+        evaluate(Operator::Multiply, self, other)
+    }
+
+    /// Divides this value by another, as described in the [arithmetic module]
+    ///
+    /// [arithmetic module]: crate::arithmetic
+    pub fn checked_div(&self, other: &Value) -> Result<Value> {
+        evaluate(Operator::Divide, self, other)
+    }
+}
+
+macro_rules! operator_impl {
+    ($trait_name:ident, $function:ident, $operator:expr) => {
+        impl $trait_name for Type {
+            type Output = Result<Value>;
+
+            fn $function(self, rhs: Self) -> Self::Output {
+                evaluate($operator, &self, &rhs)
+            }
+        }
+
+        impl $trait_name for &Type {
+            type Output = Result<Value>;
+
+            fn $function(self, rhs: Self) -> Self::Output {
+                evaluate($operator, self, rhs)
+            }
+        }
+    };
+}
+
+operator_impl!(Add, add, Operator::Add);
+operator_impl!(Sub, sub, Operator::Subtract);
+operator_impl!(Mul, mul, Operator::Multiply);
+operator_impl!(Div, div, Operator::Divide);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(Type::from(3u8) + Type::from(4u8), Ok(Type::from(7u8)));
+        assert_eq!(Type::from(3u16) - Type::from(4i8), Ok(Type::from(-1i32)));
+        assert_eq!(
+            Type::from(200u8) + Type::from(100u8),
+            Err(ArithmeticError::Overflow)
+        );
+        assert_eq!(
+            Type::from(1u64) - Type::from(2u64),
+            Err(ArithmeticError::Overflow)
+        );
+        assert_eq!(
+            Type::from(i64::MIN) / Type::from(-1i64),
+            Err(ArithmeticError::Overflow)
+        );
+        assert_eq!(Type::from(7i32) / Type::from(2i32), Ok(Type::from(3i32)));
+        assert_eq!(
+            Type::from(7i32) / Type::from(0i32),
+            Err(ArithmeticError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn promotion() {
+        assert_eq!(
+            Type::from(3u8) * Type::from(Numeric::Double(0.5)),
+            Ok(Numeric::Double(1.5).into())
+        );
+        assert_eq!(
+            Type::from(Numeric::Float(1.5)) + Type::from(Numeric::Float(1.0)),
+            Ok(Numeric::Float(2.5).into())
+        );
+        assert_eq!(
+            Type::from(Numeric::Double(f64::MAX)) * Type::from(2u8),
+            Err(ArithmeticError::Overflow)
+        );
+        let price: Decimal = "19.99".parse().unwrap();
+        assert_eq!(
+            Type::from(price) * Type::from(3u8),
+            Ok(Type::from("59.97".parse::<Decimal>().unwrap()))
+        );
+    }
+
+    #[test]
+    fn text_and_time() {
+        assert_eq!(
+            Type::from("Rad") + Type::from(Text::Char('!')),
+            Ok(Type::from("Rad!"))
+        );
+        assert!(matches!(
+            Type::from("Rad") - Type::from("d"),
+            Err(ArithmeticError::Unsupported { .. })
+        ));
+
+        let date: Type = Time::Date(ymd(2020, 2, 28)).into();
+        let next: Type = Time::Date(ymd(2020, 3, 1)).into();
+        assert_eq!(date.checked_add(&Type::from(2u8)), Ok(next.clone()));
+        assert_eq!(Type::from(2u8).checked_add(&date), Ok(next.clone()));
+        assert_eq!(next.checked_sub(&date), Ok(Type::from(2i64)));
+        assert_eq!(
+            Type::from(Time::Year(2020)) - Type::from(20i32),
+            Ok(Time::Year(2000).into())
+        );
+    }
+
+    #[test]
+    fn intervals() {
+        let start: Type =
+            Time::Timestamp(Utc.with_ymd_and_hms(2021, 1, 31, 8, 0, 0).unwrap()).into();
+        let month: Type = Time::Interval(Interval::months(1)).into();
+        let end: Type = Time::Timestamp(Utc.with_ymd_and_hms(2021, 2, 28, 8, 0, 0).unwrap()).into();
+        assert_eq!(start.checked_add(&month), Ok(end.clone()));
+        assert_eq!(month.checked_add(&start), Ok(end.clone()));
+        assert_eq!(
+            end.checked_sub(&start),
+            Ok(Time::Interval(Interval::days(28)).into())
+        );
+
+        let date: Type = Time::Date(ymd(2020, 3, 31)).into();
+        assert_eq!(
+            date.checked_sub(&month),
+            Ok(Time::Date(ymd(2020, 2, 29)).into())
+        );
+        let hours: Type = Time::Interval("PT36H".parse().unwrap()).into();
+        assert_eq!(
+            date.checked_add(&hours),
+            Ok(Time::DateTime(Local.with_ymd_and_hms(2020, 4, 1, 12, 0, 0).unwrap()).into())
+        );
+        assert_eq!(
+            month.checked_sub(&hours),
+            Ok(Time::Interval("P1MT-36H".parse().unwrap()).into())
+        );
+        assert!(month.checked_mul(&hours).is_err());
+        assert!(Type::from(Time::Year(2020)).checked_add(&month).is_err());
+    }
+
+    #[test]
+    fn nulls() {
+        let null = Type::Optional(None);
+        let some = Type::Optional(Some(Box::new(Type::from(1u8))));
+        assert_eq!(&null + &Type::from(1u8), Ok(Type::Optional(None)));
+        assert_eq!(&Type::from("a") * &null, Ok(Type::Optional(None)));
+        assert_eq!(
+            &some + &Type::from(1u8),
+            Ok(Type::Optional(Some(Box::new(Type::from(2u8)))))
+        );
+    }
+}
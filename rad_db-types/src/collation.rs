@@ -0,0 +1,258 @@
+//! The collation used to compare values with each other.
+//!
+//! Unlike the [Ord] implementation of [Value], which is only meant to give values a consistent
+//! order, collation compares values by what they represent:
+//! - NULL is placed before or after every other value, depending on the [NullOrdering]. A non-null
+//!   optional value is compared by its inner value.
+//! - All numbers are comparable. Integers are compared exactly with each other and with decimals,
+//!   and everything else is widened to a double. `NaN` is greater than every other number.
+//! - Text is compared lexicographically by its bytes, so characters, strings, binary strings and
+//!   blobs can be compared with each other.
+//! - Times are compared chronologically, where a date is the start of the day and a year is the
+//!   start of the year in the local time zone. Intervals are compared by their length, and come
+//!   before all points in time.
+//! - `false` is less than `true`.
+//!
+//! Values that aren't comparable are ordered by their kind, where numbers come before text, then
+//! times and then booleans.
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::{Decimal, Numeric, Text, Time, Type, Value};
+
+/// Where NULL values are placed when collating
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NullOrdering {
+    /// NULL is less than every other value
+    #[default]
+    NullsFirst,
+    /// NULL is greater than every other value
+    NullsLast,
+}
+
+/// An integer, widened so that signed and unsigned numbers can be compared
+fn as_integer(numeric: &Numeric) -> Option<i128> {
+    match numeric {
+        Numeric::Signed(signed) => Some(Into::<i64>::into(*signed) as i128),
+        Numeric::Unsigned(unsigned) => Some(Into::<u64>::into(*unsigned) as i128),
+        _ => None,
+    }
+}
+
+fn as_double(numeric: &Numeric) -> f64 {
+    match numeric {
+        Numeric::Float(f) => *f as f64,
+        Numeric::Double(d) => *d,
+        Numeric::Signed(signed) => Into::<i64>::into(*signed) as f64,
+        Numeric::Unsigned(unsigned) => Into::<u64>::into(*unsigned) as f64,
+        Numeric::Decimal(d) => d.to_f64(),
+    }
+}
+
+fn collate_numeric(left: &Numeric, right: &Numeric) -> Ordering {
+    match (left, right) {
+        (Numeric::Decimal(left), Numeric::Decimal(right)) => left.cmp(right),
+        (Numeric::Decimal(left), right) => match as_integer(right) {
+            Some(right) => left.cmp(&integer_decimal(right)),
+            None => collate_numeric(&Numeric::Double(left.to_f64()), right),
+        },
+        (left, Numeric::Decimal(_)) => collate_numeric(right, left).reverse(),
+        (left, right) => match (as_integer(left), as_integer(right)) {
+            (Some(left), Some(right)) => left.cmp(&right),
+            _ => Numeric::Double(as_double(left)).cmp(&Numeric::Double(as_double(right))),
+        },
+    }
+}
+
+fn integer_decimal(integer: i128) -> Decimal {
+    if integer < 0 {
+        Decimal::from(integer as i64)
+    } else {
+        Decimal::from(integer as u64)
+    }
+}
+
+fn text_bytes(text: &Text) -> Vec<u8> {
+    match text {
+        Text::Char(c) => c.to_string().into_bytes(),
+        Text::String(s, _) => s.as_bytes().to_vec(),
+        Text::Binary(b) => vec![*b],
+        Text::BinaryString(bytes, _) => bytes.clone(),
+        Text::Blob(blob) => blob.clone(),
+    }
+}
+
+/// The instant a time starts at
+fn instant(time: &Time) -> Option<DateTime<Utc>> {
+    match time {
+        Time::Date(date) => Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(|date_time| date_time.with_timezone(&Utc)),
+        Time::DateTime(date_time) => Some(date_time.with_timezone(&Utc)),
+        Time::Timestamp(timestamp) => Some(*timestamp),
+        Time::Interval(_) => None,
+        Time::Year(year) => {
+            let start = NaiveDate::from_ymd_opt(*year, 1, 1)?.and_hms_opt(0, 0, 0)?;
+            Local
+                .from_local_datetime(&start)
+                .earliest()
+                .map(|date_time| date_time.with_timezone(&Utc))
+        }
+    }
+}
+
+fn collate_time(left: &Time, right: &Time) -> Ordering {
+    match (left, right) {
+        (Time::Year(left), Time::Year(right)) => left.cmp(right),
+        (Time::Interval(left), Time::Interval(right)) => left.cmp(right),
+        (Time::Interval(_), _) => Ordering::Less,
+        (_, Time::Interval(_)) => Ordering::Greater,
+        (left, right) => instant(left).cmp(&instant(right)),
+    }
+}
+
+/// The order of kinds of values that can't be compared with each other
+fn kind_rank(value: &Value) -> u8 {
+    match value {
+        Type::Numeric(_) => 0,
+        Type::Text(_) => 1,
+        Type::Time(_) => 2,
+        Type::Boolean(_) => 3,
+        Type::Optional(_) => 4,
+    }
+}
+
+impl Type {
+    /// Compares two values using the collation described in the [collation module]
+    ///
+    /// [collation module]: crate::collation
+    pub fn collate(&self, other: &Value, nulls: NullOrdering) -> Ordering {
+        match (self, other) {
+            (Type::Optional(None), Type::Optional(None)) => Ordering::Equal,
+            (Type::Optional(None), _) => match nulls {
+                NullOrdering::NullsFirst => Ordering::Less,
+                NullOrdering::NullsLast => Ordering::Greater,
+            },
+            (_, Type::Optional(None)) => other.collate(self, nulls).reverse(),
+            (Type::Optional(Some(left)), right) => left.collate(right, nulls),
+            (left, Type::Optional(Some(right))) => left.collate(right, nulls),
+            (Type::Numeric(left), Type::Numeric(right)) => collate_numeric(left, right),
+            (Type::Text(left), Type::Text(right)) => text_bytes(left).cmp(&text_bytes(right)),
+            (Type::Time(left), Type::Time(right)) => collate_time(left, right),
+            (Type::Boolean(left), Type::Boolean(right)) => left.cmp(right),
+            (left, right) => kind_rank(left).cmp(&kind_rank(right)),
+        }
+    }
+
+    /// Whether the two values can be compared by their contents. NULL is comparable with every
+    /// value.
+    pub fn comparable(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Type::Optional(None), _) | (_, Type::Optional(None)) => true,
+            (Type::Optional(Some(left)), right) => left.comparable(right),
+            (left, Type::Optional(Some(right))) => left.comparable(right),
+            (Type::Time(Time::Interval(_)), Type::Time(time))
+            | (Type::Time(time), Type::Time(Time::Interval(_))) => {
+                matches!(time, Time::Interval(_))
+            }
+            (left, right) => kind_rank(left) == kind_rank(right),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collate(left: Value, right: Value) -> Ordering {
+        left.collate(&right, NullOrdering::default())
+    }
+
+    #[test]
+    fn nulls() {
+        let null = Type::Optional(None);
+        let one = Type::from(1u8);
+        assert_eq!(collate(null.clone(), one.clone()), Ordering::Less);
+        assert_eq!(one.collate(&null, NullOrdering::NullsLast), Ordering::Less);
+        assert_eq!(collate(null.clone(), null), Ordering::Equal);
+        assert_eq!(
+            collate(Type::Optional(Some(Box::new(one.clone()))), one),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn numeric_widening() {
+        assert_eq!(collate(Type::from(-1i8), Type::from(0u64)), Ordering::Less);
+        assert_eq!(collate(Type::from(3u8), Type::from(3i64)), Ordering::Equal);
+        assert_eq!(
+            collate(Type::from(u64::MAX), Type::from(i64::MAX)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            collate(Numeric::Double(2.5).into(), Type::from(2u32)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            collate(Numeric::Float(f32::NAN).into(), Type::from(u64::MAX)),
+            Ordering::Greater
+        );
+        let decimal: Decimal = "2.50".parse().unwrap();
+        assert_eq!(collate(decimal.into(), Type::from(2i16)), Ordering::Greater);
+        assert_eq!(collate(Type::from(3u16), decimal.into()), Ordering::Greater);
+        assert_eq!(
+            collate(Numeric::Double(2.5).into(), decimal.into()),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn text_and_time() {
+        assert_eq!(
+            collate(Type::from("apple"), Type::from("banana")),
+            Ordering::Less
+        );
+        assert_eq!(
+            collate(Text::Char('b').into(), Type::from("ba")),
+            Ordering::Less
+        );
+        assert_eq!(
+            collate(Type::from("abc"), Text::Blob(b"abc".to_vec()).into()),
+            Ordering::Equal
+        );
+
+        let date = NaiveDate::from_ymd_opt(2020, 6, 15).unwrap();
+        let noon = Local
+            .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+            .unwrap();
+        assert_eq!(
+            collate(Time::Date(date).into(), Time::DateTime(noon).into()),
+            Ordering::Less
+        );
+        assert_eq!(
+            collate(
+                Time::DateTime(noon).into(),
+                Time::Timestamp(noon.with_timezone(&Utc)).into()
+            ),
+            Ordering::Equal
+        );
+        assert_eq!(
+            collate(Time::Year(2020).into(), Time::Date(date).into()),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn incomparable() {
+        assert!(!Type::from(1u8).comparable(&Type::from("1")));
+        assert!(Type::Optional(None).comparable(&Type::from("1")));
+        assert_eq!(collate(Type::from(1u8), Type::from("1")), Ordering::Less);
+        assert_eq!(
+            collate(Type::Boolean(false), Type::from("1")),
+            Ordering::Greater
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Conversions between Rust types and values.
+//!
+//! A Rust type that implements [ToValue] is always stored as the same [DataType], and one that
+//! implements [FromValue] can only be created from a value of that type. `Option<T>` is stored as
+//! the nullable version of the type of `T`, where `None` is NULL. Non-null values of a nullable
+//! type can be converted into the Rust type of the inner type.
+//!
+//! Types whose values are stored as a plain Rust type that's already used for another type, such as
+//! a `YEAR` (an `i32`), are wrapped in a type of their own, such as [Year]. Any value can also be
+//! taken out as a [Value] without converting it.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+
+use crate::decimal::{DIVISION_SCALE, MAX_PRECISION};
+use crate::{DataType, Decimal, Interval, Numeric, Signed, Text, Time, Type, Unsigned, Value};
+
+/// When a value isn't of the type that a Rust type is stored as
+#[derive(Debug, Clone, PartialEq)]
+pub struct FromValueError {
+    expected: DataType,
+    found: Value,
+}
+
+impl FromValueError {
+    pub fn new(expected: DataType, found: Value) -> Self {
+        FromValueError { expected, found }
+    }
+
+    pub fn expected(&self) -> &DataType {
+        &self.expected
+    }
+
+    pub fn found(&self) -> &Value {
+        &self.found
+    }
+
+    /// Whether the value was NULL
+    pub fn is_null(&self) -> bool {
+        self.found == Type::Optional(None)
+    }
+}
+
+impl Display for FromValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_null() {
+            write!(
+                f,
+                "Expected a value of type {}, but found NULL",
+                self.expected
+            )
+        } else {
+            write!(
+                f,
+                "Expected a value of type {}, but found {}",
+                self.expected, self.found
+            )
+        }
+    }
+}
+
+impl Error for FromValueError {}
+
+pub type Result<T> = std::result::Result<T, FromValueError>;
+
+/// A Rust type that can be stored as a value
+pub trait ToValue {
+    /// The type that the values are stored as
+    fn data_type() -> DataType;
+
+    fn to_value(self) -> Value;
+}
+
+/// A Rust type that can be created from a value
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+/// A `YEAR` value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Year(pub i32);
+
+/// A `BINARY` value, which is a single byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Binary(pub u8);
+
+/// A `VARBINARY` value. It's stored with the largest width, `VARBINARY(65535)`, but can be created
+/// from a value of any width.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BinaryString(pub Vec<u8>);
+
+macro_rules! value_conversion {
+    ($rust:ty, $data_type:expr, $value:ident => $to:expr, $pattern:pat => $from:expr) => {
+        impl ToValue for $rust {
+            fn data_type() -> DataType {
+                $data_type
+            }
+
+            fn to_value(self) -> Value {
+                let $value = self;
+                $to
+            }
+        }
+
+        impl FromValue for $rust {
+            fn from_value(value: Value) -> Result<Self> {
+                match value {
+                    $pattern => Ok($from),
+                    Type::Optional(Some(inner)) => Self::from_value(*inner),
+                    other => Err(FromValueError::new($data_type, other)),
+                }
+            }
+        }
+    };
+}
+
+value_conversion!(i8, DataType::SignedByte, v => Signed::Byte(v).into(),
+    Type::Numeric(Numeric::Signed(Signed::Byte(v))) => v);
+value_conversion!(i16, DataType::SignedShort, v => Signed::Short(v).into(),
+    Type::Numeric(Numeric::Signed(Signed::Short(v))) => v);
+value_conversion!(i32, DataType::SignedInt, v => Signed::Int(v).into(),
+    Type::Numeric(Numeric::Signed(Signed::Int(v))) => v);
+value_conversion!(i64, DataType::SignedLong, v => Signed::Long(v).into(),
+    Type::Numeric(Numeric::Signed(Signed::Long(v))) => v);
+value_conversion!(u8, DataType::UnsignedByte, v => Unsigned::Byte(v).into(),
+    Type::Numeric(Numeric::Unsigned(Unsigned::Byte(v))) => v);
+value_conversion!(u16, DataType::UnsignedShort, v => Unsigned::Short(v).into(),
+    Type::Numeric(Numeric::Unsigned(Unsigned::Short(v))) => v);
+value_conversion!(u32, DataType::UnsignedInt, v => Unsigned::Int(v).into(),
+    Type::Numeric(Numeric::Unsigned(Unsigned::Int(v))) => v);
+value_conversion!(u64, DataType::UnsignedLong, v => Unsigned::Long(v).into(),
+    Type::Numeric(Numeric::Unsigned(Unsigned::Long(v))) => v);
+value_conversion!(f32, DataType::Float, v => Numeric::Float(v).into(),
+    Type::Numeric(Numeric::Float(v)) => v);
+value_conversion!(f64, DataType::Double, v => Numeric::Double(v).into(),
+    Type::Numeric(Numeric::Double(v)) => v);
+// A decimal keeps its own precision and scale as a value, but a Rust type has to be stored as a
+// single type, so decimals are stored with the most digits and the scale used for division
+value_conversion!(Decimal, DataType::Decimal { precision: MAX_PRECISION, scale: DIVISION_SCALE },
+    v => Numeric::Decimal(v).into(),
+    Type::Numeric(Numeric::Decimal(v)) => v);
+value_conversion!(bool, DataType::Boolean, v => Type::Boolean(v),
+    Type::Boolean(v) => v);
+value_conversion!(char, DataType::Char, v => Text::Char(v).into(),
+    Type::Text(Text::Char(v)) => v);
+value_conversion!(String, DataType::String(None), v => Text::String(v, None).into(),
+    Type::Text(Text::String(v, _)) => v);
+value_conversion!(Binary, DataType::Binary, v => Text::Binary(v.0).into(),
+    Type::Text(Text::Binary(v)) => Binary(v));
+value_conversion!(BinaryString, DataType::BinaryString(u16::MAX),
+    v => Text::BinaryString(v.0, u16::MAX).into(),
+    Type::Text(Text::BinaryString(v, _)) => BinaryString(v));
+value_conversion!(Vec<u8>, DataType::Blob, v => Text::Blob(v).into(),
+    Type::Text(Text::Blob(v)) => v);
+value_conversion!(NaiveDate, DataType::Date, v => Time::Date(v).into(),
+    Type::Time(Time::Date(v)) => v);
+value_conversion!(DateTime<Local>, DataType::DateTime, v => Time::DateTime(v).into(),
+    Type::Time(Time::DateTime(v)) => v);
+value_conversion!(DateTime<Utc>, DataType::Timestamp, v => Time::Timestamp(v).into(),
+    Type::Time(Time::Timestamp(v)) => v);
+value_conversion!(Year, DataType::Year, v => Time::Year(v.0).into(),
+    Type::Time(Time::Year(v)) => Year(v));
+value_conversion!(Interval, DataType::Interval, v => Time::Interval(v).into(),
+    Type::Time(Time::Interval(v)) => v);
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn data_type() -> DataType {
+        T::data_type().optional()
+    }
+
+    fn to_value(self) -> Value {
+        Type::Optional(self.map(|value| Box::new(value.to_value())))
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    /// NULL becomes `None`, and every other value is converted into `T`
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Type::Optional(None) => Ok(None),
+            Type::Optional(Some(inner)) => T::from_value(*inner).map(Some),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(i32::from_value((-3i32).to_value()), Ok(-3));
+        assert_eq!(
+            String::from_value("hello".to_string().to_value()),
+            Ok("hello".to_string())
+        );
+        assert_eq!(<Option<u8>>::data_type(), DataType::UnsignedByte.optional());
+        assert_eq!(<Option<u8>>::from_value(None::<u8>.to_value()), Ok(None));
+        assert_eq!(<Option<u8>>::from_value(Some(4u8).to_value()), Ok(Some(4)));
+        assert_eq!(<Option<u8>>::from_value(Type::from(4u8)), Ok(Some(4)));
+        assert_eq!(u8::from_value(Some(4u8).to_value()), Ok(4));
+    }
+
+    #[test]
+    fn wrapped_types() {
+        assert_eq!(Year::from_value(Year(2000).to_value()), Ok(Year(2000)));
+        assert_eq!(Binary::from_value(Binary(7).to_value()), Ok(Binary(7)));
+        let bytes = BinaryString(vec![1, 2, 3]);
+        assert!(BinaryString::data_type().accepts(&bytes.clone().to_value()));
+        assert_eq!(
+            BinaryString::from_value(Text::BinaryString(vec![1, 2, 3], 4).into()),
+            Ok(bytes)
+        );
+        assert!(i32::from_value(Year(2000).to_value()).is_err());
+        assert!(u8::from_value(Binary(7).to_value()).is_err());
+    }
+
+    #[test]
+    fn decimals() {
+        let decimal = Decimal::parse("1.5", 2, 1).unwrap();
+        assert!(Decimal::data_type().accepts(&decimal.to_value()));
+        assert_eq!(Decimal::from_value(decimal.to_value()), Ok(decimal));
+        assert_eq!(
+            Decimal::from_value(Type::from(1u8)).unwrap_err().expected(),
+            &Decimal::data_type()
+        );
+    }
+
+    #[test]
+    fn mismatch() {
+        let error = u64::from_value(Type::from(3u32)).unwrap_err();
+        assert_eq!(error.expected(), &DataType::UnsignedLong);
+        assert_eq!(error.found(), &Type::from(3u32));
+        assert!(!error.is_null());
+        assert!(u64::from_value(Type::Optional(None)).unwrap_err().is_null());
+        assert!(<Option<u64>>::from_value(Type::from("3")).is_err());
+    }
+
+    #[test]
+    fn any_value() {
+        let year: Type = Time::Year(2000).into();
+        assert_eq!(Value::from_value(year.clone()), Ok(year));
+        assert_eq!(
+            <Option<Value>>::from_value(Type::Optional(Some(Box::new(Text::Binary(3).into())))),
+            Ok(Some(Text::Binary(3).into()))
+        );
+    }
+}
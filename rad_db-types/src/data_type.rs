@@ -0,0 +1,311 @@
+//! Descriptions of the types of values that can be stored in a relation
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::decimal::MAX_PRECISION;
+use crate::{Decimal, Numeric, Signed, Text, Time, Type, Unsigned, Value};
+
+/// When a data type couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataTypeParseError(String);
+
+impl Display for DataTypeParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} is not a data type", self.0)
+    }
+}
+
+impl Error for DataTypeParseError {}
+
+/// The type of an attribute. Unlike a [Value], a data type doesn't hold any data, but it does
+/// keep all the parameters of the type, such as the maximum length of a string or the precision
+/// of a decimal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataType {
+    Float,
+    Double,
+    SignedByte,
+    SignedShort,
+    SignedInt,
+    SignedLong,
+    UnsignedByte,
+    UnsignedShort,
+    UnsignedInt,
+    UnsignedLong,
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    Char,
+    /// A string, with an optional maximum length in bytes
+    String(Option<u16>),
+    Binary,
+    /// A string of bytes, with a maximum length
+    BinaryString(u16),
+    Blob,
+    Date,
+    DateTime,
+    Timestamp,
+    Year,
+    Interval,
+    Boolean,
+    /// A nullable version of the inner type
+    Optional(Box<DataType>),
+}
+
+impl DataType {
+    /// Creates the nullable version of this type
+    pub fn optional(self) -> Self {
+        match self {
+            DataType::Optional(_) => self,
+            other => DataType::Optional(Box::new(other)),
+        }
+    }
+
+    /// Whether values of this type can be NULL
+    pub fn is_nullable(&self) -> bool {
+        matches!(self, DataType::Optional(_))
+    }
+
+    /// Gets the type of a value. A NULL value has no known type, so `None` is returned for it.
+    pub fn of(value: &Value) -> Option<Self> {
+        let ret = match value {
+            Type::Numeric(n) => match n {
+                Numeric::Float(_) => DataType::Float,
+                Numeric::Double(_) => DataType::Double,
+                Numeric::Signed(Signed::Byte(_)) => DataType::SignedByte,
+                Numeric::Signed(Signed::Short(_)) => DataType::SignedShort,
+                Numeric::Signed(Signed::Int(_)) => DataType::SignedInt,
+                Numeric::Signed(Signed::Long(_)) => DataType::SignedLong,
+                Numeric::Unsigned(Unsigned::Byte(_)) => DataType::UnsignedByte,
+                Numeric::Unsigned(Unsigned::Short(_)) => DataType::UnsignedShort,
+                Numeric::Unsigned(Unsigned::Int(_)) => DataType::UnsignedInt,
+                Numeric::Unsigned(Unsigned::Long(_)) => DataType::UnsignedLong,
+                Numeric::Decimal(d) => DataType::Decimal {
+                    precision: d.precision(),
+                    scale: d.scale(),
+                },
+            },
+            Type::Text(t) => match t {
+                Text::Char(_) => DataType::Char,
+                Text::String(_, max) => DataType::String(*max),
+                Text::Binary(_) => DataType::Binary,
+                Text::BinaryString(_, width) => DataType::BinaryString(*width),
+                Text::Blob(_) => DataType::Blob,
+            },
+            Type::Time(t) => match t {
+                Time::Date(_) => DataType::Date,
+                Time::DateTime(_) => DataType::DateTime,
+                Time::Timestamp(_) => DataType::Timestamp,
+                Time::Year(_) => DataType::Year,
+                Time::Interval(_) => DataType::Interval,
+            },
+            Type::Boolean(_) => DataType::Boolean,
+            Type::Optional(None) => return None,
+            Type::Optional(Some(inner)) => DataType::of(inner)?.optional(),
+        };
+        Some(ret)
+    }
+
+    /// Checks whether a value can be stored as this type. Length limits are checked against the
+    /// contents of the value, and decimals are accepted if they fit in the precision and scale.
+    /// Nullable types also accept non-null values of their inner type.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (DataType::Optional(_), Type::Optional(None)) => true,
+            (DataType::Optional(inner), Type::Optional(Some(value))) => inner.accepts(value),
+            (DataType::Optional(inner), value) => inner.accepts(value),
+            (_, Type::Optional(_)) => false,
+            (DataType::Decimal { precision, scale }, Type::Numeric(Numeric::Decimal(d))) => {
+                d.rescale(*precision, *scale).is_ok()
+            }
+            (DataType::String(Some(max)), Type::Text(Text::String(s, _))) => {
+                s.len() <= *max as usize
+            }
+            (DataType::String(None), Type::Text(Text::String(..))) => true,
+            (DataType::BinaryString(width), Type::Text(Text::BinaryString(bytes, _))) => {
+                bytes.len() <= *width as usize
+            }
+            (data_type, value) => match DataType::of(value) {
+                Some(value_type) => &value_type == data_type,
+                None => false,
+            },
+        }
+    }
+}
+
+impl From<Decimal> for DataType {
+    fn from(d: Decimal) -> Self {
+        DataType::Decimal {
+            precision: d.precision(),
+            scale: d.scale(),
+        }
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Float => write!(f, "FLOAT"),
+            DataType::Double => write!(f, "DOUBLE"),
+            DataType::SignedByte => write!(f, "TINYINT"),
+            DataType::SignedShort => write!(f, "SMALLINT"),
+            DataType::SignedInt => write!(f, "INT"),
+            DataType::SignedLong => write!(f, "BIGINT"),
+            DataType::UnsignedByte => write!(f, "TINYINT UNSIGNED"),
+            DataType::UnsignedShort => write!(f, "SMALLINT UNSIGNED"),
+            DataType::UnsignedInt => write!(f, "INT UNSIGNED"),
+            DataType::UnsignedLong => write!(f, "BIGINT UNSIGNED"),
+            DataType::Decimal { precision, scale } => {
+                write!(f, "DECIMAL({}, {})", precision, scale)
+            }
+            DataType::Char => write!(f, "CHAR"),
+            DataType::String(None) => write!(f, "TEXT"),
+            DataType::String(Some(max)) => write!(f, "VARCHAR({})", max),
+            DataType::Binary => write!(f, "BINARY"),
+            DataType::BinaryString(width) => write!(f, "VARBINARY({})", width),
+            DataType::Blob => write!(f, "BLOB"),
+            DataType::Date => write!(f, "DATE"),
+            DataType::DateTime => write!(f, "DATETIME"),
+            DataType::Timestamp => write!(f, "TIMESTAMP"),
+            DataType::Year => write!(f, "YEAR"),
+            DataType::Interval => write!(f, "INTERVAL"),
+            DataType::Boolean => write!(f, "BOOLEAN"),
+            DataType::Optional(inner) => write!(f, "{} NULL", inner),
+        }
+    }
+}
+
+impl FromStr for DataType {
+    type Err = DataTypeParseError;
+
+    /// Parses a data type as it's displayed, such as `VARCHAR(16) NULL` or `DECIMAL(5, 2)`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || DataTypeParseError(s.to_string());
+        let upper = s.trim().to_ascii_uppercase();
+        if let Some(inner) = upper.strip_suffix(" NULL") {
+            return Ok(inner.parse::<DataType>()?.optional());
+        }
+        let (name, parameters) = match upper.find('(') {
+            Some(open) => {
+                let parameters = upper[open + 1..].strip_suffix(')').ok_or_else(error)?;
+                let parameters = parameters
+                    .split(',')
+                    .map(|parameter| parameter.trim().parse::<u16>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| error())?;
+                (upper[..open].trim(), parameters)
+            }
+            None => (upper.as_str(), vec![]),
+        };
+        let ret = match (name, parameters.as_slice()) {
+            ("FLOAT", []) => DataType::Float,
+            ("DOUBLE", []) => DataType::Double,
+            ("TINYINT", []) => DataType::SignedByte,
+            ("SMALLINT", []) => DataType::SignedShort,
+            ("INT", []) => DataType::SignedInt,
+            ("BIGINT", []) => DataType::SignedLong,
+            ("TINYINT UNSIGNED", []) => DataType::UnsignedByte,
+            ("SMALLINT UNSIGNED", []) => DataType::UnsignedShort,
+            ("INT UNSIGNED", []) => DataType::UnsignedInt,
+            ("BIGINT UNSIGNED", []) => DataType::UnsignedLong,
+            ("DECIMAL", [precision, scale])
+                if *precision >= 1 && *precision <= MAX_PRECISION as u16 && scale <= precision =>
+            {
+                DataType::Decimal {
+                    precision: *precision as u8,
+                    scale: *scale as u8,
+                }
+            }
+            ("CHAR", []) => DataType::Char,
+            ("TEXT", []) => DataType::String(None),
+            ("VARCHAR", [max]) => DataType::String(Some(*max)),
+            ("BINARY", []) => DataType::Binary,
+            ("VARBINARY", [width]) => DataType::BinaryString(*width),
+            ("BLOB", []) => DataType::Blob,
+            ("DATE", []) => DataType::Date,
+            ("DATETIME", []) => DataType::DateTime,
+            ("TIMESTAMP", []) => DataType::Timestamp,
+            ("YEAR", []) => DataType::Year,
+            ("INTERVAL", []) => DataType::Interval,
+            ("BOOLEAN", []) => DataType::Boolean,
+            _ => return Err(error()),
+        };
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts() {
+        assert!(DataType::UnsignedLong.accepts(&Type::from(3u64)));
+        assert!(!DataType::UnsignedLong.accepts(&Type::from(3u8)));
+        assert!(!DataType::UnsignedLong.accepts(&Type::Optional(None)));
+
+        let varchar = DataType::String(Some(5));
+        assert!(varchar.accepts(&Type::from("hello")));
+        assert!(!varchar.accepts(&Type::from("hello world")));
+
+        let decimal = DataType::Decimal {
+            precision: 5,
+            scale: 2,
+        };
+        assert!(decimal.accepts(&Decimal::parse("1.5", 2, 1).unwrap().into()));
+        assert!(!decimal.accepts(&Decimal::parse("1.555", 4, 3).unwrap().into()));
+
+        let nullable = DataType::Boolean.optional();
+        assert!(nullable.accepts(&Type::Optional(None)));
+        assert!(nullable.accepts(&Type::Optional(Some(Box::new(Type::Boolean(true))))));
+        assert!(nullable.accepts(&Type::Boolean(false)));
+        assert!(!nullable.accepts(&Type::from(1u64)));
+    }
+
+    #[test]
+    fn of() {
+        assert_eq!(DataType::of(&Type::from(3i32)), Some(DataType::SignedInt));
+        assert_eq!(DataType::of(&Type::Optional(None)), None);
+        assert_eq!(
+            DataType::of(&Type::Optional(Some(Box::new(Text::Char('c').into())))),
+            Some(DataType::Char.optional())
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let types = vec![
+            DataType::UnsignedLong,
+            DataType::SignedByte,
+            DataType::Decimal {
+                precision: 5,
+                scale: 2,
+            },
+            DataType::String(None),
+            DataType::String(Some(16)).optional(),
+            DataType::BinaryString(4),
+            DataType::Interval.optional(),
+        ];
+        for data_type in types {
+            assert_eq!(data_type.to_string().parse(), Ok(data_type));
+        }
+        assert_eq!("varchar(3)".parse(), Ok(DataType::String(Some(3))));
+        assert!("VARCHAR".parse::<DataType>().is_err());
+        assert!("DECIMAL(300, 2)".parse::<DataType>().is_err());
+        assert!("DECIMAL(50, 2)".parse::<DataType>().is_err());
+        assert!("DECIMAL(2, 5)".parse::<DataType>().is_err());
+        assert!("DECIMAL(0, 0)".parse::<DataType>().is_err());
+        assert_eq!(
+            "DECIMAL(38, 38)".parse::<DataType>(),
+            Ok(DataType::Decimal {
+                precision: 38,
+                scale: 38
+            })
+        );
+        assert!("NUMBER".parse::<DataType>().is_err());
+    }
+}
@@ -0,0 +1,420 @@
+//! Exact fixed-point decimal numbers
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// The largest amount of digits a decimal can have
+pub const MAX_PRECISION: u8 = 38;
+
+/// The least amount of fractional digits kept when dividing decimals
+pub const DIVISION_SCALE: u8 = 6;
+
+/// When a decimal couldn't be created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecimalError {
+    /// The precision is zero or greater than [MAX_PRECISION], or the scale is greater than the precision
+    InvalidDefinition { precision: u8, scale: u8 },
+    /// The value has more digits than the precision allows
+    Overflow,
+    /// The value has more fractional digits than the scale allows
+    TooManyFractionalDigits,
+    /// The text isn't a decimal number
+    InvalidText(String),
+    /// A decimal was divided by zero
+    DivisionByZero,
+}
+
+impl Display for DecimalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecimalError::InvalidDefinition { precision, scale } => {
+                write!(
+                    f,
+                    "Invalid decimal definition DECIMAL({}, {})",
+                    precision, scale
+                )
+            }
+            DecimalError::Overflow => write!(f, "Value doesn't fit in the decimal's precision"),
+            DecimalError::TooManyFractionalDigits => {
+                write!(
+                    f,
+                    "Value has more fractional digits than the decimal's scale"
+                )
+            }
+            DecimalError::InvalidText(text) => write!(f, "{:?} is not a decimal number", text),
+            DecimalError::DivisionByZero => write!(f, "Division by zero"),
+        }
+    }
+}
+
+impl Error for DecimalError {}
+
+/// An exact number with a declared precision (the total amount of digits) and scale (the amount of
+/// digits after the decimal point). The value is stored unscaled, so `12.34` as a `DECIMAL(5, 2)`
+/// is stored as `1234`.
+///
+/// Two decimals are equal if they represent the same number, regardless of their precision and
+/// scale.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    unscaled: i128,
+    precision: u8,
+    scale: u8,
+}
+
+fn power_of_ten(exponent: u8) -> i128 {
+    10i128.pow(exponent as u32)
+}
+
+impl Decimal {
+    /// Creates a decimal from its unscaled value
+    pub fn new(unscaled: i128, precision: u8, scale: u8) -> Result<Self, DecimalError> {
+        if precision == 0 || precision > MAX_PRECISION || scale > precision {
+            return Err(DecimalError::InvalidDefinition { precision, scale });
+        }
+        if unscaled.unsigned_abs() >= power_of_ten(precision) as u128 {
+            return Err(DecimalError::Overflow);
+        }
+        Ok(Decimal {
+            unscaled,
+            precision,
+            scale,
+        })
+    }
+
+    /// Creates a zero valued decimal with a precision and scale
+    pub fn zero(precision: u8, scale: u8) -> Result<Self, DecimalError> {
+        Self::new(0, precision, scale)
+    }
+
+    /// Parses text as a decimal with a declared precision and scale. Fractional digits past the
+    /// scale are rejected rather than rounded.
+    pub fn parse(text: &str, precision: u8, scale: u8) -> Result<Self, DecimalError> {
+        let (unscaled, text_scale) = parse_unscaled(text)?;
+        Decimal::new(unscaled, MAX_PRECISION, text_scale)
+            .map_err(|_| DecimalError::Overflow)?
+            .rescale(precision, scale)
+    }
+
+    /// Converts the decimal to a different precision and scale without losing any digits
+    pub fn rescale(&self, precision: u8, scale: u8) -> Result<Self, DecimalError> {
+        let unscaled = if scale >= self.scale {
+            self.unscaled
+                .checked_mul(power_of_ten(scale - self.scale))
+                .ok_or(DecimalError::Overflow)?
+        } else {
+            let divisor = power_of_ten(self.scale - scale);
+            if self.unscaled % divisor != 0 {
+                return Err(DecimalError::TooManyFractionalDigits);
+            }
+            self.unscaled / divisor
+        };
+        Decimal::new(unscaled, precision, scale)
+    }
+
+    /// The value without the decimal point
+    pub fn unscaled(&self) -> i128 {
+        self.unscaled
+    }
+
+    /// The total amount of digits
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The amount of digits after the decimal point
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// The closest floating point number to this decimal
+    pub fn to_f64(&self) -> f64 {
+        self.unscaled as f64 / power_of_ten(self.scale) as f64
+    }
+
+    /// Adds two decimals. The result has the larger scale of the two, and one more integer digit
+    /// than the larger of the two.
+    pub fn checked_add(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let scale = self.scale.max(other.scale);
+        let integer_digits = (self.precision - self.scale).max(other.precision - other.scale) + 1;
+        let left = self.rescale(MAX_PRECISION, scale)?;
+        let right = other.rescale(MAX_PRECISION, scale)?;
+        let unscaled = left
+            .unscaled
+            .checked_add(right.unscaled)
+            .ok_or(DecimalError::Overflow)?;
+        Decimal::new(unscaled, (integer_digits + scale).min(MAX_PRECISION), scale)
+    }
+
+    /// Subtracts a decimal from this one, with the same precision and scale as [checked_add]
+    ///
+    /// [checked_add]: Decimal::checked_add
+    pub fn checked_sub(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let negated = Decimal {
+            unscaled: -other.unscaled,
+            ..*other
+        };
+        self.checked_add(&negated)
+    }
+
+    /// Multiplies two decimals. The precision and scale of the result are the sums of the
+    /// precisions and scales of the two.
+    pub fn checked_mul(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let scale = self.scale + other.scale;
+        if scale > MAX_PRECISION {
+            return Err(DecimalError::TooManyFractionalDigits);
+        }
+        let unscaled = self
+            .unscaled
+            .checked_mul(other.unscaled)
+            .ok_or(DecimalError::Overflow)?;
+        let precision = (self.precision + other.precision).min(MAX_PRECISION);
+        Decimal::new(unscaled, precision, scale)
+    }
+
+    /// Divides this decimal by another. The result keeps at least [DIVISION_SCALE] fractional
+    /// digits, and any digits past that are truncated.
+    pub fn checked_div(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        if other.unscaled == 0 {
+            return Err(DecimalError::DivisionByZero);
+        }
+        let scale = self.scale.max(other.scale).max(DIVISION_SCALE);
+        let shift = (scale - self.scale) as u32 + other.scale as u32;
+        let dividend = 10i128
+            .checked_pow(shift)
+            .and_then(|power| self.unscaled.checked_mul(power))
+            .ok_or(DecimalError::Overflow)?;
+        Decimal::new(dividend / other.unscaled, MAX_PRECISION, scale)
+    }
+
+    /// The unscaled value and scale with all trailing fractional zeroes removed, which is the same
+    /// for all decimals representing the same number
+    fn normalized(&self) -> (i128, u8) {
+        let mut unscaled = self.unscaled;
+        let mut scale = self.scale;
+        while scale > 0 && unscaled % 10 == 0 {
+            unscaled /= 10;
+            scale -= 1;
+        }
+        (unscaled, scale)
+    }
+}
+
+/// Parses text into an unscaled value, and the amount of digits after the decimal point
+fn parse_unscaled(text: &str) -> Result<(i128, u8), DecimalError> {
+    let invalid = || DecimalError::InvalidText(text.to_string());
+    let trimmed = text.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (integer, fraction) = match digits.find('.') {
+        Some(index) => (&digits[..index], &digits[index + 1..]),
+        None => (digits, ""),
+    };
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+    if !integer
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fraction.len() > MAX_PRECISION as usize {
+        return Err(DecimalError::TooManyFractionalDigits);
+    }
+
+    let mut unscaled: i128 = 0;
+    for c in integer.chars().chain(fraction.chars()) {
+        unscaled = unscaled
+            .checked_mul(10)
+            .and_then(|u| u.checked_add(c.to_digit(10).unwrap() as i128))
+            .ok_or(DecimalError::Overflow)?;
+    }
+    if negative {
+        unscaled = -unscaled;
+    }
+    Ok((unscaled, fraction.len() as u8))
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    /// Parses a decimal, using the smallest precision and scale that can hold the text exactly
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (unscaled, scale) = parse_unscaled(s)?;
+        let digits = unscaled.unsigned_abs().to_string().len() as u8;
+        Decimal::new(unscaled, digits.max(scale).max(1), scale)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(i: i64) -> Self {
+        Decimal {
+            unscaled: i as i128,
+            precision: 19,
+            scale: 0,
+        }
+    }
+}
+
+impl From<u64> for Decimal {
+    fn from(u: u64) -> Self {
+        Decimal {
+            unscaled: u as i128,
+            precision: 20,
+            scale: 0,
+        }
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.unscaled < 0 { "-" } else { "" };
+        let digits = self.unscaled.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = padded.split_at(padded.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state)
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Integer parts are compared first so that nothing has to be scaled up past i128
+        let self_divisor = power_of_ten(self.scale);
+        let other_divisor = power_of_ten(other.scale);
+        let integer_order = (self.unscaled / self_divisor).cmp(&(other.unscaled / other_divisor));
+        if integer_order != Ordering::Equal {
+            return integer_order;
+        }
+        let scale = self.scale.max(other.scale);
+        let self_fraction = (self.unscaled % self_divisor) * power_of_ten(scale - self.scale);
+        let other_fraction = (other.unscaled % other_divisor) * power_of_ten(scale - other.scale);
+        self_fraction.cmp(&other_fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        let decimal = Decimal::parse("12.3", 5, 2).unwrap();
+        assert_eq!(decimal.unscaled(), 1230);
+        assert_eq!(decimal.to_string(), "12.30");
+        assert_eq!(Decimal::parse("-0.05", 3, 2).unwrap().to_string(), "-0.05");
+        assert_eq!(Decimal::parse("7", 3, 0).unwrap().to_string(), "7");
+        assert_eq!(Decimal::parse(".5", 2, 1).unwrap().to_string(), "0.5");
+
+        assert_eq!(
+            Decimal::parse("1234.5", 5, 2).unwrap_err(),
+            DecimalError::Overflow
+        );
+        assert_eq!(
+            Decimal::parse("1.234", 5, 2).unwrap_err(),
+            DecimalError::TooManyFractionalDigits
+        );
+        assert!(Decimal::parse("1.2.3", 5, 2).is_err());
+        assert!(Decimal::parse("", 5, 2).is_err());
+        assert!(Decimal::new(0, 3, 4).is_err());
+        assert_eq!(
+            Decimal::new(i128::MIN, MAX_PRECISION, 0).unwrap_err(),
+            DecimalError::Overflow
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a: Decimal = "12.5".parse().unwrap();
+        let b: Decimal = "0.25".parse().unwrap();
+        assert_eq!(a.checked_add(&b).unwrap().to_string(), "12.75");
+        assert_eq!(b.checked_sub(&a).unwrap().to_string(), "-12.25");
+        assert_eq!(a.checked_mul(&b).unwrap().to_string(), "3.125");
+        assert_eq!(a.checked_div(&b).unwrap(), Decimal::from(50i64));
+        assert_eq!(
+            Decimal::from(1i64)
+                .checked_div(&Decimal::from(3i64))
+                .unwrap()
+                .to_string(),
+            "0.333333"
+        );
+        assert_eq!(
+            a.checked_div(&Decimal::zero(1, 0).unwrap()).unwrap_err(),
+            DecimalError::DivisionByZero
+        );
+        let max = Decimal::new(power_of_ten(38) - 1, 38, 0).unwrap();
+        assert_eq!(max.checked_add(&max).unwrap_err(), DecimalError::Overflow);
+    }
+
+    #[test]
+    fn from_str() {
+        let decimal: Decimal = "-123.450".parse().unwrap();
+        assert_eq!(decimal.precision(), 6);
+        assert_eq!(decimal.scale(), 3);
+        assert_eq!(decimal.to_string(), "-123.450");
+    }
+
+    #[test]
+    fn equality_ignores_scale() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let short = Decimal::parse("1.5", 2, 1).unwrap();
+        let long = Decimal::parse("1.50", 10, 4).unwrap();
+        assert_eq!(short, long);
+
+        let hash = |d: &Decimal| {
+            let mut hasher = DefaultHasher::new();
+            d.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&short), hash(&long));
+        assert_ne!(short, Decimal::parse("1.51", 3, 2).unwrap());
+    }
+
+    #[test]
+    fn ordering() {
+        let values: Vec<Decimal> = vec!["-10.5", "-10.25", "-0.001", "0", "0.01", "0.1", "99.99"]
+            .into_iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        for (index, value) in values.iter().enumerate() {
+            for other in &values[index + 1..] {
+                assert!(value < other, "{} < {}", value, other);
+            }
+        }
+        let max = Decimal::new(power_of_ten(38) - 1, 38, 0).unwrap();
+        let tiny = Decimal::new(1, 38, 38).unwrap();
+        assert!(tiny < max);
+        assert_eq!(Decimal::from(3i64), Decimal::parse("3.000", 4, 3).unwrap());
+    }
+}
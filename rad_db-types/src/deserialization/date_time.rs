@@ -0,0 +1,365 @@
+//! Parsing and formatting of date and time literals.
+//!
+//! By default, dates are parsed as `YYYY-MM-DD`, and date times and timestamps are parsed as
+//! ISO-8601 or RFC 3339, with either a `T` or a space between the date and the time. Additional
+//! formats can be added to a [DateTimeParser] using the `strftime` syntax of [chrono::format].
+//!
+//! Text that has an explicit offset (such as `+02:00`, `Z` or ` UTC`) is always converted from that
+//! offset. Text without an offset is handled according to the [ZonePolicy] of the parser.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use chrono::format::{parse, Parsed, StrftimeItems};
+use chrono::{
+    DateTime, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone,
+    Utc,
+};
+
+/// The formats of dates that are always accepted
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d"];
+
+/// The formats of date times with offsets that are always accepted, after RFC 3339
+const OFFSET_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f %:z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f %z",
+];
+
+/// The formats of date times without offsets that are always accepted
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Suffixes that are treated as a UTC offset
+const UTC_SUFFIXES: &[&str] = &[" UTC", "Z", "z"];
+
+/// How date times without an explicit offset are interpreted
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ZonePolicy {
+    /// The text is a time in the local time zone
+    #[default]
+    AssumeLocal,
+    /// The text is a time in UTC
+    AssumeUtc,
+    /// The text must have an offset
+    RequireOffset,
+}
+
+/// Why a date or time literal couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateTimeParseError {
+    /// The text doesn't match any of the accepted formats
+    UnrecognizedFormat {
+        text: String,
+        expected: &'static str,
+    },
+    /// The text matched a format, but a field of it is out of range, such as the 30th of February
+    OutOfRange { text: String },
+    /// The text has no offset, and the parser requires one
+    MissingOffset { text: String },
+    /// The local time is skipped by a daylight savings change
+    NonexistentLocalTime { text: String },
+    /// The local time happens twice because of a daylight savings change
+    AmbiguousLocalTime { text: String },
+}
+
+impl Display for DateTimeParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateTimeParseError::UnrecognizedFormat { text, expected } => {
+                write!(f, "{:?} is not a valid {}", text, expected)
+            }
+            DateTimeParseError::OutOfRange { text } => {
+                write!(f, "{:?} has a field that is out of range", text)
+            }
+            DateTimeParseError::MissingOffset { text } => {
+                write!(f, "{:?} doesn't have a time zone offset", text)
+            }
+            DateTimeParseError::NonexistentLocalTime { text } => {
+                write!(f, "{:?} doesn't exist in the local time zone", text)
+            }
+            DateTimeParseError::AmbiguousLocalTime { text } => {
+                write!(f, "{:?} is ambiguous in the local time zone", text)
+            }
+        }
+    }
+}
+
+impl Error for DateTimeParseError {}
+
+pub type Result<T> = std::result::Result<T, DateTimeParseError>;
+
+/// The result of trying a single format
+enum Attempt<T> {
+    Parsed(T),
+    /// The text matched the format, but isn't a valid date
+    OutOfRange,
+    NoMatch,
+}
+
+fn attempt<T, F: FnOnce(&Parsed) -> chrono::ParseResult<T>>(
+    text: &str,
+    format: &str,
+    convert: F,
+) -> Attempt<T> {
+    let mut parsed = Parsed::new();
+    if parse(&mut parsed, text, StrftimeItems::new(format)).is_err() {
+        return Attempt::NoMatch;
+    }
+    match convert(&parsed) {
+        Ok(ret) => Attempt::Parsed(ret),
+        Err(_) => Attempt::OutOfRange,
+    }
+}
+
+/// Parses date and time literals, with configurable formats and time zone handling
+#[derive(Debug, Clone, Default)]
+pub struct DateTimeParser {
+    date_formats: Vec<String>,
+    date_time_formats: Vec<String>,
+    zone_policy: ZonePolicy,
+}
+
+impl DateTimeParser {
+    /// Creates a parser that only accepts the default formats, and assumes times without an offset
+    /// are local
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a format that dates are accepted in, which is tried after the default formats
+    pub fn with_date_format<S: ToString>(mut self, format: S) -> Self {
+        self.date_formats.push(format.to_string());
+        self
+    }
+
+    /// Adds a format that date times and timestamps are accepted in, which is tried after the
+    /// default formats. The format may or may not include an offset.
+    pub fn with_date_time_format<S: ToString>(mut self, format: S) -> Self {
+        self.date_time_formats.push(format.to_string());
+        self
+    }
+
+    /// Sets how date times without an offset are interpreted
+    pub fn with_zone_policy(mut self, zone_policy: ZonePolicy) -> Self {
+        self.zone_policy = zone_policy;
+        self
+    }
+
+    pub fn zone_policy(&self) -> ZonePolicy {
+        self.zone_policy
+    }
+
+    /// Parses a date, which doesn't have a time zone
+    pub fn parse_date(&self, text: &str) -> Result<NaiveDate> {
+        let trimmed = text.trim();
+        let formats = DATE_FORMATS
+            .iter()
+            .copied()
+            .chain(self.date_formats.iter().map(String::as_str));
+        let mut out_of_range = false;
+        for format in formats {
+            match attempt(trimmed, format, Parsed::to_naive_date) {
+                Attempt::Parsed(date) => return Ok(date),
+                Attempt::OutOfRange => out_of_range = true,
+                Attempt::NoMatch => {}
+            }
+        }
+        if out_of_range {
+            Err(DateTimeParseError::OutOfRange {
+                text: text.to_string(),
+            })
+        } else {
+            Err(DateTimeParseError::UnrecognizedFormat {
+                text: text.to_string(),
+                expected: "date",
+            })
+        }
+    }
+
+    /// Parses a date time, and converts it to the local time zone
+    pub fn parse_date_time(&self, text: &str) -> Result<DateTime<Local>> {
+        self.parse_instant(text)
+            .map(|date_time| date_time.with_timezone(&Local))
+    }
+
+    /// Parses a timestamp, and converts it to UTC
+    pub fn parse_timestamp(&self, text: &str) -> Result<DateTime<Utc>> {
+        self.parse_instant(text)
+            .map(|date_time| date_time.with_timezone(&Utc))
+    }
+
+    fn parse_instant(&self, text: &str) -> Result<DateTime<FixedOffset>> {
+        let trimmed = text.trim();
+        if let Ok(date_time) = DateTime::parse_from_rfc3339(trimmed) {
+            return Ok(date_time);
+        }
+        let with_utc_offset = UTC_SUFFIXES
+            .iter()
+            .find_map(|suffix| trimmed.strip_suffix(suffix))
+            .map(|naive| format!("{}+00:00", naive.trim_end()));
+        let offset_text = with_utc_offset.as_deref().unwrap_or(trimmed);
+
+        let mut out_of_range = false;
+        let custom = self.date_time_formats.iter().map(String::as_str);
+        for format in OFFSET_FORMATS.iter().copied().chain(custom.clone()) {
+            match attempt(offset_text, format, Parsed::to_datetime) {
+                Attempt::Parsed(date_time) => return Ok(date_time),
+                Attempt::OutOfRange => out_of_range = true,
+                Attempt::NoMatch => {}
+            }
+        }
+        for format in NAIVE_FORMATS.iter().copied().chain(custom) {
+            let to_naive = |parsed: &Parsed| {
+                let date = parsed.to_naive_date()?;
+                let time = parsed.to_naive_time()?;
+                Ok(NaiveDateTime::new(date, time))
+            };
+            match attempt(trimmed, format, to_naive) {
+                Attempt::Parsed(naive) => return self.apply_zone_policy(text, naive),
+                Attempt::OutOfRange => out_of_range = true,
+                Attempt::NoMatch => {}
+            }
+        }
+        if out_of_range {
+            Err(DateTimeParseError::OutOfRange {
+                text: text.to_string(),
+            })
+        } else {
+            Err(DateTimeParseError::UnrecognizedFormat {
+                text: text.to_string(),
+                expected: "date time",
+            })
+        }
+    }
+
+    fn apply_zone_policy(&self, text: &str, naive: NaiveDateTime) -> Result<DateTime<FixedOffset>> {
+        match self.zone_policy {
+            ZonePolicy::AssumeLocal => {
+                let local = single(text, Local.from_local_datetime(&naive))?;
+                Ok(local.fixed_offset())
+            }
+            ZonePolicy::AssumeUtc => Ok(Utc.from_utc_datetime(&naive).fixed_offset()),
+            ZonePolicy::RequireOffset => Err(DateTimeParseError::MissingOffset {
+                text: text.to_string(),
+            }),
+        }
+    }
+}
+
+fn single<T>(text: &str, result: LocalResult<T>) -> Result<T> {
+    match result {
+        LocalResult::Single(ret) => Ok(ret),
+        LocalResult::None => Err(DateTimeParseError::NonexistentLocalTime {
+            text: text.to_string(),
+        }),
+        LocalResult::Ambiguous(..) => Err(DateTimeParseError::AmbiguousLocalTime {
+            text: text.to_string(),
+        }),
+    }
+}
+
+/// Formats a date as `YYYY-MM-DD`
+pub fn format_date(date: &NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Formats a date time as RFC 3339, with its offset
+pub fn format_date_time<Tz: TimeZone>(date_time: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    date_time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        let parser = DateTimeParser::new();
+        let date = parser.parse_date("2021-03-07").unwrap();
+        assert_eq!(date, NaiveDate::from_ymd_opt(2021, 3, 7).unwrap());
+        assert_eq!(format_date(&date), "2021-03-07");
+        assert_eq!(
+            parser.parse_date("2021-02-30"),
+            Err(DateTimeParseError::OutOfRange {
+                text: "2021-02-30".to_string()
+            })
+        );
+        assert!(matches!(
+            parser.parse_date("07/03/2021"),
+            Err(DateTimeParseError::UnrecognizedFormat { .. })
+        ));
+
+        let parser = parser.with_date_format("%d/%m/%Y");
+        assert_eq!(parser.parse_date("07/03/2021").unwrap(), date);
+    }
+
+    #[test]
+    fn offsets() {
+        let parser = DateTimeParser::new();
+        let expected = Utc.with_ymd_and_hms(2021, 3, 7, 10, 30, 0).unwrap();
+        for text in &[
+            "2021-03-07T12:30:00+02:00",
+            "2021-03-07 12:30:00 +02:00",
+            "2021-03-07T10:30:00Z",
+            "2021-03-07 10:30:00 UTC",
+            "2021-03-07T12:30:00.000+0200",
+        ] {
+            assert_eq!(parser.parse_timestamp(text), Ok(expected), "{}", text);
+        }
+        let date_time = parser.parse_date_time("2021-03-07T12:30:00+02:00").unwrap();
+        assert_eq!(date_time.with_timezone(&Utc), expected);
+        assert_eq!(
+            parser.parse_timestamp(&format_date_time(&expected)),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn zone_policy() {
+        let naive = "2021-03-07 10:30:00.25";
+        let utc = DateTimeParser::new().with_zone_policy(ZonePolicy::AssumeUtc);
+        let expected = Utc.with_ymd_and_hms(2021, 3, 7, 10, 30, 0).unwrap()
+            + chrono::Duration::milliseconds(250);
+        assert_eq!(utc.parse_timestamp(naive), Ok(expected));
+
+        let local = DateTimeParser::new();
+        assert_eq!(
+            local.parse_date_time(naive).unwrap().naive_local(),
+            expected.naive_utc()
+        );
+
+        let strict = DateTimeParser::new().with_zone_policy(ZonePolicy::RequireOffset);
+        assert_eq!(
+            strict.parse_timestamp(naive),
+            Err(DateTimeParseError::MissingOffset {
+                text: naive.to_string()
+            })
+        );
+        assert!(strict.parse_timestamp("2021-03-07T10:30:00Z").is_ok());
+    }
+
+    #[test]
+    fn custom_date_time_format() {
+        let parser = DateTimeParser::new()
+            .with_zone_policy(ZonePolicy::AssumeUtc)
+            .with_date_time_format("%d/%m/%Y %H.%M");
+        assert_eq!(
+            parser.parse_timestamp("07/03/2021 10.30"),
+            Ok(Utc.with_ymd_and_hms(2021, 3, 7, 10, 30, 0).unwrap())
+        );
+        assert!(matches!(
+            parser.parse_timestamp("yesterday"),
+            Err(DateTimeParseError::UnrecognizedFormat { .. })
+        ));
+    }
+}
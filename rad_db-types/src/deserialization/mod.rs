@@ -0,0 +1,453 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::data_type::DataType;
+use crate::deserialization::date_time::DateTimeParser;
+use crate::serialization::HEX_PREFIX;
+use crate::{Decimal, Numeric, Signed, Text, Time, Type, Unsigned};
+
+pub mod date_time;
+
+/// Why a well formed value doesn't fit in its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidValue {
+    /// The value is longer than the type allows
+    TooLong { length: usize, max: usize },
+    /// A single byte was expected, but there were a different number of bytes
+    NotSingleByte { length: usize },
+    /// Bytes were not written as `0x` followed by pairs of hex digits
+    InvalidHex,
+}
+
+impl Display for InvalidValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidValue::TooLong { length, max } => {
+                write!(f, "length {} is longer than the maximum of {}", length, max)
+            }
+            InvalidValue::NotSingleByte { length } => {
+                write!(f, "expected a single byte, but found {}", length)
+            }
+            InvalidValue::InvalidHex => write!(f, "bytes must be written as {}<hex>", HEX_PREFIX),
+        }
+    }
+}
+
+impl Error for InvalidValue {}
+
+/// Why a tuple couldn't be parsed
+#[derive(Debug)]
+pub enum ParseTupleFailure {
+    /// A value couldn't be parsed as the type of its column
+    Value {
+        /// The index of the column
+        column: usize,
+        expected: DataType,
+        /// The text of the value, after quotes and escapes were removed
+        text: String,
+        /// The byte offset of the start of the value within the tuple
+        offset: usize,
+        cause: Box<dyn Error + Send + Sync>,
+    },
+    /// The tuple ended in the middle of an escape sequence
+    UnterminatedEscape { offset: usize },
+    /// The tuple doesn't have one value for every type
+    WrongArity { expected: usize, found: usize },
+}
+
+impl ParseTupleFailure {
+    /// The column that couldn't be parsed, if the failure was caused by a single value
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            ParseTupleFailure::Value { column, .. } => Some(*column),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ParseTupleFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTupleFailure::Value {
+                column,
+                expected,
+                text,
+                offset,
+                cause,
+            } => write!(
+                f,
+                "Failure to parse column {} (byte {}): {:?} is not a valid {}: {}",
+                column, offset, text, expected, cause
+            ),
+            ParseTupleFailure::UnterminatedEscape { offset } => {
+                write!(
+                    f,
+                    "Failure to parse a tuple: unterminated escape at byte {}",
+                    offset
+                )
+            }
+            ParseTupleFailure::WrongArity { expected, found } => write!(
+                f,
+                "Failure to parse a tuple: expected {} values, but found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl Error for ParseTupleFailure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseTupleFailure::Value { cause, .. } => Some(&**cause),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ParseTupleFailure>;
+
+/// The result of parsing a single value, where the error is the cause of the failure
+type ValueResult<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+pub fn parse_using_types<S: AsRef<str>, I: IntoIterator<Item = DataType>>(
+    to_parse: S,
+    iterator: I,
+) -> Result<Vec<Type>> {
+    parse_using_types_with(to_parse, iterator, &DateTimeParser::default())
+}
+
+/// Parses a tuple, using a specific parser for any dates and times within it
+pub fn parse_using_types_with<S: AsRef<str>, I: IntoIterator<Item = DataType>>(
+    to_parse: S,
+    iterator: I,
+    date_time_parser: &DateTimeParser,
+) -> Result<Vec<Type>> {
+    parse_using_types_helper(
+        to_parse.as_ref(),
+        iterator.into_iter().collect(),
+        date_time_parser,
+    )
+}
+
+fn parse_using_types_helper(
+    to_parse: &str,
+    iterator: Vec<DataType>,
+    date_time_parser: &DateTimeParser,
+) -> Result<Vec<Type>> {
+    let mut current = String::new();
+    let mut current_offset = 0;
+    let mut current_quoted = false;
+    let mut strings_vector = vec![];
+    let mut in_quote = false;
+    let mut chars_iterator = to_parse.char_indices();
+
+    while let Some((index, c)) = chars_iterator.next() {
+        if c == '"' {
+            in_quote = !in_quote;
+            current_quoted = true;
+        } else if c == '\\' {
+            let (_, next) = chars_iterator
+                .next()
+                .ok_or(ParseTupleFailure::UnterminatedEscape { offset: index })?;
+            current.push(next);
+        } else if c == '|' && !in_quote {
+            let string = std::mem::take(&mut current);
+            strings_vector.push((current_offset, string, current_quoted));
+            current_offset = index + 1;
+            current_quoted = false;
+        } else {
+            current.push(c);
+        }
+    }
+
+    if !current.trim().is_empty() {
+        strings_vector.push((current_offset, current, current_quoted));
+    }
+
+    if strings_vector.len() != iterator.len() {
+        return Err(ParseTupleFailure::WrongArity {
+            expected: iterator.len(),
+            found: strings_vector.len(),
+        });
+    }
+
+    let mut output = vec![];
+    for (column, (base_type, (offset, string, quoted))) in
+        iterator.into_iter().zip(strings_vector).enumerate()
+    {
+        // only a bare NULL is null, a quoted "NULL" is text that happens to read NULL
+        let parsed = match &base_type {
+            DataType::Optional(_) if !quoted && string == "NULL" => Ok(Type::Optional(None)),
+            _ => parse_type(&base_type, &string, date_time_parser),
+        };
+        match parsed {
+            Ok(created) => output.push(created),
+            Err(cause) => {
+                return Err(ParseTupleFailure::Value {
+                    column,
+                    expected: base_type,
+                    text: string,
+                    offset,
+                    cause,
+                })
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Decodes bytes that were encoded with [bytes_to_hex]
+///
+/// [bytes_to_hex]: crate::serialization::bytes_to_hex
+pub fn hex_to_bytes(string: &str) -> std::result::Result<Vec<u8>, InvalidValue> {
+    let digits = string
+        .strip_prefix(HEX_PREFIX)
+        .ok_or(InvalidValue::InvalidHex)?;
+    if digits.len() % 2 != 0 {
+        return Err(InvalidValue::InvalidHex);
+    }
+    let mut ret = Vec::with_capacity(digits.len() / 2);
+    for index in (0..digits.len()).step_by(2) {
+        let pair = digits
+            .get(index..index + 2)
+            .ok_or(InvalidValue::InvalidHex)?;
+        ret.push(u8::from_str_radix(pair, 16).map_err(|_| InvalidValue::InvalidHex)?);
+    }
+    Ok(ret)
+}
+
+fn parse_type(
+    data_type: &DataType,
+    string: &str,
+    date_time_parser: &DateTimeParser,
+) -> ValueResult<Type> {
+    let created: Type = match data_type {
+        DataType::Float => Numeric::Float(string.parse()?).into(),
+        DataType::Double => Numeric::Double(string.parse()?).into(),
+        DataType::SignedByte => Signed::Byte(string.parse()?).into(),
+        DataType::SignedShort => Signed::Short(string.parse()?).into(),
+        DataType::SignedInt => Signed::Int(string.parse()?).into(),
+        DataType::SignedLong => Signed::Long(string.parse()?).into(),
+        DataType::UnsignedByte => Unsigned::Byte(string.parse()?).into(),
+        DataType::UnsignedShort => Unsigned::Short(string.parse()?).into(),
+        DataType::UnsignedInt => Unsigned::Int(string.parse()?).into(),
+        DataType::UnsignedLong => Unsigned::Long(string.parse()?).into(),
+        DataType::Decimal { precision, scale } => {
+            Decimal::parse(string, *precision, *scale)?.into()
+        }
+        DataType::Char => Text::Char(string.parse()?).into(),
+        DataType::String(len) => {
+            if let Some(max_len) = len {
+                if (*max_len as usize) < string.len() {
+                    return Err(InvalidValue::TooLong {
+                        length: string.len(),
+                        max: *max_len as usize,
+                    }
+                    .into());
+                }
+            }
+            Text::String(string.to_string(), *len).into()
+        }
+        DataType::Binary => {
+            let bytes = hex_to_bytes(string)?;
+            if bytes.len() != 1 {
+                return Err(InvalidValue::NotSingleByte {
+                    length: bytes.len(),
+                }
+                .into());
+            }
+            Text::Binary(bytes[0]).into()
+        }
+        DataType::BinaryString(len) => {
+            let bytes = hex_to_bytes(string)?;
+            if (*len as usize) < bytes.len() {
+                return Err(InvalidValue::TooLong {
+                    length: bytes.len(),
+                    max: *len as usize,
+                }
+                .into());
+            }
+            Text::BinaryString(bytes, *len).into()
+        }
+        DataType::Blob => Text::Blob(hex_to_bytes(string)?).into(),
+        DataType::Date => Time::Date(date_time_parser.parse_date(string)?).into(),
+        DataType::DateTime => Time::DateTime(date_time_parser.parse_date_time(string)?).into(),
+        DataType::Timestamp => Time::Timestamp(date_time_parser.parse_timestamp(string)?).into(),
+        DataType::Year => Time::Year(string.parse()?).into(),
+        DataType::Interval => Time::Interval(string.parse()?).into(),
+        DataType::Boolean => Type::Boolean(string.parse()?),
+        DataType::Optional(inner_type) => {
+            let inner = parse_type(inner_type, string, date_time_parser)?;
+            Type::Optional(Some(Box::new(inner)))
+        }
+    };
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialization::serialize_values;
+    use crate::{Signed, Unsigned};
+
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let types = vec![
+            DataType::SignedByte,
+            DataType::UnsignedLong,
+            DataType::String(None),
+        ];
+        let input = vec!["3", "23241212332", "\"Hello World!\""].join("|");
+        let output = parse_using_types(input, types).unwrap();
+        assert_eq!(output[0], Signed::Byte(3).into());
+        assert_eq!(output[1], Unsigned::Long(23241212332).into());
+        assert_eq!(
+            output[2],
+            Text::String("Hello World!".to_string(), None).into()
+        );
+    }
+
+    #[test]
+    fn string_too_big() {
+        let types = vec![DataType::String(Some(1))];
+        let input = vec!["\"Hello World!\""].join("|");
+        parse_using_types(input, types).unwrap_err();
+    }
+
+    #[test]
+    fn nullable() {
+        let types = vec![
+            DataType::UnsignedInt.optional(),
+            DataType::UnsignedInt.optional(),
+        ];
+        let output = parse_using_types("NULL|3", types).unwrap();
+        assert_eq!(output[0], Type::Optional(None));
+        assert_eq!(
+            output[1],
+            Type::Optional(Some(Box::new(Unsigned::Int(3).into())))
+        );
+    }
+
+    #[test]
+    fn null_text_round_trip() {
+        round_trip(vec![Type::Optional(Some(Box::new(
+            Text::String("NULL".to_string(), None).into(),
+        )))]);
+        let types = vec![
+            DataType::String(None).optional(),
+            DataType::String(None).optional(),
+        ];
+        let output = parse_using_types("\"NULL\"|NULL", types).unwrap();
+        assert_ne!(output[0], Type::Optional(None));
+        assert_eq!(output[1], Type::Optional(None));
+    }
+
+    fn round_trip(values: Vec<Type>) {
+        let serialized = serialize_values(values.clone());
+        let types = values.iter().map(|v| DataType::of(v).unwrap());
+        let parsed = parse_using_types(&serialized, types).unwrap();
+        assert_eq!(parsed, values, "{} didn't round trip", serialized);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        round_trip(vec![
+            Text::Binary(b'|').into(),
+            Text::Binary(0xff).into(),
+            Text::BinaryString(b"a|b\"c\\d".to_vec(), 16).into(),
+            Text::Blob(vec![0x00, 0x9f, 0x92, 0x96, 0xc3, 0x28, b'|', b'"']).into(),
+            Text::Blob(vec![]).into(),
+        ]);
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        round_trip(vec![
+            Text::String("pipe | in the middle".to_string(), None).into(),
+            Text::String("\"quoted\" and \\back\\slashed\\".to_string(), None).into(),
+            Text::Char('"').into(),
+            Text::Char('\\').into(),
+            Text::Char('|').into(),
+        ]);
+    }
+
+    #[test]
+    fn decimal_round_trip() {
+        round_trip(vec![
+            Decimal::parse("-1234.5", 8, 3).unwrap().into(),
+            Decimal::parse("0.07", 2, 2).unwrap().into(),
+        ]);
+        let types = vec![DataType::Decimal {
+            precision: 4,
+            scale: 2,
+        }];
+        parse_using_types("1.234", types.clone()).unwrap_err();
+        parse_using_types("123.4", types).unwrap_err();
+    }
+
+    #[test]
+    fn times_round_trip() {
+        use chrono::{Local, NaiveDate, TimeZone, Utc};
+
+        round_trip(vec![
+            Time::Date(NaiveDate::from_ymd_opt(1999, 3, 7).unwrap()).into(),
+            Time::DateTime(Local.timestamp_opt(951782400, 123).unwrap()).into(),
+            Time::Timestamp(Utc.timestamp_opt(-86400, 999_999_999).unwrap()).into(),
+            Time::Year(2021).into(),
+            Time::Interval("P1MT2H".parse().unwrap()).into(),
+        ]);
+        parse_using_types("1999-03-7x", vec![DataType::Date]).unwrap_err();
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let types = vec![DataType::Blob];
+        parse_using_types("0x123", types.clone()).unwrap_err();
+        parse_using_types("0xzz", types.clone()).unwrap_err();
+        parse_using_types("ff", types).unwrap_err();
+        let types = vec![DataType::BinaryString(2)];
+        parse_using_types("0x0102", types.clone()).unwrap();
+        parse_using_types("0x010203", types).unwrap_err();
+        let types = vec![DataType::Binary];
+        parse_using_types("0x0102", types).unwrap_err();
+    }
+
+    #[test]
+    fn failure_details() {
+        let types = vec![DataType::UnsignedByte, DataType::SignedInt, DataType::Date];
+        let error = parse_using_types("1|\"two\"|2021-02-30", types.clone()).unwrap_err();
+        match &error {
+            ParseTupleFailure::Value {
+                column,
+                expected,
+                text,
+                offset,
+                ..
+            } => {
+                assert_eq!(*column, 1);
+                assert_eq!(expected, &DataType::SignedInt);
+                assert_eq!(text, "two");
+                assert_eq!(*offset, 2);
+            }
+            other => panic!("unexpected failure {:?}", other),
+        }
+        assert!(error.source().is_some());
+
+        let error = parse_using_types("1|2|2021-02-30", types.clone()).unwrap_err();
+        assert_eq!(error.column(), Some(2));
+        let cause = error.source().unwrap();
+        assert!(cause.is::<date_time::DateTimeParseError>());
+
+        match parse_using_types("1|2", types.clone()).unwrap_err() {
+            ParseTupleFailure::WrongArity { expected, found } => {
+                assert_eq!((expected, found), (3, 2));
+            }
+            other => panic!("unexpected failure {:?}", other),
+        }
+        match parse_using_types("1|2|\\", types).unwrap_err() {
+            ParseTupleFailure::UnterminatedEscape { offset } => assert_eq!(offset, 4),
+            other => panic!("unexpected failure {:?}", other),
+        }
+    }
+}
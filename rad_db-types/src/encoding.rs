@@ -0,0 +1,454 @@
+//! A compact, type-tagged binary encoding of values.
+//!
+//! Every value is written as a single tag byte followed by its payload. Fixed width numbers are
+//! written in little endian, and anything with a variable length (strings, byte strings, blobs) is
+//! prefixed by its length as an unsigned LEB128 integer. Because every value carries its own tag,
+//! decoding doesn't need to know the types of the relation ahead of time, and `NULL` values can be
+//! represented directly.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Utc};
+
+use crate::{Decimal, Interval, Numeric, Signed, Text, Time, Type, Unsigned};
+
+const TAG_NULL: u8 = 0x00;
+const TAG_SOME: u8 = 0x01;
+
+const TAG_FLOAT: u8 = 0x10;
+const TAG_DOUBLE: u8 = 0x11;
+const TAG_SIGNED_BYTE: u8 = 0x12;
+const TAG_SIGNED_SHORT: u8 = 0x13;
+const TAG_SIGNED_INT: u8 = 0x14;
+const TAG_SIGNED_LONG: u8 = 0x15;
+const TAG_UNSIGNED_BYTE: u8 = 0x16;
+const TAG_UNSIGNED_SHORT: u8 = 0x17;
+const TAG_UNSIGNED_INT: u8 = 0x18;
+const TAG_UNSIGNED_LONG: u8 = 0x19;
+const TAG_DECIMAL: u8 = 0x1a;
+
+const TAG_CHAR: u8 = 0x20;
+const TAG_STRING: u8 = 0x21;
+const TAG_BINARY: u8 = 0x22;
+const TAG_BINARY_STRING: u8 = 0x23;
+const TAG_BLOB: u8 = 0x24;
+
+const TAG_DATE: u8 = 0x30;
+const TAG_DATE_TIME: u8 = 0x31;
+const TAG_TIMESTAMP: u8 = 0x32;
+const TAG_YEAR: u8 = 0x33;
+const TAG_INTERVAL: u8 = 0x34;
+
+const TAG_BOOLEAN: u8 = 0x40;
+
+/// When a value couldn't be decoded from its binary representation
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a value
+    UnexpectedEnd,
+    /// The tag byte doesn't correspond to any type
+    UnknownTag(u8),
+    /// The payload of the value isn't valid for its type
+    InvalidValue(&'static str),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "Unexpected end of encoded value"),
+            DecodeError::UnknownTag(tag) => write!(f, "Unknown type tag {:#04x}", tag),
+            DecodeError::InvalidValue(reason) => write!(f, "Invalid encoded value: {}", reason),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+pub type Result<T> = std::result::Result<T, DecodeError>;
+
+/// Writes an unsigned LEB128 integer into the buffer
+pub fn write_length(buffer: &mut Vec<u8>, mut length: u64) {
+    loop {
+        let byte = (length & 0x7f) as u8;
+        length >>= 7;
+        if length == 0 {
+            buffer.push(byte);
+            return;
+        }
+        buffer.push(byte | 0x80);
+    }
+}
+
+/// Reads an unsigned LEB128 integer from the front of the input, advancing it
+pub fn read_length(input: &mut &[u8]) -> Result<u64> {
+    let mut ret = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = take_byte(input)?;
+        if shift >= 64 {
+            return Err(DecodeError::InvalidValue("length prefix is too long"));
+        }
+        ret |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(ret);
+        }
+        shift += 7;
+    }
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    write_length(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+/// Encodes a single value onto the end of the buffer
+pub fn encode_value(buffer: &mut Vec<u8>, value: &Type) {
+    match value {
+        Type::Numeric(n) => match n {
+            Numeric::Float(f) => {
+                buffer.push(TAG_FLOAT);
+                buffer.extend_from_slice(&f.to_le_bytes());
+            }
+            Numeric::Double(d) => {
+                buffer.push(TAG_DOUBLE);
+                buffer.extend_from_slice(&d.to_le_bytes());
+            }
+            Numeric::Signed(signed) => match signed {
+                Signed::Byte(b) => {
+                    buffer.push(TAG_SIGNED_BYTE);
+                    buffer.extend_from_slice(&b.to_le_bytes());
+                }
+                Signed::Short(s) => {
+                    buffer.push(TAG_SIGNED_SHORT);
+                    buffer.extend_from_slice(&s.to_le_bytes());
+                }
+                Signed::Int(i) => {
+                    buffer.push(TAG_SIGNED_INT);
+                    buffer.extend_from_slice(&i.to_le_bytes());
+                }
+                Signed::Long(l) => {
+                    buffer.push(TAG_SIGNED_LONG);
+                    buffer.extend_from_slice(&l.to_le_bytes());
+                }
+            },
+            Numeric::Unsigned(unsigned) => match unsigned {
+                Unsigned::Byte(b) => {
+                    buffer.push(TAG_UNSIGNED_BYTE);
+                    buffer.push(*b);
+                }
+                Unsigned::Short(s) => {
+                    buffer.push(TAG_UNSIGNED_SHORT);
+                    buffer.extend_from_slice(&s.to_le_bytes());
+                }
+                Unsigned::Int(i) => {
+                    buffer.push(TAG_UNSIGNED_INT);
+                    buffer.extend_from_slice(&i.to_le_bytes());
+                }
+                Unsigned::Long(l) => {
+                    buffer.push(TAG_UNSIGNED_LONG);
+                    buffer.extend_from_slice(&l.to_le_bytes());
+                }
+            },
+            Numeric::Decimal(d) => {
+                buffer.push(TAG_DECIMAL);
+                buffer.push(d.precision());
+                buffer.push(d.scale());
+                buffer.extend_from_slice(&d.unscaled().to_le_bytes());
+            }
+        },
+        Type::Text(t) => match t {
+            Text::Char(c) => {
+                buffer.push(TAG_CHAR);
+                buffer.extend_from_slice(&(*c as u32).to_le_bytes());
+            }
+            Text::String(s, max_len) => {
+                buffer.push(TAG_STRING);
+                match max_len {
+                    None => buffer.push(0),
+                    Some(max_len) => {
+                        buffer.push(1);
+                        buffer.extend_from_slice(&max_len.to_le_bytes());
+                    }
+                }
+                write_bytes(buffer, s.as_bytes());
+            }
+            Text::Binary(b) => {
+                buffer.push(TAG_BINARY);
+                buffer.push(*b);
+            }
+            Text::BinaryString(bytes, width) => {
+                buffer.push(TAG_BINARY_STRING);
+                buffer.extend_from_slice(&width.to_le_bytes());
+                write_bytes(buffer, bytes);
+            }
+            Text::Blob(blob) => {
+                buffer.push(TAG_BLOB);
+                write_bytes(buffer, blob);
+            }
+        },
+        Type::Time(t) => match t {
+            Time::Date(d) => {
+                buffer.push(TAG_DATE);
+                buffer.extend_from_slice(&d.num_days_from_ce().to_le_bytes());
+            }
+            Time::DateTime(d) => {
+                buffer.push(TAG_DATE_TIME);
+                buffer.extend_from_slice(&d.timestamp().to_le_bytes());
+                buffer.extend_from_slice(&d.timestamp_subsec_nanos().to_le_bytes());
+            }
+            Time::Timestamp(t) => {
+                buffer.push(TAG_TIMESTAMP);
+                buffer.extend_from_slice(&t.timestamp().to_le_bytes());
+                buffer.extend_from_slice(&t.timestamp_subsec_nanos().to_le_bytes());
+            }
+            Time::Year(y) => {
+                buffer.push(TAG_YEAR);
+                buffer.extend_from_slice(&y.to_le_bytes());
+            }
+            Time::Interval(interval) => {
+                buffer.push(TAG_INTERVAL);
+                buffer.extend_from_slice(&interval.get_months().to_le_bytes());
+                buffer.extend_from_slice(&interval.get_days().to_le_bytes());
+                buffer.extend_from_slice(&interval.get_nanos().to_le_bytes());
+            }
+        },
+        Type::Boolean(b) => {
+            buffer.push(TAG_BOOLEAN);
+            buffer.push(*b as u8);
+        }
+        Type::Optional(None) => {
+            buffer.push(TAG_NULL);
+        }
+        Type::Optional(Some(inner)) => {
+            buffer.push(TAG_SOME);
+            encode_value(buffer, inner);
+        }
+    }
+}
+
+/// Encodes a list of values, prefixed by the amount of values
+pub fn encode_values<'a, I: IntoIterator<Item = &'a Type>>(buffer: &mut Vec<u8>, values: I) {
+    let values: Vec<&Type> = values.into_iter().collect();
+    write_length(buffer, values.len() as u64);
+    for value in values {
+        encode_value(buffer, value);
+    }
+}
+
+fn take_byte(input: &mut &[u8]) -> Result<u8> {
+    let (first, rest) = input.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+    *input = rest;
+    Ok(*first)
+}
+
+fn take_bytes<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8]> {
+    if input.len() < count {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (taken, rest) = input.split_at(count);
+    *input = rest;
+    Ok(taken)
+}
+
+fn take_array<const N: usize>(input: &mut &[u8]) -> Result<[u8; N]> {
+    let mut ret = [0u8; N];
+    ret.copy_from_slice(take_bytes(input, N)?);
+    Ok(ret)
+}
+
+fn take_length_prefixed<'a>(input: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = read_length(input)?;
+    if len > input.len() as u64 {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    take_bytes(input, len as usize)
+}
+
+/// Decodes a single value from the front of the input, advancing it past the value
+pub fn decode_value(input: &mut &[u8]) -> Result<Type> {
+    let tag = take_byte(input)?;
+    let ret = match tag {
+        TAG_NULL => Type::Optional(None),
+        TAG_SOME => Type::Optional(Some(Box::new(decode_value(input)?))),
+        TAG_FLOAT => Numeric::Float(f32::from_le_bytes(take_array(input)?)).into(),
+        TAG_DOUBLE => Numeric::Double(f64::from_le_bytes(take_array(input)?)).into(),
+        TAG_SIGNED_BYTE => Signed::Byte(i8::from_le_bytes(take_array(input)?)).into(),
+        TAG_SIGNED_SHORT => Signed::Short(i16::from_le_bytes(take_array(input)?)).into(),
+        TAG_SIGNED_INT => Signed::Int(i32::from_le_bytes(take_array(input)?)).into(),
+        TAG_SIGNED_LONG => Signed::Long(i64::from_le_bytes(take_array(input)?)).into(),
+        TAG_UNSIGNED_BYTE => Unsigned::Byte(take_byte(input)?).into(),
+        TAG_UNSIGNED_SHORT => Unsigned::Short(u16::from_le_bytes(take_array(input)?)).into(),
+        TAG_UNSIGNED_INT => Unsigned::Int(u32::from_le_bytes(take_array(input)?)).into(),
+        TAG_UNSIGNED_LONG => Unsigned::Long(u64::from_le_bytes(take_array(input)?)).into(),
+        TAG_DECIMAL => {
+            let precision = take_byte(input)?;
+            let scale = take_byte(input)?;
+            let unscaled = i128::from_le_bytes(take_array(input)?);
+            Decimal::new(unscaled, precision, scale)
+                .map_err(|_| DecodeError::InvalidValue("invalid decimal"))?
+                .into()
+        }
+        TAG_CHAR => {
+            let code = u32::from_le_bytes(take_array(input)?);
+            let c = std::char::from_u32(code)
+                .ok_or(DecodeError::InvalidValue("not a unicode scalar value"))?;
+            Text::Char(c).into()
+        }
+        TAG_STRING => {
+            let max_len = match take_byte(input)? {
+                0 => None,
+                1 => Some(u16::from_le_bytes(take_array(input)?)),
+                _ => return Err(DecodeError::InvalidValue("invalid string length marker")),
+            };
+            let bytes = take_length_prefixed(input)?;
+            let string = String::from_utf8(bytes.to_vec())
+                .map_err(|_| DecodeError::InvalidValue("string is not valid utf-8"))?;
+            Text::String(string, max_len).into()
+        }
+        TAG_BINARY => Text::Binary(take_byte(input)?).into(),
+        TAG_BINARY_STRING => {
+            let width = u16::from_le_bytes(take_array(input)?);
+            let bytes = take_length_prefixed(input)?;
+            Text::BinaryString(bytes.to_vec(), width).into()
+        }
+        TAG_BLOB => Text::Blob(take_length_prefixed(input)?.to_vec()).into(),
+        TAG_DATE => {
+            let days = i32::from_le_bytes(take_array(input)?);
+            let date = NaiveDate::from_num_days_from_ce_opt(days)
+                .ok_or(DecodeError::InvalidValue("date out of range"))?;
+            Time::Date(date).into()
+        }
+        TAG_DATE_TIME => {
+            let seconds = i64::from_le_bytes(take_array(input)?);
+            let nanos = u32::from_le_bytes(take_array(input)?);
+            let date_time = Local
+                .timestamp_opt(seconds, nanos)
+                .single()
+                .ok_or(DecodeError::InvalidValue("date time out of range"))?;
+            Time::DateTime(date_time).into()
+        }
+        TAG_TIMESTAMP => {
+            let seconds = i64::from_le_bytes(take_array(input)?);
+            let nanos = u32::from_le_bytes(take_array(input)?);
+            let timestamp = Utc
+                .timestamp_opt(seconds, nanos)
+                .single()
+                .ok_or(DecodeError::InvalidValue("timestamp out of range"))?;
+            Time::Timestamp(timestamp).into()
+        }
+        TAG_YEAR => Time::Year(i32::from_le_bytes(take_array(input)?)).into(),
+        TAG_INTERVAL => {
+            let months = i32::from_le_bytes(take_array(input)?);
+            let days = i32::from_le_bytes(take_array(input)?);
+            let nanos = i64::from_le_bytes(take_array(input)?);
+            Time::Interval(Interval::new(months, days, nanos)).into()
+        }
+        TAG_BOOLEAN => match take_byte(input)? {
+            0 => Type::Boolean(false),
+            1 => Type::Boolean(true),
+            _ => return Err(DecodeError::InvalidValue("invalid boolean")),
+        },
+        unknown => return Err(DecodeError::UnknownTag(unknown)),
+    };
+    Ok(ret)
+}
+
+/// Decodes a list of values that was encoded with [encode_values], advancing the input past them
+///
+/// [encode_values]: self::encode_values
+pub fn decode_values(input: &mut &[u8]) -> Result<Vec<Type>> {
+    let count = read_length(input)?;
+    let mut ret = Vec::new();
+    for _ in 0..count {
+        ret.push(decode_value(input)?);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(values: Vec<Type>) {
+        let mut buffer = vec![];
+        encode_values(&mut buffer, &values);
+        let mut input = &*buffer;
+        let decoded = decode_values(&mut input).unwrap();
+        assert!(input.is_empty());
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn numeric_round_trip() {
+        round_trip(vec![
+            Numeric::Float(1.5).into(),
+            Numeric::Double(-3.25).into(),
+            Signed::Byte(-3).into(),
+            Signed::Short(-300).into(),
+            Signed::Int(i32::MIN).into(),
+            Signed::Long(i64::MAX).into(),
+            Unsigned::Byte(255).into(),
+            Unsigned::Short(65535).into(),
+            Unsigned::Int(7).into(),
+            Unsigned::Long(23241212332).into(),
+            Decimal::parse("-12345.678", 10, 4).unwrap().into(),
+        ]);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        round_trip(vec![
+            Text::Char('|').into(),
+            Text::String("Hello | \"World\"\n".to_string(), None).into(),
+            Text::String("short".to_string(), Some(8)).into(),
+            Text::Binary(0xff).into(),
+            Text::BinaryString(vec![0, 159, 146, 150], 4).into(),
+            Text::Blob(vec![0; 300]).into(),
+        ]);
+    }
+
+    #[test]
+    fn time_round_trip() {
+        round_trip(vec![
+            Time::Date(NaiveDate::from_ymd_opt(1999, 3, 7).unwrap()).into(),
+            Time::DateTime(Local.timestamp_opt(951782400, 123).unwrap()).into(),
+            Time::Timestamp(Utc.timestamp_opt(-86400, 999_999_999).unwrap()).into(),
+            Time::Year(2021).into(),
+            Time::Interval("P1Y2DT-3.25S".parse().unwrap()).into(),
+        ]);
+    }
+
+    #[test]
+    fn null_round_trip() {
+        round_trip(vec![
+            Type::Optional(None),
+            Type::Optional(Some(Box::new(Type::Boolean(true)))),
+            Type::Boolean(false),
+        ]);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let mut buffer = vec![];
+        encode_value(&mut buffer, &Type::from(23241212332u64));
+        buffer.pop();
+        assert_eq!(
+            decode_value(&mut &*buffer).unwrap_err(),
+            DecodeError::UnexpectedEnd
+        );
+        assert_eq!(
+            decode_value(&mut &[0xee][..]).unwrap_err(),
+            DecodeError::UnknownTag(0xee)
+        );
+    }
+
+    #[test]
+    fn lengths() {
+        for length in vec![0u64, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buffer = vec![];
+            write_length(&mut buffer, length);
+            assert_eq!(read_length(&mut &*buffer).unwrap(), length);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Spans of time
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use chrono::{DateTime, Days, Duration, Months, TimeZone};
+
+/// The amount of nanoseconds in a second
+pub const NANOS_PER_SECOND: i64 = 1_000_000_000;
+/// The amount of nanoseconds in a day
+pub const NANOS_PER_DAY: i64 = 24 * 60 * 60 * NANOS_PER_SECOND;
+/// The amount of days a month is treated as when intervals are compared
+pub const DAYS_PER_MONTH: i64 = 30;
+
+/// When an interval couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntervalParseError(String);
+
+impl Display for IntervalParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} is not an ISO-8601 duration", self.0)
+    }
+}
+
+impl Error for IntervalParseError {}
+
+/// A span of time, made up of months, days and nanoseconds. The parts are kept separate because
+/// months and days don't have a fixed length, so adding one month to January 31st gives the last
+/// day of February, and adding one day across a daylight savings change keeps the same wall clock
+/// time.
+///
+/// When intervals are compared with each other, a month is treated as 30 days and a day as 24
+/// hours, so `P1M` is equal to `P30D`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Interval {
+    months: i32,
+    days: i32,
+    nanos: i64,
+}
+
+impl Interval {
+    pub fn new(months: i32, days: i32, nanos: i64) -> Self {
+        Interval {
+            months,
+            days,
+            nanos,
+        }
+    }
+
+    /// An interval of some amount of months
+    pub fn months(months: i32) -> Self {
+        Self::new(months, 0, 0)
+    }
+
+    /// An interval of some amount of days
+    pub fn days(days: i32) -> Self {
+        Self::new(0, days, 0)
+    }
+
+    /// An interval of a fixed length of time
+    pub fn from_duration(duration: Duration) -> Option<Self> {
+        Some(Self::new(0, 0, duration.num_nanoseconds()?))
+    }
+
+    /// The months part of the interval
+    pub fn get_months(&self) -> i32 {
+        self.months
+    }
+
+    /// The days part of the interval
+    pub fn get_days(&self) -> i32 {
+        self.days
+    }
+
+    /// The nanoseconds part of the interval
+    pub fn get_nanos(&self) -> i64 {
+        self.nanos
+    }
+
+    /// The length of the interval in nanoseconds, with a month being 30 days
+    fn approximate_nanos(&self) -> i128 {
+        let days = self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128;
+        days * NANOS_PER_DAY as i128 + self.nanos as i128
+    }
+
+    pub fn checked_add(&self, other: &Interval) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.checked_add(other.months)?,
+            days: self.days.checked_add(other.days)?,
+            nanos: self.nanos.checked_add(other.nanos)?,
+        })
+    }
+
+    pub fn checked_neg(&self) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.checked_neg()?,
+            days: self.days.checked_neg()?,
+            nanos: self.nanos.checked_neg()?,
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Interval) -> Option<Interval> {
+        self.checked_add(&other.checked_neg()?)
+    }
+
+    /// Adds the interval to a point in time. The months are added first, then the days, and then
+    /// the rest of the time.
+    pub fn add_to<Tz: TimeZone>(&self, date_time: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let date_time = if self.months >= 0 {
+            date_time
+                .clone()
+                .checked_add_months(Months::new(self.months as u32))?
+        } else {
+            date_time
+                .clone()
+                .checked_sub_months(Months::new(self.months.unsigned_abs()))?
+        };
+        let date_time = if self.days >= 0 {
+            date_time.checked_add_days(Days::new(self.days as u64))?
+        } else {
+            date_time.checked_sub_days(Days::new(self.days.unsigned_abs() as u64))?
+        };
+        date_time.checked_add_signed(Duration::nanoseconds(self.nanos))
+    }
+
+    /// The interval between two points in time, as days and nanoseconds
+    pub fn between<Tz: TimeZone, Tz2: TimeZone>(
+        start: &DateTime<Tz>,
+        end: &DateTime<Tz2>,
+    ) -> Option<Self> {
+        let nanos = end.clone().signed_duration_since(start).num_nanoseconds()?;
+        let days = i32::try_from(nanos / NANOS_PER_DAY).ok()?;
+        Some(Interval::new(0, days, nanos % NANOS_PER_DAY))
+    }
+}
+
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.approximate_nanos() == other.approximate_nanos()
+    }
+}
+
+impl Eq for Interval {}
+
+impl Hash for Interval {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.approximate_nanos().hash(state)
+    }
+}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.approximate_nanos().cmp(&other.approximate_nanos())
+    }
+}
+
+impl Display for Interval {
+    /// Displays the interval as an ISO-8601 duration, such as `P1Y2M3DT4H5M6.5S`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.months == 0 && self.days == 0 && self.nanos == 0 {
+            return write!(f, "PT0S");
+        }
+        write!(f, "P")?;
+        let years = self.months / 12;
+        let months = self.months % 12;
+        if years != 0 {
+            write!(f, "{}Y", years)?;
+        }
+        if months != 0 {
+            write!(f, "{}M", months)?;
+        }
+        if self.days != 0 {
+            write!(f, "{}D", self.days)?;
+        }
+        if self.nanos != 0 {
+            write!(f, "T")?;
+            let sign = if self.nanos < 0 { "-" } else { "" };
+            let nanos = self.nanos.unsigned_abs();
+            let hours = nanos / (3600 * NANOS_PER_SECOND as u64);
+            let minutes = nanos / (60 * NANOS_PER_SECOND as u64) % 60;
+            let seconds = nanos / NANOS_PER_SECOND as u64 % 60;
+            let fraction = nanos % NANOS_PER_SECOND as u64;
+            if hours != 0 {
+                write!(f, "{}{}H", sign, hours)?;
+            }
+            if minutes != 0 {
+                write!(f, "{}{}M", sign, minutes)?;
+            }
+            if seconds != 0 || fraction != 0 {
+                write!(f, "{}{}", sign, seconds)?;
+                if fraction != 0 {
+                    let fraction = format!("{:09}", fraction);
+                    write!(f, ".{}", fraction.trim_end_matches('0'))?;
+                }
+                write!(f, "S")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a possibly signed, possibly fractional number of some unit into nanoseconds
+fn parse_nanos(number: &str, unit_nanos: i64) -> Option<i64> {
+    let (negative, digits) = match number.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, number),
+    };
+    let (integer, fraction) = match digits.find(['.', ',']) {
+        Some(index) => (&digits[..index], &digits[index + 1..]),
+        None => (digits, ""),
+    };
+    if integer.is_empty() || fraction.len() > 9 {
+        return None;
+    }
+    let integer: i64 = integer.parse().ok()?;
+    let mut nanos = integer.checked_mul(unit_nanos)?;
+    if !fraction.is_empty() {
+        // The fraction is scaled to billionths of the unit first, so it can't overflow
+        let billionths: i64 = format!("{:0<9}", fraction).parse().ok()?;
+        let fraction_nanos = billionths as i128 * unit_nanos as i128 / NANOS_PER_SECOND as i128;
+        nanos = nanos.checked_add(fraction_nanos as i64)?;
+    }
+    Some(if negative { -nanos } else { nanos })
+}
+
+impl FromStr for Interval {
+    type Err = IntervalParseError;
+
+    /// Parses an ISO-8601 duration, such as `P1Y2M3W4DT5H6M7.5S`. Every part can be negative, and
+    /// the whole duration can be negated by starting it with a `-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || IntervalParseError(s.to_string());
+        let trimmed = s.trim();
+        let (negative, rest) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let rest = rest
+            .strip_prefix('P')
+            .or_else(|| rest.strip_prefix('p'))
+            .ok_or_else(error)?;
+        if rest.is_empty() {
+            return Err(error());
+        }
+
+        let mut interval = Interval::default();
+        let mut in_time = false;
+        let mut time_parts = 0;
+        let mut number = String::new();
+        for c in rest.chars() {
+            let c = c.to_ascii_uppercase();
+            match c {
+                '0'..='9' | '-' | '.' | ',' => number.push(c),
+                'T' if !in_time && number.is_empty() => in_time = true,
+                unit => {
+                    if number.is_empty() {
+                        return Err(error());
+                    }
+                    let whole = || number.parse::<i32>().ok();
+                    let time = |unit_nanos| {
+                        parse_nanos(&number, unit_nanos).map(|nanos| Interval::new(0, 0, nanos))
+                    };
+                    let part = match (in_time, unit) {
+                        (false, 'Y') => whole()
+                            .and_then(|y| y.checked_mul(12))
+                            .map(Interval::months),
+                        (false, 'M') => whole().map(Interval::months),
+                        (false, 'W') => whole().and_then(|w| w.checked_mul(7)).map(Interval::days),
+                        (false, 'D') => whole().map(Interval::days),
+                        (true, 'H') => time(3600 * NANOS_PER_SECOND),
+                        (true, 'M') => time(60 * NANOS_PER_SECOND),
+                        (true, 'S') => time(NANOS_PER_SECOND),
+                        _ => None,
+                    };
+                    interval = part
+                        .and_then(|part| interval.checked_add(&part))
+                        .ok_or_else(error)?;
+                    if in_time {
+                        time_parts += 1;
+                    }
+                    number.clear();
+                }
+            }
+        }
+        // A `T` has to be followed by at least one time part
+        if !number.is_empty() || (in_time && time_parts == 0) {
+            return Err(error());
+        }
+        if negative {
+            interval = interval.checked_neg().ok_or_else(error)?;
+        }
+        Ok(interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn parse_and_display() {
+        let interval: Interval = "P1Y2M3DT4H5M6.5S".parse().unwrap();
+        assert_eq!(interval.get_months(), 14);
+        assert_eq!(interval.get_days(), 3);
+        assert_eq!(
+            interval.get_nanos(),
+            (4 * 3600 + 5 * 60 + 6) * NANOS_PER_SECOND + NANOS_PER_SECOND / 2
+        );
+        assert_eq!(interval.to_string(), "P1Y2M3DT4H5M6.5S");
+        assert_eq!("P2W".parse::<Interval>().unwrap(), Interval::days(14));
+        assert_eq!("-P1M".parse::<Interval>().unwrap().get_months(), -1);
+        assert_eq!(Interval::default().to_string(), "PT0S");
+        assert_eq!(
+            "PT-90S".parse::<Interval>().unwrap().to_string(),
+            "PT-1M-30S"
+        );
+
+        assert!("P".parse::<Interval>().is_err());
+        assert!("1D".parse::<Interval>().is_err());
+        assert!("P1H".parse::<Interval>().is_err());
+        assert!("P1.5D".parse::<Interval>().is_err());
+        assert!("PT".parse::<Interval>().is_err());
+        assert!("P1DT".parse::<Interval>().is_err());
+        assert_eq!(
+            "PT1.123456789H".parse::<Interval>().unwrap().get_nanos(),
+            3600 * NANOS_PER_SECOND + 123_456_789 * 3600
+        );
+        assert_eq!(
+            "PT-0.5M".parse::<Interval>().unwrap().get_nanos(),
+            -30 * NANOS_PER_SECOND
+        );
+    }
+
+    #[test]
+    fn compare() {
+        assert_eq!(Interval::months(1), Interval::days(30));
+        assert!(Interval::days(1) < "PT25H".parse().unwrap());
+        assert!(Interval::months(-1) < Interval::default());
+    }
+
+    #[test]
+    fn calendar_arithmetic() {
+        let start = Utc.with_ymd_and_hms(2021, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(
+            Interval::months(1).add_to(&start),
+            Some(Utc.with_ymd_and_hms(2021, 2, 28, 12, 0, 0).unwrap())
+        );
+        let interval: Interval = "P1DT1H".parse().unwrap();
+        let end = interval.add_to(&start).unwrap();
+        assert_eq!(end, Utc.with_ymd_and_hms(2021, 2, 1, 13, 0, 0).unwrap());
+        assert_eq!(Interval::between(&start, &end), Some(interval));
+        assert_eq!(
+            Interval::between(&end, &start).unwrap().to_string(),
+            "P-1DT-1H"
+        );
+    }
+}
@@ -1,43 +1,128 @@
 //! This crate contains all of the types that can be used in the RadDB program. It establishes
 //! all relevant traits as well.
 
-use chrono::{Date, DateTime, Local, Utc};
-use std::cmp::min;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use std::cmp::{min, Ordering};
 use std::convert::{TryFrom, TryInto};
 use std::ffi::CString;
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::num::NonZeroU8;
 
+pub use data_type::DataType;
+pub use decimal::Decimal;
+pub use interval::Interval;
+
+pub mod arithmetic;
+pub mod collation;
+pub mod conversion;
+pub mod data_type;
+pub mod decimal;
 pub mod deserialization;
+pub mod encoding;
+pub mod interval;
 pub mod serialization;
+#[cfg(feature = "serde")]
+mod serde_support;
 
 /// Values and Types are equivalent!
 pub type Value = Type;
 
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+/// A number. Numbers of different variants are never equal, and are ordered by variant first.
+///
+/// Floating point numbers are compared and hashed canonically, so that `-0.0` is the same as
+/// `0.0`, and every `NaN` is the same as every other `NaN` and is greater than every other number.
+/// This lets floating point values be used within keys and sorted.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Numeric {
-    Float(f32),
-    Double(f64),
+    Float(#[cfg_attr(feature = "serde", serde(with = "serde_support::float"))] f32),
+    Double(#[cfg_attr(feature = "serde", serde(with = "serde_support::float"))] f64),
     Signed(Signed),
     Unsigned(Unsigned),
+    Decimal(Decimal),
+}
+
+/// Removes the sign from zero, and replaces all NaNs with the same NaN
+fn canonical_f32(f: f32) -> f32 {
+    if f.is_nan() {
+        f32::NAN
+    } else if f == 0.0 {
+        0.0
+    } else {
+        f
+    }
+}
+
+/// Removes the sign from zero, and replaces all NaNs with the same NaN
+fn canonical_f64(d: f64) -> f64 {
+    if d.is_nan() {
+        f64::NAN
+    } else if d == 0.0 {
+        0.0
+    } else {
+        d
+    }
+}
+
+impl Numeric {
+    /// The position of the variant in the order of numerics
+    fn rank(&self) -> u8 {
+        match self {
+            Numeric::Float(_) => 0,
+            Numeric::Double(_) => 1,
+            Numeric::Signed(_) => 2,
+            Numeric::Unsigned(_) => 3,
+            Numeric::Decimal(_) => 4,
+        }
+    }
+}
+
+impl PartialEq for Numeric {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
 impl Eq for Numeric {}
 
+impl PartialOrd for Numeric {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Numeric {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Numeric::Float(self_f), Numeric::Float(other_f)) => {
+                canonical_f32(*self_f).total_cmp(&canonical_f32(*other_f))
+            }
+            (Numeric::Double(self_d), Numeric::Double(other_d)) => {
+                canonical_f64(*self_d).total_cmp(&canonical_f64(*other_d))
+            }
+            (Numeric::Signed(self_n), Numeric::Signed(other_n)) => self_n.cmp(other_n),
+            (Numeric::Unsigned(self_n), Numeric::Unsigned(other_n)) => self_n.cmp(other_n),
+            (Numeric::Decimal(self_d), Numeric::Decimal(other_d)) => self_d.cmp(other_d),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
 impl Hash for Numeric {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
-            Numeric::Float(_) | Numeric::Double(_) => {
-                panic!("Can't hash on floating point numbers")
-            }
+            Numeric::Float(f) => canonical_f32(*f).to_bits().hash(state),
+            Numeric::Double(d) => canonical_f64(*d).to_bits().hash(state),
             Numeric::Signed(s) => s.hash(state),
             Numeric::Unsigned(o) => o.hash(state),
+            Numeric::Decimal(d) => d.hash(state),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Signed {
     Byte(i8),
     Short(i16),
@@ -46,6 +131,7 @@ pub enum Signed {
 }
 
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Unsigned {
     Byte(u8),
     Short(u16),
@@ -53,7 +139,8 @@ pub enum Unsigned {
     Long(u64),
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Text {
     Char(char),
     String(String, Option<u16>),
@@ -62,16 +149,23 @@ pub enum Text {
     Blob(Vec<u8>),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Time {
-    Date(Date<Local>),
-    DateTime(DateTime<Local>),
-    Timestamp(DateTime<Utc>),
+    Date(#[cfg_attr(feature = "serde", serde(with = "serde_support::date"))] NaiveDate),
+    DateTime(
+        #[cfg_attr(feature = "serde", serde(with = "serde_support::date_time"))] DateTime<Local>,
+    ),
+    Timestamp(
+        #[cfg_attr(feature = "serde", serde(with = "serde_support::date_time"))] DateTime<Utc>,
+    ),
     Year(i32),
+    Interval(Interval),
 }
 
 /// Base type for all data types
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Numeric(Numeric),
     Text(Text),
@@ -98,6 +192,12 @@ impl From<Unsigned> for Type {
     }
 }
 
+impl From<Decimal> for Type {
+    fn from(d: Decimal) -> Self {
+        Numeric::Decimal(d).into()
+    }
+}
+
 impl From<Text> for Type {
     fn from(t: Text) -> Self {
         Type::Text(t)
@@ -204,6 +304,7 @@ impl Display for Numeric {
             Numeric::Double(d) => d,
             Numeric::Signed(s) => s,
             Numeric::Unsigned(u) => u,
+            Numeric::Decimal(d) => d,
         };
         write!(f, "{}", disp)
     }
@@ -233,6 +334,7 @@ impl Display for Time {
             Time::DateTime(datetime) => datetime,
             Time::Timestamp(t) => t,
             Time::Year(yr) => yr,
+            Time::Interval(interval) => interval,
         };
         write!(f, "{}", disp)
     }
@@ -287,6 +389,9 @@ impl SameType for Numeric {
             (Numeric::Unsigned(self_n), Numeric::Unsigned(other_n)) => self_n.same_type(other_n),
             (Numeric::Double(_), Numeric::Double(_)) => true,
             (Numeric::Float(_), Numeric::Float(_)) => true,
+            (Numeric::Decimal(self_d), Numeric::Decimal(other_d)) => {
+                self_d.precision() == other_d.precision() && self_d.scale() == other_d.scale()
+            }
             _ => false,
         }
     }
@@ -312,6 +417,7 @@ impl SameType for Time {
             (Time::DateTime(_), Time::DateTime(_)) => true,
             (Time::Timestamp(_), Time::Timestamp(_)) => true,
             (Time::Year(_), Time::Year(_)) => true,
+            (Time::Interval(_), Time::Interval(_)) => true,
             _ => false,
         }
     }
@@ -508,7 +614,6 @@ impl TryFrom<Value> for String {
 mod tests {
     use super::*;
     use crate::serialization::serialize_values;
-    use chrono::TimeZone;
 
     #[test]
     fn conversion() {
@@ -523,7 +628,7 @@ mod tests {
 
     #[test]
     fn date() {
-        let date = Time::Date(Local.ymd(1999, 3, 7));
+        let date = Time::Date(NaiveDate::from_ymd_opt(1999, 3, 7).unwrap());
         println!("{}", date);
     }
 
@@ -536,12 +641,59 @@ mod tests {
         ];
         let to_check = types.clone();
         let serialized = serialize_values(types);
-        let types: Vec<Type> = vec![
-            Signed::Byte(0).into(),
-            Unsigned::Long(0).into(),
-            Text::String(String::new(), None).into(),
+        let types = vec![
+            DataType::SignedByte,
+            DataType::UnsignedLong,
+            DataType::String(None),
         ];
         let deserialized = deserialization::parse_using_types(serialized, types).unwrap();
         assert_eq!(deserialized, to_check);
     }
+
+    #[test]
+    fn float_hash_and_order() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |n: Numeric| {
+            let mut hasher = DefaultHasher::new();
+            n.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(Numeric::Double(0.0), Numeric::Double(-0.0));
+        assert_eq!(hash(Numeric::Double(0.0)), hash(Numeric::Double(-0.0)));
+        assert_eq!(Numeric::Float(f32::NAN), Numeric::Float(-f32::NAN));
+        assert_eq!(
+            hash(Numeric::Float(f32::NAN)),
+            hash(Numeric::Float(-f32::NAN))
+        );
+        assert_ne!(Numeric::Float(1.0), Numeric::Double(1.0));
+
+        let mut values = vec![
+            Numeric::Double(f64::NAN),
+            Numeric::Double(1.5),
+            Numeric::Double(f64::INFINITY),
+            Numeric::Double(-0.0),
+            Numeric::Double(f64::NEG_INFINITY),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                Numeric::Double(f64::NEG_INFINITY),
+                Numeric::Double(0.0),
+                Numeric::Double(1.5),
+                Numeric::Double(f64::INFINITY),
+                Numeric::Double(f64::NAN),
+            ]
+        );
+
+        let mut types: Vec<Type> = vec![
+            Type::from(Numeric::Double(2.0)),
+            Type::Optional(None),
+            Type::from(Numeric::Double(-1.0)),
+        ];
+        types.sort();
+        assert_eq!(types[0], Type::from(Numeric::Double(-1.0)));
+        assert_eq!(types[2], Type::Optional(None));
+    }
 }
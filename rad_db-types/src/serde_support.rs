@@ -0,0 +1,251 @@
+//! Implementations of [serde::Serialize] and [serde::Deserialize] for the values that can't derive
+//! them, enabled by the `serde` feature.
+//!
+//! Values are represented by what they mean rather than how they're stored, so that the
+//! representation stays stable. Dates are written as `YYYY-MM-DD`, date times and timestamps as
+//! RFC 3339, intervals as ISO-8601 durations, and decimals as their digits along with their
+//! precision and scale. Floating point numbers that aren't finite are written as `"NaN"`, `"inf"`
+//! and `"-inf"`, as most formats have no numbers for them.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Decimal, Interval};
+
+#[derive(Serialize, Deserialize)]
+struct DecimalRepr {
+    value: String,
+    precision: u8,
+    scale: u8,
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DecimalRepr {
+            value: self.to_string(),
+            precision: self.precision(),
+            scale: self.scale(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = DecimalRepr::deserialize(deserializer)?;
+        Decimal::parse(&repr.value, repr.precision, repr.scale).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Floats and doubles, for use with `#[serde(with = "...")]`
+pub(crate) mod float {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    use serde::de::{DeserializeOwned, Error};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// A floating point number, which may be NaN or infinite
+    pub trait Float: Copy + Display + FromStr + Serialize + DeserializeOwned {
+        fn is_finite(self) -> bool;
+    }
+
+    impl Float for f32 {
+        fn is_finite(self) -> bool {
+            f32::is_finite(self)
+        }
+    }
+
+    impl Float for f64 {
+        fn is_finite(self) -> bool {
+            f64::is_finite(self)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FloatRepr<F> {
+        Number(F),
+        Text(String),
+    }
+
+    pub fn serialize<F: Float, S: Serializer>(float: &F, serializer: S) -> Result<S::Ok, S::Error> {
+        if float.is_finite() {
+            float.serialize(serializer)
+        } else {
+            serializer.collect_str(float)
+        }
+    }
+
+    pub fn deserialize<'de, F: Float, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<F, D::Error> {
+        match FloatRepr::<F>::deserialize(deserializer)? {
+            FloatRepr::Number(float) => Ok(float),
+            FloatRepr::Text(text) => match text.as_str() {
+                "NaN" | "inf" | "-inf" => text
+                    .parse()
+                    .map_err(|_| D::Error::custom(format!("invalid float {}", text))),
+                _ => Err(D::Error::custom(format!("invalid float {}", text))),
+            },
+        }
+    }
+}
+
+/// Dates, for use with `#[serde(with = "...")]`
+pub(crate) mod date {
+    use chrono::NaiveDate;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::deserialization::date_time::{format_date, DateTimeParser};
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_date(date))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        DateTimeParser::new()
+            .parse_date(&text)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Date times and timestamps, for use with `#[serde(with = "...")]`
+pub(crate) mod date_time {
+    use chrono::{DateTime, Local, TimeZone, Utc};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::deserialization::date_time::{format_date_time, DateTimeParser};
+
+    /// A time zone that date times can be deserialized into
+    pub trait ParseZone: TimeZone {
+        fn parse(text: &str) -> crate::deserialization::date_time::Result<DateTime<Self>>;
+    }
+
+    impl ParseZone for Local {
+        fn parse(text: &str) -> crate::deserialization::date_time::Result<DateTime<Self>> {
+            DateTimeParser::new().parse_date_time(text)
+        }
+    }
+
+    impl ParseZone for Utc {
+        fn parse(text: &str) -> crate::deserialization::date_time::Result<DateTime<Self>> {
+            DateTimeParser::new().parse_timestamp(text)
+        }
+    }
+
+    pub fn serialize<Tz: TimeZone, S: Serializer>(
+        date_time: &DateTime<Tz>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        serializer.serialize_str(&format_date_time(date_time))
+    }
+
+    pub fn deserialize<'de, Tz: ParseZone, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Tz>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Tz::parse(&text).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, TimeZone, Utc};
+
+    use crate::{DataType, Numeric, Signed, Text, Time, Type, Unsigned};
+
+    use super::*;
+
+    fn round_trip(value: Type) {
+        let json = serde_json::to_string(&value).unwrap();
+        let parsed: Type = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, value, "{} didn't round trip", json);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        round_trip(Signed::Byte(-3).into());
+        round_trip(Unsigned::Long(u64::MAX).into());
+        round_trip(Numeric::Double(2.5).into());
+        round_trip(Numeric::Float(-0.25).into());
+        round_trip(Numeric::Float(f32::NAN).into());
+        round_trip(Numeric::Float(f32::NEG_INFINITY).into());
+        round_trip(Numeric::Double(f64::NAN).into());
+        round_trip(Numeric::Double(f64::INFINITY).into());
+        round_trip(Numeric::Double(f64::NEG_INFINITY).into());
+        round_trip(Decimal::parse("-12.30", 6, 2).unwrap().into());
+        round_trip(Text::String("a \"quote\"".to_string(), Some(20)).into());
+        round_trip(Text::Char('|').into());
+        round_trip(Text::BinaryString(vec![0, 1, 255], 4).into());
+        round_trip(Text::Blob(vec![]).into());
+        round_trip(Time::Date(NaiveDate::from_ymd_opt(2020, 2, 29).unwrap()).into());
+        round_trip(Time::DateTime(Local.timestamp_opt(1_000_000_000, 5).unwrap()).into());
+        round_trip(Time::Timestamp(Utc.timestamp_opt(-1, 0).unwrap()).into());
+        round_trip(Time::Year(1999).into());
+        round_trip(Time::Interval("P1Y2DT3.25S".parse().unwrap()).into());
+        round_trip(Type::Boolean(true));
+        round_trip(Type::Optional(None));
+        round_trip(Type::Optional(Some(Box::new(Type::from(7u16)))));
+    }
+
+    #[test]
+    fn stable_representation() {
+        assert_eq!(
+            serde_json::to_string(&Type::from(3u8)).unwrap(),
+            r#"{"Numeric":{"Unsigned":{"Byte":3}}}"#
+        );
+        let decimal: Type = Decimal::parse("1.5", 4, 2).unwrap().into();
+        assert_eq!(
+            serde_json::to_string(&decimal).unwrap(),
+            r#"{"Numeric":{"Decimal":{"value":"1.50","precision":4,"scale":2}}}"#
+        );
+        let timestamp: Type =
+            Time::Timestamp(Utc.with_ymd_and_hms(2021, 3, 4, 5, 6, 7).unwrap()).into();
+        assert_eq!(
+            serde_json::to_string(&timestamp).unwrap(),
+            r#"{"Time":{"Timestamp":"2021-03-04T05:06:07Z"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Type::from(Numeric::Double(f64::INFINITY))).unwrap(),
+            r#"{"Numeric":{"Double":"inf"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Type::from(Numeric::Float(f32::NAN))).unwrap(),
+            r#"{"Numeric":{"Float":"NaN"}}"#
+        );
+        let data_type = DataType::String(Some(8)).optional();
+        let json = serde_json::to_string(&data_type).unwrap();
+        assert_eq!(json, r#"{"Optional":{"String":8}}"#);
+        assert_eq!(serde_json::from_str::<DataType>(&json).unwrap(), data_type);
+    }
+
+    #[test]
+    fn invalid_values() {
+        serde_json::from_str::<Type>(r#"{"Time":{"Date":"2021-02-30"}}"#).unwrap_err();
+        serde_json::from_str::<Type>(r#"{"Time":{"Interval":"1D"}}"#).unwrap_err();
+        serde_json::from_str::<Type>(r#"{"Numeric":{"Double":"1.5"}}"#).unwrap_err();
+        serde_json::from_str::<Decimal>(r#"{"value":"123.4","precision":4,"scale":2}"#)
+            .unwrap_err();
+    }
+}
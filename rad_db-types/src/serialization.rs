@@ -1,28 +1,58 @@
-use crate::{Text, Type};
+use crate::deserialization::date_time::{format_date, format_date_time};
+use crate::{Text, Time, Type};
+
+/// The prefix of a hex encoded byte value
+pub const HEX_PREFIX: &str = "0x";
+
+/// Encodes bytes as `0x` followed by two lowercase hex digits per byte
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    let mut ret = String::with_capacity(HEX_PREFIX.len() + bytes.len() * 2);
+    ret += HEX_PREFIX;
+    for byte in bytes {
+        ret += &format!("{:02x}", byte);
+    }
+    ret
+}
+
+/// Surrounds text with quotes, escaping any quotes and backslashes inside of it
+fn quote(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len() + 2);
+    ret.push('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret.push('"');
+    ret
+}
+
+/// Serializes a single value so that it can be parsed back with its type
+pub fn serialize_value(value: Type) -> String {
+    match value {
+        Type::Text(text) => match text {
+            Text::Char(c) => quote(&c.to_string()),
+            Text::String(s, _) => quote(&s),
+            Text::Binary(b) => bytes_to_hex(&[b]),
+            Text::BinaryString(bytes, _) => bytes_to_hex(&bytes),
+            Text::Blob(blob) => bytes_to_hex(&blob),
+        },
+        Type::Time(time) => match time {
+            Time::Date(date) => format_date(&date),
+            Time::DateTime(date_time) => format_date_time(&date_time),
+            Time::Timestamp(timestamp) => format_date_time(&timestamp),
+            rest => rest.to_string(),
+        },
+        Type::Optional(Some(inner)) => serialize_value(*inner),
+        rest => rest.to_string(),
+    }
+}
 
 pub fn serialize_values<I: IntoIterator<Item = Type>>(values: I) -> String {
     let vec = values
         .into_iter()
-        .map(|v: Type| match v {
-            Type::Text(text) => match text {
-                Text::Char(c) => {
-                    format!("\"{}\"", c)
-                }
-                Text::String(s, _) => {
-                    format!("\"{}\"", s)
-                }
-                Text::Binary(_) => {
-                    unimplemented!()
-                }
-                Text::BinaryString(_, _) => {
-                    unimplemented!()
-                }
-                Text::Blob(_) => {
-                    unimplemented!()
-                }
-            },
-            rest => rest.to_string(),
-        })
+        .map(serialize_value)
         .collect::<Vec<String>>();
     vec.join("|")
 }